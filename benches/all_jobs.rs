@@ -0,0 +1,38 @@
+//Wall time of Installed::all over a few hundred entries, read on one thread and on every core.
+//Run with `cargo bench --bench all_jobs -- [ENTRIES]`.
+use std::{env, fs, num::NonZeroUsize, process, thread, time::{Duration, Instant}};
+use tal::apps::Installed;
+
+const ROUNDS: u32 = 20;
+
+fn time(installed: &Installed) -> (usize, Duration) {
+	let start = Instant::now();
+	let mut listed = 0;
+	for _ in 0..ROUNDS {
+		listed = installed.all().expect("scan failed").len();
+	}
+	(listed, start.elapsed() / ROUNDS)
+}
+
+fn main() {
+	let entries: usize = env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(500);
+	let dir = env::temp_dir().join(format!("tal-bench-jobs-{}", process::id()));
+	let (user, system) = (dir.join("user"), dir.join("system"));
+	fs::create_dir_all(&user).expect("failed to create the user dir");
+	fs::create_dir_all(&system).expect("failed to create the system dir");
+	for i in 0..entries {
+		let contents = format!("[Desktop Entry]\nType=Application\nName=App {i}\nName[de]=Anwendung {i}\nComment=Benchmark entry {i}\nExec=app-{i} %U\nCategories=Utility;\n\n[Desktop Action new]\nName=New\nExec=app-{i} --new\n");
+		fs::write(system.join(format!("app-{i}.desktop")), &contents).expect("failed to write an entry");
+		if i % 10 == 0 {
+			fs::write(user.join(format!("app-{i}.desktop")), &contents).expect("failed to write an entry");
+		}
+	}
+
+	let cores = thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
+	let installed = |jobs| Installed::from_paths([user.clone(), system.clone()]).with_jobs(jobs);
+	let (listed, serial) = time(&installed(NonZeroUsize::MIN));
+	let (_, parallel) = time(&installed(cores));
+	println!("{listed} entries listed, 1 job {serial:?}, {cores} jobs {parallel:?}");
+
+	let _ = fs::remove_dir_all(dir);
+}
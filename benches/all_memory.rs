@@ -0,0 +1,58 @@
+//Peak heap use and wall time of Installed::all over a directory with thousands of entries.
+//Run with `cargo bench --bench all_memory -- [ENTRIES]`.
+use std::{alloc::{GlobalAlloc, Layout, System}, env, fs, process, sync::atomic::{AtomicUsize, Ordering}, time::Instant};
+use tal::apps::Installed;
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let ptr = unsafe { System.alloc(layout) };
+		if !ptr.is_null() {
+			let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+			PEAK.fetch_max(current, Ordering::Relaxed);
+		}
+		ptr
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		unsafe { System.dealloc(ptr, layout) };
+		CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn main() {
+	let entries: usize = env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(10_000);
+	let dir = env::temp_dir().join(format!("tal-bench-{}", process::id()));
+	let (user, system) = (dir.join("user"), dir.join("system"));
+	fs::create_dir_all(&user).expect("failed to create the user dir");
+	fs::create_dir_all(&system).expect("failed to create the system dir");
+	for i in 0..entries {
+		let contents = format!("[Desktop Entry]\nType=Application\nName=App {i}\nComment=Benchmark entry {i}\nExec=app-{i} %U\nCategories=Utility;\n\n[Desktop Action new]\nName=New\nExec=app-{i} --new\n");
+		fs::write(system.join(format!("app-{i}.desktop")), &contents).expect("failed to write an entry");
+		//Every tenth system entry is shadowed by a user one
+		if i % 10 == 0 {
+			fs::write(user.join(format!("app-{i}.desktop")), &contents).expect("failed to write an entry");
+		}
+	}
+
+	let installed = Installed::from_paths([user, system]);
+	let baseline = CURRENT.load(Ordering::Relaxed);
+	PEAK.store(baseline, Ordering::Relaxed);
+	let start = Instant::now();
+	let listed = installed.all().expect("scan failed");
+	let elapsed = start.elapsed();
+	let peak = PEAK.load(Ordering::Relaxed) - baseline;
+	let retained = CURRENT.load(Ordering::Relaxed) - baseline;
+	println!("{} entries listed from {} files in {elapsed:?}", listed.len(), entries + entries.div_ceil(10));
+	println!("peak heap {} KiB, {} KiB held by the result", peak / 1024, retained / 1024);
+
+	drop(listed);
+	let _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,11 @@
+//Lists the entries of the directories given as arguments: cargo run --example from_paths -- ~/.local/share/applications
+use std::{env, path::PathBuf};
+use tal::apps::Installed;
+
+fn main() {
+	let installed = Installed::from_paths(env::args_os().skip(1).map(PathBuf::from));
+	match installed.all() {
+		Ok(entries) => entries.into_iter().for_each(|app| println!("{}\t{}\t{}", app.id, app.name, app.exec)),
+		Err(e) => eprintln!("{e}"),
+	}
+}
@@ -1,72 +1,638 @@
-use std::{collections::HashMap, env, fmt, fs::{self, ReadDir}, io::Error, path::PathBuf, process::{Command, Stdio}};
+use std::{borrow::Cow, collections::{HashMap, HashSet}, env, ffi::OsStr, fmt, fs::{self, ReadDir}, hash::{Hash, Hasher}, io::{self, Error, ErrorKind}, num::NonZeroUsize, path::{Path, PathBuf}, os::unix::process::{CommandExt, ExitStatusExt}, process::{Child, Command, ExitStatus, Stdio}, sync::{Mutex, OnceLock, atomic::{AtomicUsize, Ordering}}, thread, time::SystemTime};
 use thiserror::Error;
 
+use crate::cache::{CachedEntry, CachedFile, EntryCache};
+use crate::category::Tree;
+use crate::exec::{IoClass, UnterminatedQuote, exec_program, exec_split, executable_in, exec_tokenize, expand_entry_codes, expand_invocations, file_uri, priority_wrap, shell_quote, shell_quote_program, shell_wrap, strip_field_codes, which};
+use crate::fold;
+use crate::fuzzy::{Scorer, Subsequence};
+use crate::history::History;
+use crate::icon;
+use crate::json::Json;
+use crate::locale::Locale;
+use crate::render::{self, Format, Renderer};
+use crate::startup::StartupId;
+use crate::terminal::{self, Origin, Terminal};
+use crate::xdg;
+
 type Actions = HashMap<String, IniAction<String>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct IniAction<T: AsRef<str>> {
 	pub name: Option<T>,
 	pub exec: Option<T>,
 	pub terminal: Option<bool>,
+	/// Overrides the entry's Path
+	pub path: Option<T>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Ini {
+	pub id: String,
 	pub name: String,
+	pub untranslated_name: Option<String>,
+	pub generic_name: Option<String>,
+	pub comment: Option<String>,
+	pub keywords: Vec<String>,
 	pub exec: String,
 	pub terminal: bool,
 	pub actions: Actions,
+	pub categories: Vec<String>,
+	pub no_display: bool,
+	pub hidden: bool,
+	pub only_show_in: Vec<String>,
+	pub not_show_in: Vec<String>,
+	pub autostart_enabled: Option<bool>,
+	pub startup_wm_class: Option<String>,
+	pub icon: Option<String>,
+	pub mime_types: Vec<String>,
+	/// The desktop file this entry was read from
+	pub file: Option<PathBuf>,
+	/// Launched over D-Bus first, Exec is only the fallback and may be empty
+	pub dbus_activatable: bool,
+	/// Modification time of `file` when it was read
+	pub modified: Option<SystemTime>,
+	/// Gets a `$DESKTOP_STARTUP_ID` when launched, see [`StartupId`]
+	pub startup_notify: bool,
+	/// Program that has to be installed for the entry to be listed
+	pub try_exec: Option<String>,
+	/// Environment variables set for the launched processes, from the tal specific `X-Tal-Env=KEY=VALUE;` key
+	pub env: Vec<(String, String)>,
+	/// Working directory of the launched processes, from Path=
+	pub path: Option<PathBuf>,
+	/// Flatpak application ID of an exported entry, from X-Flatpak=
+	pub flatpak: Option<String>,
 }
 
 #[derive(Debug, Error)]
 pub enum RunError {
-	#[error("Failed to get system applications.")]
-	System,
-	#[error("Failed to get user applications.")]
-	User,
-	#[error("Failed to get flatpak applications.")]
-	Flatpak,
+	#[error(transparent)]
+	Read(#[from] ReadError),
 	#[error("Application {0:?} failed to start because it doesn't know what terminal to use...")]
 	NoTerminal(String),
 	#[error("An error occured executing the application, most likely a terminal does not exist.\n{0}")]
 	Exec(Error),
 	#[error("Application {0:?} does not exist.")]
 	NotFound(String),
+	#[error("Application {0:?} has nothing to execute.")]
+	EmptyExec(String),
+	#[error("Application {0:?} could not be activated over D-Bus ({1}) and the Exec fallback failed too.\n{2}")]
+	Activation(String, String, Box<RunError>),
+	#[error("Application {0:?} has no action {1:?}, its actions are: {list}.", list = action_list(.2))]
+	ActionNotFound(String, String, Vec<String>),
+	#[error("Application {0:?} has a malformed Exec, {1}.")]
+	MalformedExec(String, UnterminatedQuote),
+	#[error("Application {0:?} is ambiguous, it could be: {list}.", list = .1.join(", "))]
+	Ambiguous(String, Vec<String>),
+	#[error("Application {0:?} exited with {1}.")]
+	Exited(String, ExitStatus),
+	#[error("Application {0:?} has to start in {dir} which is not a directory.", dir = .1.display())]
+	PathNotFound(String, PathBuf),
+	#[error("{file} can't be launched. {1}", file = .0.display())]
+	InvalidFile(PathBuf, DecodeError),
+}
+
+//The escapes `ApplicationEntry` resolves in string values, so a written value reads back the same.
+//A leading space is written as `\s` for parsers that trim values.
+fn escape(value: &str) -> String {
+	let escaped = value.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r");
+	match escaped.strip_prefix(' ') {
+		Some(rest) => format!("\\s{rest}"),
+		None => escaped,
+	}
+}
+
+//A list item also escapes the `;` that would end it
+fn escape_item(item: &str) -> String {
+	escape(item).replace(';', "\\;")
+}
+
+fn action_list(actions: &[String]) -> String {
+	match actions.is_empty() {
+		true => "none".to_owned(),
+		false => actions.join(", "),
+	}
+}
+
+//Actions take part in equality but not in the hash, equal entries still hash the same
+impl PartialEq for Ini {
+	fn eq(&self, other: &Self) -> bool {
+		self.id == other.id && self.name == other.name && self.exec == other.exec && self.terminal == other.terminal
+			&& self.actions == other.actions
+	}
+}
+impl Eq for Ini {}
+impl Hash for Ini {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.id.hash(state);
+		self.name.hash(state);
+		self.exec.hash(state);
+		self.terminal.hash(state);
+	}
+}
+
+impl Ini {
+	/// Checks OnlyShowIn/NotShowIn against the current desktops, an unknown desktop shows everything
+	pub fn shown_in(&self, desktops: &[String]) -> bool {
+		if desktops.is_empty() { return true; }
+		let listed = |list: &[String]| list.iter().any(|desktop| desktops.contains(desktop));
+		(self.only_show_in.is_empty() || listed(&self.only_show_in)) && !listed(&self.not_show_in)
+	}
+
+	pub fn autostarts(&self, desktops: &[String]) -> bool {
+		!self.hidden && self.autostart_enabled != Some(false) && self.shown_in(desktops)
+	}
+
+	/// Whether MimeType lists `mime_type`, either exactly or through a `type/*` wildcard
+	pub fn handles(&self, mime_type: &str) -> bool {
+		let media_type = mime_type.split_once('/').map_or(mime_type, |(media_type, _)| media_type);
+		self.mime_types.iter().any(|handled| handled == mime_type || handled.strip_suffix("/*") == Some(media_type))
+	}
+
+	/// Whether StartupWMClass names this window class, compared case-insensitively
+	pub fn matches_wm_class(&self, wm_class: &str) -> bool {
+		self.startup_wm_class.as_ref().is_some_and(|class| class.eq_ignore_ascii_case(wm_class))
+	}
+
+	/// Name in the locale the entry was decoded with, see `untranslated_name` for the plain Name
+	pub fn resolved_name(&self) -> &str {
+		&self.name
+	}
+
+	/// Exec split into its argv tokens, field codes left in place
+	pub fn exec_tokens(&self) -> Vec<String> {
+		exec_split(&self.exec)
+	}
+
+	/// The desktop file ID, e.g. `org.gnome.Nautilus.desktop`
+	pub fn id(&self) -> &str {
+		&self.id
+	}
+
+	/// The Icon file closest to `size` pixels, looked up in the hicolor theme
+	pub fn icon_path(&self, size: u32) -> Option<PathBuf> {
+		icon::resolve_icon(self.icon.as_deref()?, size, icon::FALLBACK_THEME)
+	}
+
+	/// The key of the action whose key or Name is `query`, compared case-insensitively like application names
+	pub fn action_id(&self, query: &str) -> Option<&str> {
+		let query = query.to_lowercase();
+		let mut ids: Vec<&String> = self.actions.keys().collect();
+		ids.sort();
+		//Keys are looked at before names, so an action can't hide behind another one's Name
+		ids.iter().find(|id| id.to_lowercase() == query)
+			.or_else(|| ids.iter().find(|id| self.actions[**id].name.as_ref().is_some_and(|name| name.to_lowercase() == query)))
+			.map(|id| id.as_str())
+	}
+
+	/// This entry launching the action [`Ini::action_id`] finds instead, an action without Terminal
+	/// inherits the entry's
+	pub fn with_action(&self, query: &str) -> Result<Ini, RunError> {
+		let Some(action) = self.action_id(query).map(|id| &self.actions[id]) else {
+			let mut available: Vec<String> = self.actions.keys().cloned().collect();
+			available.sort();
+			return Err(RunError::ActionNotFound(self.name.clone(), query.to_owned(), available))
+		};
+		Ok(Ini {
+			exec: action.exec.clone().unwrap_or_default(),
+			terminal: action.terminal.unwrap_or(self.terminal),
+			path: action.path.as_ref().map(PathBuf::from).or_else(|| self.path.clone()),
+			//Actions are launched through their Exec
+			dbus_activatable: false,
+			..self.clone()
+		})
+	}
+
+	/// Whether the TryExec program is installed, an entry without TryExec always is
+	pub fn try_exec_found(&self) -> bool {
+		self.try_exec_found_in(&env::var_os("PATH").unwrap_or_default())
+	}
+
+	/// Whether TryExec, if set, is an executable file on `path` or at its absolute path
+	pub fn try_exec_found_in(&self, path: &OsStr) -> bool {
+		self.try_exec.as_deref().is_none_or(|program| executable_in(program, path).is_some())
+	}
+
+	/// Whether Name, Exec, Comment or an action's Name contains `query`, which is lowercase already
+	pub fn matches_search(&self, query: &str) -> bool {
+		let has = |text: &str| text.to_lowercase().contains(query);
+		has(&self.name) || has(&self.exec) || self.comment.as_deref().is_some_and(has)
+			|| self.actions.values().any(|action| action.name.as_deref().is_some_and(has))
+	}
+
+	/// Whether Categories lists `category` as a whole token, ignoring case
+	pub fn has_category(&self, category: &str) -> bool {
+		self.categories.iter().any(|listed| listed.eq_ignore_ascii_case(category))
+	}
+
+	/// The flatpak application ID: X-Flatpak, else the app of an Exec running `flatpak run`
+	pub fn flatpak_id(&self) -> Option<String> {
+		if let Some(id) = &self.flatpak {
+			return Some(id.clone())
+		}
+		let tokens = exec_split(&self.exec);
+		let program = exec_program(&tokens)?;
+		if Path::new(program).file_name()? != "flatpak" {
+			return None
+		}
+		//The first argument after `run` that isn't an option, options take their values as `--option=value`
+		let mut args = tokens.iter().skip_while(|token| *token != program).skip(1);
+		args.next().filter(|command| *command == "run")?;
+		args.find(|arg| !arg.starts_with('-')).filter(|id| !id.starts_with(['@', '%'])).cloned()
+	}
+
+	/// Absolute path of the program Exec runs, looked up on PATH with symlinks resolved
+	pub fn program_path(&self) -> Option<PathBuf> {
+		let path = which(exec_program(&exec_split(&self.exec))?)?;
+		Some(fs::canonicalize(&path).unwrap_or(path))
+	}
+
+	pub fn to_json(&self) -> Json {
+		Json::obj([
+			("id", Json::from(self.id.as_str())),
+			("name", self.name.as_str().into()),
+			("generic_name", self.generic_name.clone().into()),
+			("comment", self.comment.clone().into()),
+			("icon", self.icon.clone().into()),
+			("exec", self.exec.as_str().into()),
+			("terminal", self.terminal.into()),
+			("categories", self.categories.clone().into()),
+			("keywords", self.keywords.clone().into()),
+			("file", self.file.as_ref().map(|file| file.to_string_lossy().into_owned()).into()),
+		])
+	}
+
+	/// [`Ini::to_json`] with every other key tal reads as well
+	pub fn to_json_details(&self) -> Json {
+		let mut json = self.to_json();
+		if let Json::Obj(fields) = &mut json {
+			let env = self.env.iter().map(|(name, value)| (name.as_str(), value.as_str()));
+			fields.extend([
+				("untranslated_name", self.untranslated_name.clone().into()),
+				("mime_types", self.mime_types.clone().into()),
+				("only_show_in", self.only_show_in.clone().into()),
+				("not_show_in", self.not_show_in.clone().into()),
+				("no_display", self.no_display.into()),
+				("hidden", self.hidden.into()),
+				("dbus_activatable", self.dbus_activatable.into()),
+				("startup_notify", self.startup_notify.into()),
+				("startup_wm_class", self.startup_wm_class.clone().into()),
+				("try_exec", self.try_exec.clone().into()),
+				("path", self.path.as_ref().map(|path| path.to_string_lossy().into_owned()).into()),
+				("flatpak", self.flatpak_id().into()),
+				("env", Json::obj(env)),
+			].map(|(key, value)| (key.to_owned(), value)));
+		}
+		json
+	}
+
+	/// Serializes the entry back into desktop file syntax, decoding the result gives an equal entry.
+	/// Name holds the resolved name, so a localized name is written as the plain Name.
+	pub fn to_desktop_string(&self) -> String {
+		let mut out = String::from("[Desktop Entry]\nType=Application\n");
+		let mut key = |key: &str, val: &str| {
+			out.push_str(key);
+			out.push('=');
+			out.push_str(val);
+			out.push('\n');
+		};
+		let list = |items: &[String]| items.iter().map(|item| format!("{};", escape_item(item))).collect::<String>();
+		key("Name", &escape(&self.name));
+		for (name, val) in [("GenericName", &self.generic_name), ("Comment", &self.comment), ("Icon", &self.icon)] {
+			if let Some(val) = val {
+				key(name, &escape(val));
+			}
+		}
+		key("Exec", &escape(&self.exec));
+		key("Terminal", if self.terminal { "true" } else { "false" });
+		for (name, items) in [
+			("Categories", &self.categories), ("Keywords", &self.keywords), ("MimeType", &self.mime_types),
+			("OnlyShowIn", &self.only_show_in), ("NotShowIn", &self.not_show_in),
+		] {
+			if !items.is_empty() {
+				key(name, &list(items));
+			}
+		}
+		if self.no_display {
+			key("NoDisplay", "true");
+		}
+		if self.hidden {
+			key("Hidden", "true");
+		}
+		if self.dbus_activatable {
+			key("DBusActivatable", "true");
+		}
+		if self.startup_notify {
+			key("StartupNotify", "true");
+		}
+		if let Some(wm_class) = &self.startup_wm_class {
+			key("StartupWMClass", &escape(wm_class));
+		}
+		if let Some(try_exec) = &self.try_exec {
+			key("TryExec", &escape(try_exec));
+		}
+		if let Some(path) = &self.path {
+			key("Path", &escape(&path.to_string_lossy()));
+		}
+		if let Some(flatpak) = &self.flatpak {
+			key("X-Flatpak", &escape(flatpak));
+		}
+		if let Some(enabled) = self.autostart_enabled {
+			key("X-GNOME-Autostart-enabled", if enabled { "true" } else { "false" });
+		}
+		if !self.env.is_empty() {
+			key("X-Tal-Env", &self.env.iter().map(|(name, value)| format!("{}={};", escape_item(name), escape_item(value))).collect::<String>());
+		}
+		//Sorted so the output is stable, the HashMap order isn't
+		let mut action_ids: Vec<&String> = self.actions.keys().collect();
+		action_ids.sort();
+		if !action_ids.is_empty() {
+			key("Actions", &action_ids.iter().map(|id| format!("{id};")).collect::<String>());
+		}
+		for id in action_ids {
+			let action = &self.actions[id];
+			out.push_str(&format!("\n[Desktop Action {id}]\n"));
+			if let Some(name) = &action.name {
+				out.push_str(&format!("Name={}\n", escape(name)));
+			}
+			if let Some(exec) = &action.exec {
+				out.push_str(&format!("Exec={}\n", escape(exec)));
+			}
+			if let Some(terminal) = action.terminal {
+				out.push_str(&format!("Terminal={terminal}\n"));
+			}
+			if let Some(path) = &action.path {
+				out.push_str(&format!("Path={}\n", escape(path)));
+			}
+		}
+		out
+	}
+}
+
+/// How [`find_entry`] matches names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchOptions {
+	/// Compare names and the query without their diacritics, see [`fold::fold_diacritics`]
+	pub fold_diacritics: bool,
+	/// Pick the best scoring of several partial matches instead of failing with [`RunError::Ambiguous`]
+	pub first: bool,
+}
+
+/// The entry of `entries` that `query` names, compared case-insensitively: an exact Name, translated
+/// or not, else the entry whose program is `query`, else the one entry whose name starts with `query`,
+/// else the one containing it and else the one containing its letters in order, so typos like
+/// `firfox` still match. Entries sharing the Name give the first, several matches in a later stage
+/// are [`RunError::Ambiguous`] unless `options.first` is set.
+pub fn find_entry(mut entries: Vec<Ini>, query: &str, options: MatchOptions) -> Result<Ini, RunError> {
+	let normalize = |text: &str| match options.fold_diacritics {
+		true => fold::fold_diacritics(&text.to_lowercase()),
+		false => text.to_lowercase(),
+	};
+	let query_lower = normalize(query);
+	let named = |ini: &Ini| normalize(&ini.name) == query_lower
+		|| ini.untranslated_name.as_ref().is_some_and(|name| normalize(name) == query_lower);
+	if let Some(index) = entries.iter().position(named) {
+		return Ok(entries.swap_remove(index))
+	}
+	let program_is_query = |ini: &Ini| exec_program(&exec_split(&ini.exec))
+		.and_then(|program| Path::new(program).file_name())
+		.is_some_and(|program| program.to_string_lossy().to_lowercase() == query_lower);
+	let score = |ini: &Ini| Subsequence.score(&query_lower, &normalize(&ini.name));
+	let stages: [&dyn Fn(&Ini) -> bool; 4] = [
+		&program_is_query,
+		&|ini| normalize(&ini.name).starts_with(&query_lower),
+		&|ini| normalize(&ini.name).contains(&query_lower),
+		&|ini| score(ini).is_some(),
+	];
+	for stage in stages {
+		let matches: Vec<usize> = entries.iter().enumerate().filter(|(_, ini)| stage(ini)).map(|(index, _)| index).collect();
+		let best = match matches.as_slice() {
+			[] => continue,
+			[index] => *index,
+			//Ties prefer the shorter name and then listing order, like the fuzzy ranking
+			_ if options.first => *matches.iter()
+				.min_by_key(|index| (std::cmp::Reverse(score(&entries[**index])), entries[**index].name.chars().count()))
+				.unwrap_or(&matches[0]),
+			_ => return Err(RunError::Ambiguous(query.to_owned(), matches.iter().map(|index| entries[*index].name.clone()).collect())),
+		};
+		return Ok(entries.swap_remove(best))
+	}
+	Err(RunError::NotFound(query.to_owned()))
+}
+
+/// The entries matching a search for `query`, ignoring case, see [`Ini::matches_search`]
+pub fn filter(mut entries: Vec<Ini>, query: &str) -> Vec<Ini> {
+	let query = query.to_lowercase();
+	entries.retain(|ini| ini.matches_search(&query));
+	entries
+}
+
+/// The entries whose Categories list every one of `categories`, see [`Ini::has_category`]
+pub fn filter_categories(mut entries: Vec<Ini>, categories: &[impl AsRef<str>]) -> Vec<Ini> {
+	entries.retain(|ini| categories.iter().all(|category| ini.has_category(category.as_ref())));
+	entries
+}
+
+pub fn current_desktops() -> Vec<String> {
+	env::var("XDG_CURRENT_DESKTOP")
+		.map(|desktops| desktops.split(':').filter(|d| !d.is_empty()).map(|d| d.to_owned()).collect())
+		.unwrap_or_default()
 }
 
 impl fmt::Display for Ini {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "Name={}\n\t- Exec={}\n\t- Terminal={}", self.name, self.exec, self.terminal)
+		Details { ini: self, exec: &self.exec }.fmt(f)
+	}
+}
+
+//The detailed form of an entry with Exec shown as given, the optional keys only when they are set
+pub(crate) struct Details<'a> {
+	pub ini: &'a Ini,
+	pub exec: &'a str,
+}
+impl fmt::Display for Details<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ini = self.ini;
+		write!(f, "Name={}", ini.name)?;
+		for (key, val) in [("GenericName", &ini.generic_name), ("Comment", &ini.comment), ("Icon", &ini.icon)] {
+			if let Some(val) = val {
+				write!(f, "\n\t- {key}={val}")?;
+			}
+		}
+		if !ini.categories.is_empty() {
+			write!(f, "\n\t- Categories={}", ini.categories.join(";"))?;
+		}
+		write!(f, "\n\t- Exec={}\n\t- Terminal={}", self.exec, ini.terminal)?;
+		if let Some(path) = &ini.path {
+			write!(f, "\n\t- Path={}", path.display())?;
+		}
+		if let Some(flatpak) = ini.flatpak_id() {
+			write!(f, "\n\t- Flatpak={flatpak}")?;
+		}
+		//Which file won helps when debugging overrides
+		match &ini.file {
+			Some(file) => write!(f, "\n\t- File={}", file.display()),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Why an applications directory could not be listed
+#[derive(Debug, Error)]
+pub enum ReadError {
+	#[error("{} does not exist.", .0.display())]
+	NotFound(PathBuf),
+	#[error("Failed to read {} applications from {}.\n{}", .0.name(), .1.display(), .2)]
+	Io(Source, PathBuf, Error),
+}
+
+impl RunError {
+	/// Stable name of the failure for scripts, in the `error` field of [`RunError::to_json`]
+	pub const fn kind(&self) -> &'static str {
+		match self {
+			Self::Read(ReadError::NotFound(_)) => "DirectoryNotFound",
+			Self::Read(ReadError::Io(..)) => "ReadFailed",
+			Self::NoTerminal(_) => "NoTerminal",
+			Self::Exec(_) => "Exec",
+			Self::NotFound(_) => "NotFound",
+			Self::EmptyExec(_) => "EmptyExec",
+			Self::Activation(..) => "ActivationFailed",
+			Self::ActionNotFound(..) => "ActionNotFound",
+			Self::MalformedExec(..) => "MalformedExec",
+			Self::Ambiguous(..) => "Ambiguous",
+			Self::Exited(..) => "Exited",
+			Self::PathNotFound(..) => "PathNotFound",
+			Self::InvalidFile(..) => "InvalidFile",
+		}
+	}
+
+	pub fn to_json(&self) -> Json {
+		let path = |path: &Path| Json::from(path.to_string_lossy().into_owned());
+		let mut fields = vec![("error", Json::from(self.kind()))];
+		match self {
+			Self::Read(ReadError::NotFound(dir)) => fields.push(("path", path(dir))),
+			Self::Read(ReadError::Io(source, dir, e)) => fields.extend([
+				("source", source.name().into()),
+				("path", path(dir)),
+				("message", e.to_string().into()),
+			]),
+			Self::NoTerminal(name) | Self::NotFound(name) | Self::EmptyExec(name) => fields.push(("name", name.as_str().into())),
+			Self::Exec(e) => fields.push(("message", e.to_string().into())),
+			Self::Activation(name, dbus, fallback) => fields.extend([
+				("name", name.as_str().into()),
+				("dbus", dbus.as_str().into()),
+				("fallback", fallback.to_json()),
+			]),
+			Self::ActionNotFound(name, action, available) => fields.extend([
+				("name", name.as_str().into()),
+				("action", action.as_str().into()),
+				("available", available.clone().into()),
+			]),
+			Self::MalformedExec(name, e) => fields.extend([
+				("name", name.as_str().into()),
+				("message", e.to_string().into()),
+			]),
+			Self::Ambiguous(name, candidates) => fields.extend([
+				("name", name.as_str().into()),
+				("candidates", candidates.clone().into()),
+			]),
+			Self::Exited(name, status) => fields.extend([
+				("name", name.as_str().into()),
+				("code", status.code().into()),
+				("signal", status.signal().into()),
+			]),
+			Self::PathNotFound(name, dir) => fields.extend([
+				("name", name.as_str().into()),
+				("path", path(dir)),
+			]),
+			Self::InvalidFile(file, e) => fields.extend([
+				("path", path(file)),
+				("message", e.to_string().into()),
+			]),
+		}
+		Json::obj(fields)
 	}
 }
 
-struct ApplicationEntry(String);
+#[derive(Debug, Error)]
+pub enum DecodeError {
+	#[error("Failed to read the file.\n{0}")]
+	Read(Error),
+	#[error("The file is not valid UTF-8.")]
+	NotUtf8,
+	#[error("The file does not start with a [Desktop Entry] group.")]
+	NotDesktopEntry,
+	#[error("The entry has no Name key.")]
+	MissingName,
+	#[error("The entry has no Exec key.")]
+	MissingExec,
+}
+
+//Keys of [Desktop Entry] read in the best matching locale, the order `decode` destructures them in
+const LOCALIZED_KEYS: [&str; 3] = ["Name", "GenericName", "Comment"];
+
+//The group of a desktop file the following keys belong to
+#[derive(Clone, Copy)]
+enum Group<'a> {
+	Entry,
+	Action(&'a str),
+	Other,
+}
+
+/// Decodes the contents of a desktop file with its untranslated keys, see [`ApplicationEntry::decode`]
+/// to read them in a locale
+pub fn parse_desktop_entry(contents: &str) -> Result<Ini, DecodeError> {
+	ApplicationEntry::new(contents.to_owned()).decode(None)
+}
+
+pub struct ApplicationEntry(String);
 impl ApplicationEntry {
 	pub const fn new(entry_inner: String) -> Self {
 		Self(entry_inner)
 	}
 	const fn body<T: AsRef<str>>(&self) -> IniAction<T> {
-		IniAction { name: None, exec: None, terminal: None }
+		IniAction { name: None, exec: None, terminal: None, path: None }
 	}
 
 	#[inline]
-	fn lines(&self) -> Option<Vec<&str>> {
-		let ini_lines: Vec<&str> = self.0.split("\n")
+	//Splits on `\r\n`, `\n` and the lone `\r` of old Mac files alike
+	fn split_lines<'a>(&self, text: &'a str) -> Vec<&'a str> {
+		let mut lines = Vec::new();
+		let mut rest = text;
+		while let Some(end) = rest.find(['\r', '\n']) {
+			lines.push(&rest[..end]);
+			let separator_len = if rest[end..].starts_with("\r\n") { 2 } else { 1 };
+			rest = &rest[end + separator_len..];
+		}
+		lines.push(rest);
+		lines
+	}
+
+	fn lines(&self) -> Result<Vec<&str>, DecodeError> {
+		let entry_inner = self.0.strip_prefix('\u{feff}').unwrap_or(&self.0); //Strip the UTF-8 BOM
+		let ini_lines: Vec<&str> = self.split_lines(entry_inner).into_iter()
 			.filter(|line| !(*line).starts_with("#")) //Filter out comments
+			.skip_while(|line| line.trim().is_empty()) //Blank lines before the first group
 			.collect();
-		match ini_lines.first() {
-			Some(&"[Desktop Entry]") => Some(ini_lines),
-			_ => None,
+		match ini_lines.first().and_then(|line| self.group_header(line)) {
+			Some(Group::Entry) => Ok(ini_lines),
+			_ => Err(DecodeError::NotDesktopEntry),
 		}
 	}
 
 	#[inline]
-	fn is_action<'a>(&self, line: &'a str) -> Option<&'a str> {
-		if let Some((_, right)) = line.split_once("Action ") {
-			let action_section = &right[..right.len() - 1]; //trim "]"
-			return Some(action_section)
-		};
-		None
+	//The group a `[...]` header line starts, `None` for every other line. Trailing whitespace is
+	//allowed, and a name with brackets of its own like `[Desktop Action new][x]` is no group tal knows.
+	fn group_header<'a>(&self, line: &'a str) -> Option<Group<'a>> {
+		let group_name = line.trim_end().strip_prefix('[')?.strip_suffix(']')?;
+		Some(match group_name {
+			_ if group_name.contains(['[', ']']) => Group::Other,
+			"Desktop Entry" => Group::Entry,
+			_ => match group_name.strip_prefix("Desktop Action ") {
+				Some(action) if !action.is_empty() => Group::Action(action),
+				_ => Group::Other,
+			},
+		})
 	}
 
 	#[inline]
@@ -79,209 +645,1210 @@ impl ApplicationEntry {
 	}
 
 	#[inline]
-	fn decode_finished(&self, mut body: IniAction<&str>, actions: Actions) -> Option<Ini> {
-		if body.terminal.is_none() {
-			body.terminal = Some(false);
+	fn localized_key<'a>(&self, field_key: &'a str, key: &str) -> Option<&'a str> {
+		field_key.strip_prefix(key)?.strip_prefix('[')?.strip_suffix(']')
+	}
+
+	#[inline]
+	//Resolves the `\s`, `\n`, `\t`, `\r` and `\\` escapes of a string value, other backslashes
+	//and a trailing one are kept as written
+	fn unescape(&self, value: &str) -> String {
+		if !value.contains('\\') {
+			return value.to_owned()
 		}
-		if let (Some(name), Some(exec), Some(terminal)) = (body.name, body.exec, body.terminal) {
-			return Some(Ini { name: name.to_owned(), exec: exec.to_owned(), terminal, actions });
+		let mut unescaped = String::with_capacity(value.len());
+		let mut chars = value.chars();
+		while let Some(c) = chars.next() {
+			if c != '\\' {
+				unescaped.push(c);
+				continue;
+			}
+			match chars.next() {
+				Some('s') => unescaped.push(' '),
+				Some('n') => unescaped.push('\n'),
+				Some('t') => unescaped.push('\t'),
+				Some('r') => unescaped.push('\r'),
+				Some('\\') => unescaped.push('\\'),
+				Some(other) => {
+					unescaped.push('\\');
+					unescaped.push(other);
+				},
+				None => unescaped.push('\\'),
+			}
+		}
+		unescaped
+	}
+
+	//Items end at a `;` that isn't escaped as `\;`, the other escapes apply to every item
+	fn str_as_list(&self, s: &str) -> Vec<String> {
+		let mut items = Vec::new();
+		let mut item = String::new();
+		let mut chars = s.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				';' => items.push(std::mem::take(&mut item)),
+				'\\' => match chars.next() {
+					Some(';') => item.push(';'),
+					Some(escaped) => {
+						item.push('\\');
+						item.push(escaped);
+					},
+					None => item.push('\\'),
+				},
+				_ => item.push(c),
+			}
 		}
-		None
+		items.push(item);
+		items.into_iter().filter(|item| !item.is_empty()).map(|item| self.unescape(&item)).collect()
 	}
 
+	//`KEY=VALUE` pairs of a list, pairs without a key are dropped
 	#[inline]
-	fn decode_kv_hash(&self, line: &str, action: &mut IniAction<String>) {
+	fn str_as_env(&self, s: &str) -> Vec<(String, String)> {
+		self.str_as_list(s).into_iter()
+			.filter_map(|pair| pair.split_once('=').filter(|(name, _)| !name.is_empty()).map(|(name, value)| (name.to_owned(), value.to_owned())))
+			.collect()
+	}
+
+	fn decode_finished(&self, body: IniAction<&str>, localized_name: Option<&str>, entry: Ini) -> Result<Ini, DecodeError> {
+		let name = body.name.ok_or(DecodeError::MissingName)?;
+		//D-Bus activated entries don't need an Exec to fall back to
+		let exec = body.exec.or(entry.dbus_activatable.then_some("")).ok_or(DecodeError::MissingExec)?;
+		let (name, untranslated_name) = match localized_name {
+			Some(localized) => (self.unescape(localized), Some(self.unescape(name))),
+			None => (self.unescape(name), None),
+		};
+		//Exec is unescaped like any string first, its own quoting rules apply to the result
+		Ok(Ini { name, untranslated_name, exec: self.unescape(exec), terminal: body.terminal.unwrap_or(false), ..entry })
+	}
+
+	#[inline]
+	//`name_rank` is the locale rank of the localized Name the action holds, if it holds one
+	fn decode_kv_hash(&self, line: &str, action: &mut IniAction<String>, locale: Option<&Locale>, name_rank: &mut Option<usize>) {
 		if let Some((act_field_key, act_field_val)) = line.split_once("=") {
+			if let Some(key_locale) = self.localized_key(act_field_key, "Name") {
+				if let Some(rank) = locale.and_then(|locale| locale.rank(key_locale))
+					&& name_rank.is_none_or(|best_rank| rank < best_rank)
+				{
+					action.name = Some(self.unescape(act_field_val));
+					*name_rank = Some(rank);
+				}
+				return;
+			}
 			match act_field_key {
-				"Name" => action.name = Some(act_field_val.to_owned()),
-				"Exec" => action.exec = Some(act_field_val.to_owned()),
+				//A localized Name wins no matter the order of the keys
+				"Name" if name_rank.is_some() => (),
+				"Name" => action.name = Some(self.unescape(act_field_val)),
+				"Exec" => action.exec = Some(self.unescape(act_field_val)),
 				"Terminal" => action.terminal = Some(self.str_as_bool(act_field_val)),
+				"Path" if !act_field_val.is_empty() => action.path = Some(self.unescape(act_field_val)),
 				_ => ()
 			}
 		};
 	}
 
-	pub fn decode(&self) -> Option<Ini> {
+	pub fn decode(&self, locale: Option<&Locale>) -> Result<Ini, DecodeError> {
 		let ini_lines = self.lines()?;
 		let mut body = self.body();
+		//Best ranked `(rank, value)` of each of LOCALIZED_KEYS
+		let mut localized: [Option<(usize, &str)>; LOCALIZED_KEYS.len()] = [None; LOCALIZED_KEYS.len()];
+		let mut entry = Ini::default();
 
-		let mut curr_act_name: Option<&str> = None;
-		let mut h_acts: Actions = HashMap::new();
+		let mut group = Group::Other;
+		let mut entry_started = false;
+		let mut curr_act_name_rank: Option<usize> = None;
 
 		for line in ini_lines {
-			//Check if we ran into a custom desktop action
-			if let Some(act) = self.is_action(line) {
-				curr_act_name = Some(act);
-				h_acts.insert(act.to_owned(), self.body());
+			//Keys only count in [Desktop Entry] and its actions, any other group is skipped
+			if let Some(header) = self.group_header(line) {
+				group = match header {
+					//The second [Desktop Entry] of a broken file doesn't override the first
+					Group::Entry if entry_started => Group::Other,
+					Group::Entry => {
+						entry_started = true;
+						Group::Entry
+					},
+					Group::Action(act) => {
+						curr_act_name_rank = None;
+						entry.actions.insert(act.to_owned(), self.body());
+						Group::Action(act)
+					},
+					Group::Other => Group::Other,
+				};
 				continue;
 			}
-			//Are we in a desktop action?
-			if let Some(act_name) = curr_act_name {
-				h_acts.entry(act_name.to_owned()).and_modify(|action| self.decode_kv_hash(line, action));
-				continue;
-			};
+			match group {
+				Group::Entry => (),
+				Group::Action(act_name) => {
+					entry.actions.entry(act_name.to_owned())
+						.and_modify(|action| self.decode_kv_hash(line, action, locale, &mut curr_act_name_rank));
+					continue;
+				},
+				Group::Other => continue,
+			}
 
 			let (field_key, field_val) = match line.split_once("=") {
 				Some(kv) => kv,
 				None => continue,
 			};
+			let localized_field = LOCALIZED_KEYS.iter().enumerate()
+				.find_map(|(index, key)| self.localized_key(field_key, key).map(|key_locale| (index, key_locale)));
+			if let Some((index, key_locale)) = localized_field {
+				if let Some(rank) = locale.and_then(|locale| locale.rank(key_locale))
+					&& localized[index].is_none_or(|(best_rank, _)| rank < best_rank)
+				{
+					localized[index] = Some((rank, field_val));
+				}
+				continue;
+			}
 			match field_key {
 				"Name" => body.name = Some(field_val),
 				"Exec" => body.exec = Some(field_val),
 				"Terminal" => body.terminal = Some(self.str_as_bool(field_val)),
-				"NoDisplay" => if self.str_as_bool(field_val) { return None; },
+				"GenericName" => entry.generic_name = Some(self.unescape(field_val)),
+				"Comment" => entry.comment = Some(self.unescape(field_val)),
+				"Keywords" => entry.keywords = self.str_as_list(field_val),
+				"Categories" => entry.categories = self.str_as_list(field_val),
+				"NoDisplay" => entry.no_display = self.str_as_bool(field_val),
+				"Hidden" => entry.hidden = self.str_as_bool(field_val),
+				"OnlyShowIn" => entry.only_show_in = self.str_as_list(field_val),
+				"NotShowIn" => entry.not_show_in = self.str_as_list(field_val),
+				"X-GNOME-Autostart-enabled" => entry.autostart_enabled = Some(self.str_as_bool(field_val)),
+				"X-Tal-Env" => entry.env = self.str_as_env(field_val),
+				"X-Flatpak" => entry.flatpak = Some(self.unescape(field_val)).filter(|id| !id.is_empty()),
+				"MimeType" => entry.mime_types = self.str_as_list(field_val),
+				"Icon" => entry.icon = Some(self.unescape(field_val)),
+				"StartupWMClass" => entry.startup_wm_class = Some(self.unescape(field_val)),
+				"TryExec" => entry.try_exec = Some(self.unescape(field_val)),
+				"Path" => entry.path = Some(PathBuf::from(self.unescape(field_val))).filter(|_| !field_val.is_empty()),
+				"DBusActivatable" => entry.dbus_activatable = self.str_as_bool(field_val),
+				"StartupNotify" => entry.startup_notify = self.str_as_bool(field_val),
 				_ => continue,
 			}
 		}
 
-		self.decode_finished(body, h_acts)
+		let [localized_name, localized_generic_name, localized_comment] = localized.map(|best| best.map(|(_, value)| value));
+		if let Some(generic_name) = localized_generic_name {
+			entry.generic_name = Some(self.unescape(generic_name));
+		}
+		if let Some(comment) = localized_comment {
+			entry.comment = Some(self.unescape(comment));
+		}
+		self.decode_finished(body, localized_name, entry)
 	}
 }
 
+/// How a launched process is tied to tal and the terminal tal runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpawnMode {
+	/// Its own session and process group with no stdin or output, it keeps running once the terminal is closed
+	#[default]
+	Detached,
+	/// Like `Detached` but in tal's process group, so the terminal's signals still reach it
+	Background,
+	/// Shares tal's stdin, stdout and stderr and is waited on, a failed exit is a [`RunError::Exited`]
+	Attached,
+}
+
 pub struct Spawn {
 	name: String,
 	terminal: Option<String>,
+	terminal_fallback: Option<String>,
+	locale: Option<String>,
+	args: Vec<String>,
+	shell: Option<String>,
+	history: bool,
+	terminal_tools: Option<Vec<String>>,
+	niceness: Option<i32>,
+	io_class: Option<IoClass>,
+	startup_id: Option<StartupId>,
+	terminal_override: Option<String>,
+	detect_terminal: bool,
 }
 impl Spawn {
     pub const fn new(name: String, terminal: Option<String>) -> Self {
-    	Self { name, terminal }
+    	Self {
+    		name, terminal, terminal_fallback: None, locale: None, args: Vec::new(), shell: None, history: false, terminal_tools: None,
+    		niceness: None, io_class: None, startup_id: None, terminal_override: None, detect_terminal: true,
+    	}
     }
 
-    fn sys_exec(&self, app: Ini, stdout: bool) -> Result<(), RunError> {
-		let mut args: Vec<String> = app.exec.split_whitespace()
-			.filter(|s| !matches!(*s, "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%k" | "%v" | "%m" | "%c" | "%i" | "%s"))
-			.map(|s| s.to_owned())
-			.collect();
-		let std_inherit_or_null = || if stdout { Stdio::inherit() } else { Stdio::null() };
+    /// Hand `id` to StartupNotify entries instead of a new ID per started process, so the caller
+    /// knows which startup sequence to end
+    pub fn with_startup_id(mut self, id: Option<StartupId>) -> Self {
+    	self.startup_id = id;
+    	self
+    }
 
-		if app.terminal {
-			match self.terminal.clone() {
-				Some(term) => {
-					args.insert(0, term);
-					args.insert(1, "-e".to_owned());
-				},
-				None => return Err(RunError::NoTerminal(app.name))
-			}
-		}
-		match Command::new(args.remove(0))
-			.args(args)
-			.stdout(std_inherit_or_null())
-			.stderr(std_inherit_or_null())
-			.spawn()
-		{
-			Ok(mut child_proc) => {
-				println!("Launching application {:?}.", app.name);
-				if stdout {
-					child_proc.wait().map_err(RunError::Exec)?;
-				}
-				Ok(())
+    /// Start the command with this niceness and I/O scheduling class, see [`priority_wrap`]
+    pub const fn with_priority(mut self, niceness: Option<i32>, io_class: Option<IoClass>) -> Self {
+    	self.niceness = niceness;
+    	self.io_class = io_class;
+    	self
+    }
+
+    /// Also open entries in a terminal when their program is one of `tools`, even with Terminal=false.
+    /// This is a best-effort guess for entries that forgot Terminal=true.
+    pub fn with_terminal_tools(mut self, tools: Option<Vec<String>>) -> Self {
+    	self.terminal_tools = tools;
+    	self
+    }
+
+    /// Record successful launches in the history store
+    pub const fn with_history(mut self, history: bool) -> Self {
+    	self.history = history;
+    	self
+    }
+
+    /// Run the command through this shell as `shell -lc '<command>'`
+    pub fn with_shell(mut self, shell: Option<String>) -> Self {
+    	self.shell = shell;
+    	self
+    }
+
+    /// Last resort terminal used when neither `$TERMINAL` nor detection finds one, only if it is found on PATH
+    pub fn with_terminal_fallback(mut self, fallback: Option<String>) -> Self {
+    	self.terminal_fallback = fallback;
+    	self
+    }
+
+    /// Terminal that wins over `$TERMINAL`, detection and the fallback, from `--terminal`
+    pub fn with_terminal(mut self, terminal: Option<String>) -> Self {
+    	self.terminal_override = terminal;
+    	self
+    }
+
+    /// Whether the first of [`terminal::KNOWN_TERMINALS`] on PATH is used when `$TERMINAL` names no terminal,
+    /// before the fallback is tried. It is by default.
+    pub const fn with_terminal_detection(mut self, detect: bool) -> Self {
+    	self.detect_terminal = detect;
+    	self
+    }
+
+    /// Files or URLs substituted into the Exec field codes
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+    	self.args = args;
+    	self
+    }
+
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+    	self.locale = locale;
+    	self
+    }
+
+    pub fn resolve_terminal(&self) -> Option<Terminal> {
+    	if let Some(term) = self.terminal_override.clone().filter(|term| !term.is_empty()) {
+    		return Some(Terminal::new(term, Origin::Flag))
+    	}
+    	terminal::resolve(self.terminal.clone(), self.terminal_fallback.clone(), self.detect_terminal)
+    }
+
+    /// The resolved terminal on its own, so it opens a shell starting in `dir`
+    pub fn resolve_terminal_here(&self, dir: PathBuf) -> Result<ResolvedCommand, RunError> {
+		let term = self.resolve_terminal().ok_or_else(|| RunError::NoTerminal(self.name.clone()))?;
+		Ok(ResolvedCommand { name: self.name.clone(), program: term.program, args: Vec::new(), terminal: true, cwd: Some(dir), file: None })
+    }
+
+    /// Opens the resolved terminal in `dir` without waiting on it
+    pub fn terminal_here(&self, dir: PathBuf, mode: SpawnMode) -> Result<Child, RunError> {
+		self.command(self.resolve_terminal_here(dir)?, mode).spawn().map_err(RunError::Exec)
+    }
+
+    fn command(&self, resolved: ResolvedCommand, mode: SpawnMode) -> Command {
+		let std_inherit_or_null = || match mode {
+			SpawnMode::Attached => Stdio::inherit(),
+			SpawnMode::Detached | SpawnMode::Background => Stdio::null(),
+		};
+		let mut command = Command::new(resolved.program);
+		//A new session has no controlling terminal, so closing the one tal was started from neither
+		//hangs it up nor reaches it through tal's process group
+		if mode == SpawnMode::Detached {
+			//SAFETY: setsid is async-signal-safe and nothing else runs between fork and exec
+			unsafe {
+				command.pre_exec(|| match libc::setsid() {
+					-1 => Err(io::Error::last_os_error()),
+					_ => Ok(()),
+				});
+			}
+		}
+		if let Some(locale) = &self.locale {
+			command.env("LC_ALL", locale).env("LANG", locale);
+		}
+		if let Some(cwd) = resolved.cwd {
+			command.current_dir(cwd);
+		}
+		command.args(resolved.args).stdin(std_inherit_or_null()).stdout(std_inherit_or_null()).stderr(std_inherit_or_null());
+		command
+    }
+
+    /// Builds the commands a launch of `app` runs, one per instance
+    pub fn resolve(&self, app: &Ini) -> Result<Vec<ResolvedCommand>, RunError> {
+		let file = app.file.as_ref().map(|file| file.to_string_lossy());
+		let tokens = exec_tokenize(&app.exec).map_err(|e| RunError::MalformedExec(app.name.clone(), e))?;
+		//Arguments appended to an empty Exec would otherwise run the first one as the program
+		if tokens.is_empty() {
+			return Err(RunError::EmptyExec(app.name.clone()))
+		}
+		let tokens = expand_entry_codes(tokens, &app.name, file.as_deref(), app.icon.as_deref());
+		let mut invocations = expand_invocations(tokens, &self.args);
+		let terminal = app.terminal || self.terminal_tools.as_ref()
+			.is_some_and(|tools| invocations.first().is_some_and(|argv| terminal::is_terminal_tool(argv, tools)));
+		if let Some(shell) = &self.shell {
+			invocations = invocations.into_iter().map(|args| shell_wrap(shell, args)).collect();
+		}
+		if terminal {
+			match self.resolve_terminal() {
+				Some(term) => invocations = invocations.into_iter().map(|args| term.wrap(args)).collect(),
+				None => return Err(RunError::NoTerminal(app.name.clone()))
+			}
+		}
+		if self.niceness.is_some() || self.io_class.is_some() {
+			invocations = invocations.into_iter().map(|args| priority_wrap(args, self.niceness, self.io_class)).collect();
+		}
+		let cwd = match &app.path {
+			Some(path) if path.is_dir() => Some(path.clone()),
+			//Spawning in a missing directory would only fail with an opaque ENOENT
+			Some(path) => return Err(RunError::PathNotFound(app.name.clone(), path.clone())),
+			None => env::current_dir().ok(),
+		};
+		invocations.into_iter().map(|mut args| {
+			if args.is_empty() {
+				return Err(RunError::EmptyExec(app.name.clone()))
+			}
+			let program = args.remove(0);
+			Ok(ResolvedCommand { name: app.name.clone(), program, args, terminal, cwd: cwd.clone(), file: app.file.clone() })
+		}).collect()
+    }
+
+    /// Calls `org.freedesktop.Application` on the entry's well-known bus name through gdbus,
+    /// `Activate` without arguments and `Open` with them as URIs
+    fn dbus_activate(&self, app: &Ini) -> Result<(), String> {
+		let bus_name = app.id.strip_suffix(".desktop").filter(|name| !name.is_empty())
+			.ok_or_else(|| format!("{:?} is not a valid bus name", app.id))?;
+		let object_path = format!("/{}", bus_name.replace('.', "/").replace('-', "_"));
+		let mut command = Command::new("gdbus");
+		command.args(["call", "--session", "--dest", bus_name, "--object-path", &object_path]);
+		match self.args.is_empty() {
+			true => command.args(["--method", "org.freedesktop.Application.Activate", "{}"]),
+			false => {
+				let uris: Vec<String> = self.args.iter().map(|arg| format!("'{}'", file_uri(arg))).collect();
+				command.args(["--method", "org.freedesktop.Application.Open", &format!("[{}]", uris.join(", ")), "{}"])
 			},
-			Err(spawn_err) => Err(RunError::Exec(spawn_err))
+		};
+		if let Some(locale) = &self.locale {
+			command.env("LC_ALL", locale).env("LANG", locale);
+		}
+		let output = command.stdin(Stdio::null()).output().map_err(|e| format!("gdbus could not run: {e}"))?;
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
 		}
     }
 
-    pub fn run(&self, stdout: bool) -> Result<(), RunError> {
-		let all_apps = Installed.all()?;
-		for app_entry in all_apps.into_iter() {
-			if app_entry.name.to_lowercase() == self.name.to_lowercase() {
-				return self.sys_exec(app_entry, stdout)
-			};
+    /// Starts every invocation of `app` and hands the children to the caller without waiting on them.
+    /// DBusActivatable entries are activated over D-Bus instead and give no children, their Exec
+    /// only runs when that fails.
+    pub fn spawn(&self, app: &Ini, mode: SpawnMode) -> Result<Vec<Child>, RunError> {
+		let children = match app.dbus_activatable {
+			true => match self.dbus_activate(app) {
+				Ok(()) => Vec::new(),
+				Err(dbus) => self.spawn_exec(app, mode).map_err(|e| RunError::Activation(app.name.clone(), dbus, Box::new(e)))?,
+			},
+			false => self.spawn_exec(app, mode)?,
 		};
-		Err(RunError::NotFound(self.name.clone()))
+		if self.history {
+			let mut history = History::load();
+			history.record(app, History::now());
+			if let Err(e) = history.save() {
+				eprintln!("Could not save the launch history: {e}");
+			}
+		}
+		Ok(children)
+    }
+
+    fn spawn_exec(&self, app: &Ini, mode: SpawnMode) -> Result<Vec<Child>, RunError> {
+		let mut children = Vec::new();
+		for resolved in self.resolve(app)? {
+			let mut command = self.command(resolved, mode);
+			command.envs(app.env.iter().map(|(name, value)| (name, value)));
+			//An ID tal was started with belongs to tal's own startup, never to what it launches
+			match app.startup_notify {
+				true => command.env("DESKTOP_STARTUP_ID", self.startup_id.clone().unwrap_or_default().as_str()),
+				false => command.env_remove("DESKTOP_STARTUP_ID"),
+			};
+			children.push(command.spawn().map_err(RunError::Exec)?);
+		}
+		Ok(children)
+    }
+
+    fn sys_exec(&self, app: Ini, mode: SpawnMode) -> Result<(), RunError> {
+		let children = self.spawn(&app, mode)?;
+		println!("Launching application {:?}.", app.name);
+		for mut child_proc in children {
+			match mode {
+				SpawnMode::Attached => {
+					let status = child_proc.wait().map_err(RunError::Exec)?;
+					if !status.success() {
+						return Err(RunError::Exited(app.name, status))
+					}
+				},
+				//Reaped in the background so a batch of launches leaves no zombies while tal runs
+				SpawnMode::Detached | SpawnMode::Background => drop(thread::spawn(move || child_proc.wait())),
+			}
+		}
+		Ok(())
+    }
+
+    pub fn launch(&self, app: Ini, mode: SpawnMode) -> Result<(), RunError> {
+		self.sys_exec(app, mode)
+    }
+
+    /// Every entry named exactly like this launch in listing order, a launch by name picks the first
+    pub fn candidates(&self, installed: &Installed) -> Result<Vec<Ini>, RunError> {
+		let query = self.name.to_lowercase();
+		Ok(installed.all()?.into_iter().filter(|app_entry| {
+			let untranslated_match = app_entry.untranslated_name.as_ref().is_some_and(|name| name.to_lowercase() == query);
+			app_entry.name.to_lowercase() == query || untranslated_match
+		}).collect())
+    }
+
+    /// Reads the desktop file at `path` from outside the applications directories, in the locale the launch
+    /// uses. Its file name is the desktop file ID, a file that can't be read or decoded is a [`RunError::InvalidFile`].
+    pub fn read_file(&self, path: &Path) -> Result<Ini, RunError> {
+		let invalid = |e| RunError::InvalidFile(path.to_owned(), e);
+		let locale = self.locale.as_deref().map_or_else(Locale::from_env, Locale::parse);
+		let bytes = fs::read(path).map_err(|e| invalid(DecodeError::Read(e)))?;
+		let contents = String::from_utf8(bytes).map_err(|_| invalid(DecodeError::NotUtf8))?;
+		let mut ini = ApplicationEntry::new(contents).decode(locale.as_ref()).map_err(invalid)?;
+		ini.id = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+		ini.modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+		ini.file = Some(path.to_owned());
+		Ok(ini)
+    }
+
+    /// The entry a launch by name would pick, see [`Installed::find`]
+    pub fn find(&self, installed: &Installed) -> Result<Ini, RunError> {
+		installed.find(&self.name)
+    }
+
+    /// The entry to launch with `action` applied. Without `action` a name that isn't found is tried as
+    /// `app:action`, so `firefox:new-private-window` works while names with a colon still match first.
+    pub fn find_target(&self, installed: &Installed, action: Option<&str>) -> Result<Ini, RunError> {
+		if let Some(action) = action {
+			return self.find(installed)?.with_action(action)
+		}
+		match self.find(installed) {
+			Err(RunError::NotFound(name)) => match name.rsplit_once(':') {
+				Some((app, action)) if !app.is_empty() && !action.is_empty() => match installed.find(app) {
+					Ok(app) => app.with_action(action),
+					Err(RunError::NotFound(_)) => Err(RunError::NotFound(name)),
+					Err(e) => Err(e),
+				},
+				_ => Err(RunError::NotFound(name)),
+			},
+			found => found,
+		}
+    }
+
+    pub fn run(&self, installed: &Installed, mode: SpawnMode) -> Result<(), RunError> {
+		self.sys_exec(self.find(installed)?, mode)
+	}
+
+    /// Launches the `action` desktop action of the entry [`Spawn::find`] picks, matched by key or Name.
+    /// An unknown action is a [`RunError::ActionNotFound`] listing the available ones.
+    pub fn run_action(&self, installed: &Installed, action: &str, mode: SpawnMode) -> Result<(), RunError> {
+		self.sys_exec(self.find_target(installed, Some(action))?, mode)
+	}
+
+    /// Like [`Spawn::run`] but returns the PIDs of the started processes instead of waiting on them
+    pub fn run_pid(&self, installed: &Installed, mode: SpawnMode) -> Result<Vec<u32>, RunError> {
+		let children = self.spawn(&self.find(installed)?, mode)?;
+		Ok(children.iter().map(Child::id).collect())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedCommand {
+	pub name: String,
+	pub program: String,
+	pub args: Vec<String>,
+	pub terminal: bool,
+	pub cwd: Option<PathBuf>,
+	/// The desktop file of the entry the command was resolved from
+	pub file: Option<PathBuf>,
+}
+impl ResolvedCommand {
+	pub fn to_json(&self) -> Json {
+		Json::obj([
+			("name", Json::from(self.name.as_str())),
+			("program", self.program.as_str().into()),
+			("args", self.args.clone().into()),
+			("terminal", self.terminal.into()),
+			("cwd", self.cwd.as_ref().map(|cwd| cwd.to_string_lossy().into_owned()).into()),
+			("file", self.file.as_ref().map(|file| file.to_string_lossy().into_owned()).into()),
+		])
+	}
+}
+//The argv quoted for a POSIX shell, so it can be pasted and run as is
+impl fmt::Display for ResolvedCommand {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", shell_quote_program(&self.program))?;
+		self.args.iter().try_for_each(|arg| write!(f, " {}", shell_quote(arg)))
 	}
 }
 
-pub struct Installed;
+/// What makes two entries the same when shadowing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DedupKey {
+	/// The desktop file ID, as the specification shadows
+	#[default]
+	Id,
+	Name,
+	/// Exec without its field codes, so wrappers launching the same command collapse
+	Exec,
+}
+impl DedupKey {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"id" => Some(Self::Id),
+			"name" => Some(Self::Name),
+			"exec" => Some(Self::Exec),
+			_ => None,
+		}
+	}
+
+	pub fn key<'a>(&self, ini: &'a Ini) -> Cow<'a, str> {
+		match self {
+			Self::Id => Cow::Borrowed(&ini.id),
+			Self::Name => Cow::Borrowed(&ini.name),
+			Self::Exec => Cow::Owned(strip_field_codes(&ini.exec)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+	User,
+	Flatpak,
+	Snap,
+	System,
+	Custom,
+}
+impl Source {
+	/// Sources in the order `all()` collects them
+	pub const ALL: [Self; 4] = [Self::User, Self::Flatpak, Self::Snap, Self::System];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|source| source.name() == name)
+	}
+
+	pub const fn name(&self) -> &'static str {
+		match self {
+			Self::User => "user",
+			Self::Flatpak => "flatpak",
+			Self::Snap => "snap",
+			Self::System => "system",
+			Self::Custom => "custom",
+		}
+	}
+}
+
+//What `Installed::scan_files` found
+struct Scan {
+	files: Vec<(String, PathBuf)>,
+	//The source of every one of `files`
+	sources: Vec<Source>,
+	errors: Vec<ReadError>,
+	//Whether any directory could be read, even an empty one
+	read_any: bool,
+}
+
+pub struct Installed {
+	jobs: NonZeroUsize,
+	locale: Option<Locale>,
+	dirs: Vec<(Source, PathBuf)>,
+	verbose: bool,
+	dedup: DedupKey,
+	cache: Option<EntryCache>,
+	desktops: Vec<String>,
+	matching: MatchOptions,
+	filter: bool,
+}
+impl Default for Installed {
+	fn default() -> Self {
+		Self::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+	}
+}
 impl Installed {
 	pub const UNIX_FLATPAK_APPS_PATH: &str = "/var/lib/flatpak/exports/share/applications";
+	pub const UNIX_SNAP_APPS_PATH: &str = "/var/lib/snapd/desktop/applications";
 	pub const UNIX_USER_APPS_PATH: &str = ".local/share/applications";
 	pub const UNIX_SYS_APPS_PATH: &str = "/usr/share/applications";
+	pub const AUTOSTART_DIR: &str = "autostart";
 
-	fn to_inis(&self, apps: &[PathBuf]) -> Vec<Ini> {
-		apps.iter().filter_map(|app_buf| {
-			fs::read(app_buf).ok()
-				.and_then(|bytes| String::from_utf8(bytes).ok())
-				.and_then(|entry_inner| ApplicationEntry::new(entry_inner).decode())
-		}).collect()
+	pub fn new(jobs: NonZeroUsize) -> Self {
+		Self {
+			jobs, locale: Locale::from_env(), dirs: Self::default_dirs(), verbose: false, dedup: DedupKey::Id, cache: None,
+			desktops: current_desktops(), matching: MatchOptions::default(), filter: true,
+		}
+	}
+
+	/// Reads applications from the given directories only, earlier directories shadow later ones
+	pub fn from_paths(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+		Self::from_dirs(paths.into_iter().map(|path| (Source::Custom, path)))
+	}
+
+	pub fn from_dirs(dirs: impl IntoIterator<Item = (Source, PathBuf)>) -> Self {
+		Self { dirs: dirs.into_iter().collect(), ..Self::default() }
 	}
 
-	fn get_app_bufs(&self, read_dir: Option<ReadDir>) -> Option<Vec<PathBuf>> {
-		read_dir.map(|apps_dir| {
-			apps_dir.filter_map(Result::ok).filter_map(|entry| {
-				let path = entry.path();
-				let f_type = entry.file_type().ok()?;
-				match (f_type.is_file() || f_type.is_symlink()) && path.extension()? == "desktop" {
-					true => Some(path),
-					false => None,
+	/// How many desktop files are read and decoded at once
+	pub const fn with_jobs(mut self, jobs: NonZeroUsize) -> Self {
+		self.jobs = jobs;
+		self
+	}
+
+	/// Reorders the directories so sources earlier in `priority` shadow the later ones
+	pub fn with_source_priority(mut self, priority: &[Source]) -> Self {
+		self.dirs.sort_by_key(|(source, _)| priority.iter().position(|prioritized| prioritized == source).unwrap_or(priority.len()));
+		self
+	}
+
+	pub fn with_locale(mut self, locale: Option<Locale>) -> Self {
+		self.locale = locale;
+		self
+	}
+
+	/// Which key decides that a later entry is shadowed by an earlier one, the desktop file ID by default
+	pub const fn with_dedup(mut self, dedup: DedupKey) -> Self {
+		self.dedup = dedup;
+		self
+	}
+
+	/// Read the entries of unchanged desktop files from `cache` and refresh it when a file changed, for [`Installed::all`]
+	pub fn with_cache(mut self, cache: Option<EntryCache>) -> Self {
+		self.cache = cache;
+		self
+	}
+
+	/// The desktops OnlyShowIn/NotShowIn are checked against, `$XDG_CURRENT_DESKTOP` by default.
+	/// Without any both keys are ignored.
+	pub fn with_desktops(mut self, desktops: Vec<String>) -> Self {
+		self.desktops = desktops;
+		self
+	}
+
+	/// Whether listings leave out NoDisplay and Hidden entries, entries for other desktops and entries
+	/// whose TryExec isn't installed, they do by default
+	pub const fn with_filter(mut self, filter: bool) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// How [`Installed::find`] matches names
+	pub const fn with_matching(mut self, matching: MatchOptions) -> Self {
+		self.matching = matching;
+		self
+	}
+
+	/// Report files that fail to decode on stderr instead of skipping them silently
+	pub fn with_verbose(mut self, verbose: bool) -> Self {
+		self.verbose = verbose;
+		self
+	}
+
+	/// The applications directories of the given XDG data directories: the user's below `data_home`,
+	/// the user and system flatpak exports, snapd's desktop directory and one per `data_dirs` entry.
+	/// `data_dirs` entries that are flatpak exports count as flatpak and ones ending in `snapd/desktop`
+	/// as snap rather than system.
+	pub fn resolve_dirs(data_home: Option<&Path>, data_dirs: &[PathBuf]) -> Vec<(Source, PathBuf)> {
+		let flatpak_dirs: Vec<PathBuf> = data_home.map(|data_home| data_home.join("flatpak/exports/share/applications")).into_iter()
+			.chain([PathBuf::from(Self::UNIX_FLATPAK_APPS_PATH)])
+			.collect();
+		let mut dirs: Vec<(Source, PathBuf)> = data_home.map(|data_home| (Source::User, data_home.join("applications"))).into_iter()
+			.chain(flatpak_dirs.iter().map(|dir| (Source::Flatpak, dir.clone())))
+			.chain([(Source::Snap, PathBuf::from(Self::UNIX_SNAP_APPS_PATH))])
+			.collect();
+		for data_dir in data_dirs {
+			let dir = data_dir.join("applications");
+			if !dirs.iter().any(|(_, known)| *known == dir) {
+				let source = if data_dir.ends_with("snapd/desktop") { Source::Snap } else { Source::System };
+				dirs.push((source, dir));
+			}
+		}
+		dirs
+	}
+
+	fn default_dirs() -> Vec<(Source, PathBuf)> {
+		Self::resolve_dirs(xdg::data_home().as_deref(), &xdg::data_dirs())
+	}
+
+	/// The desktop file ID is the path below the applications directory with `/` replaced by `-`
+	pub fn desktop_file_id(root: &Path, app_buf: &Path) -> Option<String> {
+		let relative = app_buf.strip_prefix(root).ok()?;
+		let components: Option<Vec<&str>> = relative.components().map(|component| component.as_os_str().to_str()).collect();
+		Some(components?.join("-"))
+	}
+
+	fn to_ini(&self, (id, app_buf): &(String, PathBuf)) -> Option<Ini> {
+		let modified = fs::metadata(app_buf).and_then(|meta| meta.modified()).ok();
+		self.decode_file(id, app_buf, modified, fs::read(app_buf))
+	}
+
+	fn decode_bytes(&self, bytes: Result<Vec<u8>, Error>) -> Result<Ini, DecodeError> {
+		bytes.map_err(DecodeError::Read)
+			.and_then(|bytes| String::from_utf8(bytes).map_err(|_| DecodeError::NotUtf8))
+			.and_then(|entry_inner| ApplicationEntry::new(entry_inner).decode(self.locale.as_ref()))
+	}
+
+	fn decode_file(&self, id: &str, app_buf: &Path, modified: Option<SystemTime>, bytes: Result<Vec<u8>, Error>) -> Option<Ini> {
+		match self.decode_bytes(bytes) {
+			Ok(mut ini) => {
+				ini.id = id.to_owned();
+				ini.modified = modified;
+				ini.file = Some(app_buf.to_owned());
+				Some(ini)
+			},
+			Err(e) => {
+				if self.verbose {
+					eprintln!("Skipping {}: {e}", app_buf.display());
 				}
-			}).collect()
-		})
+				None
+			}
+		}
 	}
 
-	fn read(&self, path: &str) -> Option<Vec<Ini>> {
-		let sys_apps = self.get_app_bufs(fs::read_dir(path).ok())?;
-		Some(self.to_inis(&sys_apps))
+	//The entry of `file` read from disk as the cache keeps it, `None` when it can't be read at all
+	fn read_cached(&self, file: &CachedFile) -> Option<CachedEntry> {
+		match self.decode_bytes(fs::read(&file.path)) {
+			Ok(mut ini) => {
+				ini.id = file.id.clone();
+				ini.modified = file.modified;
+				ini.file = Some(file.path.clone());
+				Some(CachedEntry::Entry(Box::new(ini)))
+			},
+			Err(e @ DecodeError::Read(_)) => {
+				if self.verbose {
+					eprintln!("Skipping {}: {e}", file.path.display());
+				}
+				None
+			},
+			Err(e) => Some(CachedEntry::Invalid(e.to_string())),
+		}
+	}
+
+	//Decodes only the files `cache` doesn't hold unchanged and refreshes it when any of them changed
+	fn decode_cached(&self, apps: &[(String, PathBuf)], cache: Option<&EntryCache>) -> Vec<Ini> {
+		let Some(cache) = cache else { return self.decode_all(apps) };
+		let files: Vec<CachedFile> = apps.iter().map(|(id, path)| CachedFile {
+			id: id.clone(),
+			path: path.clone(),
+			modified: fs::metadata(path).and_then(|meta| meta.modified()).ok(),
+		}).collect();
+		let (cached, exact) = cache.load_each(&files, self.locale.as_ref());
+		let cached: Vec<Mutex<Option<CachedEntry>>> = cached.into_iter().map(Mutex::new).collect();
+		let decoded = self.each_parallel(files.len(), |index| match cached[index].lock().ok()?.take() {
+			Some(entry) => Some(entry),
+			None => self.read_cached(&files[index]),
+		});
+		//A file that can't be read leaves the cache as it was
+		if !exact && decoded.iter().all(Option::is_some) {
+			let entries: Vec<CachedEntry> = decoded.into_iter().flatten().collect();
+			if let Err(e) = cache.save(&files, &entries, self.locale.as_ref()) && self.verbose {
+				eprintln!("Could not write the entry cache {}: {e}", cache.path().display());
+			}
+			return entries.into_iter().zip(&files).filter_map(|(entry, file)| self.cached_ini(file, entry)).collect()
+		}
+		decoded.into_iter().zip(&files).filter_map(|(entry, file)| self.cached_ini(file, entry?)).collect()
+	}
+
+	//The listed form of a cached entry, the ones that failed to decode are reported like fresh ones
+	fn cached_ini(&self, file: &CachedFile, entry: CachedEntry) -> Option<Ini> {
+		match entry {
+			CachedEntry::Entry(ini) => Some(*ini),
+			CachedEntry::Invalid(message) => {
+				if self.verbose {
+					eprintln!("Skipping {}: {message}", file.path.display());
+				}
+				None
+			},
+		}
+	}
+
+	fn decode_all(&self, apps: &[(String, PathBuf)]) -> Vec<Ini> {
+		self.each_parallel(apps.len(), |index| self.to_ini(&apps[index])).into_iter().flatten().collect()
+	}
+
+	//Runs `work` for every index below `len` on up to `jobs` threads. A fixed set of workers pull
+	//the next index, so at most `jobs` files are in flight. Each result lands in the slot of its
+	//index, which keeps discovery order, and with it the shadowing precedence, without sorting.
+	fn each_parallel<T: Send + Sync>(&self, len: usize, work: impl Fn(usize) -> Option<T> + Sync) -> Vec<Option<T>> {
+		let jobs = self.jobs.get().min(len);
+		if jobs <= 1 {
+			return (0..len).map(work).collect()
+		}
+		let next_index = AtomicUsize::new(0);
+		let slots: Vec<OnceLock<T>> = (0..len).map(|_| OnceLock::new()).collect();
+		thread::scope(|scope| {
+			for _ in 0..jobs {
+				scope.spawn(|| loop {
+					let index = next_index.fetch_add(1, Ordering::Relaxed);
+					if index >= len {
+						break
+					}
+					if let Some(result) = work(index) {
+						let _ = slots[index].set(result);
+					}
+				});
+			}
+		});
+		slots.into_iter().map(OnceLock::into_inner).collect()
+	}
+
+	//Desktop files in `read_dir`, and in every directory below it while `visited` is given
+	fn get_app_bufs(&self, read_dir: ReadDir, visited: Option<&mut HashSet<PathBuf>>) -> Vec<PathBuf> {
+		let mut app_bufs = Vec::new();
+		let mut sub_dirs = Vec::new();
+		for entry in read_dir.filter_map(Result::ok) {
+			let path = entry.path();
+			let Ok(f_type) = entry.file_type() else { continue };
+			if f_type.is_dir() || (f_type.is_symlink() && path.is_dir()) {
+				sub_dirs.push(path);
+			} else if (f_type.is_file() || f_type.is_symlink()) && path.extension().is_some_and(|ext| ext == "desktop") {
+				app_bufs.push(path);
+			}
+		}
+		let Some(visited) = visited else { return app_bufs };
+		for dir in sub_dirs {
+			//Directories are entered once by their canonical path, so symlink cycles end
+			let Ok(canonical) = fs::canonicalize(&dir) else { continue };
+			if !visited.insert(canonical) {
+				continue
+			}
+			match fs::read_dir(&dir) {
+				Ok(read_dir) => app_bufs.append(&mut self.get_app_bufs(read_dir, Some(&mut *visited))),
+				Err(e) => if self.verbose {
+					eprintln!("Skipping {}: {e}", dir.display());
+				},
+			}
+		}
+		app_bufs
+	}
+
+	fn dir_files(&self, source: Source, dir: &Path, recursive: bool) -> Result<Vec<(String, PathBuf)>, ReadError> {
+		let read_dir = fs::read_dir(dir).map_err(|e| match e.kind() {
+			ErrorKind::NotFound => ReadError::NotFound(dir.to_owned()),
+			_ => ReadError::Io(source, dir.to_owned(), e),
+		})?;
+		let mut visited = recursive.then(|| HashSet::from_iter(fs::canonicalize(dir).ok()));
+		Ok(self.get_app_bufs(read_dir, visited.as_mut()).into_iter()
+			.filter_map(|app_buf| Some((Self::desktop_file_id(dir, &app_buf)?, app_buf)))
+			.collect())
+	}
+
+	//Applications directories are read recursively, a subdirectory becomes a prefix of the desktop file ID
+	fn app_files(&self, source: Source, dir: &Path) -> Result<Vec<(String, PathBuf)>, ReadError> {
+		self.dir_files(source, dir, true)
+	}
+
+	//Keeps the first entry of every dedup key, the desktop file ID unless configured otherwise
+	fn shadow(&self, mut entries: Vec<Ini>) -> Vec<Ini> {
+		//Borrowed keys and an in place retain, so no ID is copied and nothing is reallocated
+		let keep: Vec<bool> = {
+			let mut seen_keys = HashSet::with_capacity(entries.len());
+			entries.iter().map(|ini| seen_keys.insert(self.dedup.key(ini))).collect()
+		};
+		let mut keep = keep.into_iter();
+		entries.retain(|_| keep.next().unwrap_or(false));
+		entries
+	}
+
+	//Shadowing happens before filtering so a NoDisplay or Hidden override also hides the entry it shadows
+	fn listed(&self, entries: Vec<Ini>) -> Vec<Ini> {
+		let mut listed = self.shadow(entries);
+		if self.filter {
+			listed.retain(|ini| !ini.no_display && !ini.hidden && ini.shown_in(&self.desktops) && ini.try_exec_found());
+		}
+		listed
+	}
+
+	//Decoded entries of every directory belonging to `source`, missing directories count as empty
+	//unless none of them exist
+	fn read(&self, source: Source) -> Result<Vec<Ini>, ReadError> {
+		let mut source_apps: Option<Vec<(String, PathBuf)>> = None;
+		let mut not_found = None;
+		for (_, dir) in self.dirs.iter().filter(|(dir_source, _)| *dir_source == source) {
+			match self.app_files(source, dir) {
+				Ok(mut dir_apps) => source_apps.get_or_insert_default().append(&mut dir_apps),
+				Err(ReadError::NotFound(path)) => { not_found.get_or_insert(path); },
+				Err(e) => return Err(e),
+			}
+		}
+		match (source_apps, not_found) {
+			(Some(apps), _) => Ok(self.decode_all(&apps)),
+			(None, Some(path)) => Err(ReadError::NotFound(path)),
+			(None, None) => Ok(Vec::new()),
+		}
+	}
+
+	/// The files with desktop file ID `id` that lose to the first one, in the order they are shadowed
+	pub fn shadowed(&self, id: &str) -> Vec<PathBuf> {
+		self.scan_files().files.into_iter().filter(|(file_id, _)| file_id == id).skip(1).map(|(_, path)| path).collect()
+	}
+
+	/// Every desktop file below `root` decoded, with desktop file IDs relative to `root` like in an
+	/// applications directory. Symlinked subdirectories are followed but every directory is read once,
+	/// so symlink cycles end. Entries are neither shadowed nor filtered, a `root` that can't be read
+	/// gives nothing.
+	pub fn scan_dir(&self, root: &Path) -> Vec<Ini> {
+		self.app_files(Source::Custom, root).map(|apps| self.decode_all(&apps)).unwrap_or_default()
+	}
+
+	/// The scanned directories in shadowing order
+	pub fn dirs(&self) -> &[(Source, PathBuf)] {
+		&self.dirs
+	}
+
+	/// The desktop files that fail to decode, shadowed ones included
+	pub fn decode_errors(&self) -> Vec<(PathBuf, DecodeError)> {
+		self.scan_files().files.into_iter()
+			.filter_map(|(_, path)| self.decode_bytes(fs::read(&path)).err().map(|e| (path, e)))
+			.collect()
+	}
+
+	/// Every desktop file in the scanned directories including shadowed ones, directories that don't exist are skipped
+	pub fn desktop_files(&self) -> Result<Vec<(Source, PathBuf)>, ReadError> {
+		let mut files = Vec::new();
+		for (source, dir) in &self.dirs {
+			match self.app_files(*source, dir) {
+				Ok(dir_apps) => files.extend(dir_apps.into_iter().map(|(_, path)| (*source, path))),
+				Err(ReadError::NotFound(_)) => (),
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(files)
 	}
 
-	pub fn flatpak(&self) -> Option<Vec<Ini>> {
-		self.read(Self::UNIX_FLATPAK_APPS_PATH)
+	pub fn flatpak(&self) -> Result<Vec<Ini>, ReadError> {
+		self.source(Source::Flatpak)
 	}
 
-	pub fn system(&self) -> Option<Vec<Ini>> {
-		self.read(Self::UNIX_SYS_APPS_PATH)
+	pub fn snap(&self) -> Result<Vec<Ini>, ReadError> {
+		self.source(Source::Snap)
 	}
 
-	pub fn user(&self) -> Option<Vec<Ini>> {
-		let user_apps = self.get_app_bufs(env::home_dir().map(|mut home| {
-			home.push(Self::UNIX_USER_APPS_PATH);
-			home
-		}).and_then(|user_apps| fs::read_dir(user_apps).ok()))?;
-		Some(self.to_inis(&user_apps))
+	pub fn system(&self) -> Result<Vec<Ini>, ReadError> {
+		self.source(Source::System)
 	}
 
+	pub fn user(&self) -> Result<Vec<Ini>, ReadError> {
+		self.source(Source::User)
+	}
+
+	fn autostart_dirs(&self) -> Vec<PathBuf> {
+		xdg::config_home().into_iter()
+			.chain(xdg::config_dirs())
+			.map(|dir| dir.join(Self::AUTOSTART_DIR))
+			.collect()
+	}
+
+	/// Entries from the XDG autostart directories, a file name found earlier shadows the same name in later directories
+	pub fn autostart(&self) -> Vec<Ini> {
+		let desktops = current_desktops();
+		let mut seen_ids = HashSet::new();
+		let autostart_apps: Vec<(String, PathBuf)> = self.autostart_dirs().into_iter()
+			//Autostart directories aren't recursive
+			.filter_map(|dir| match self.dir_files(Source::Custom, &dir, false) {
+				Ok(dir_apps) => Some(dir_apps),
+				Err(ReadError::NotFound(_)) => None,
+				Err(e) => {
+					if self.verbose {
+						eprintln!("Skipping {}: {e}", dir.display());
+					}
+					None
+				},
+			})
+			.flatten()
+			.filter(|(id, _)| seen_ids.insert(id.clone()))
+			.collect();
+		self.decode_all(&autostart_apps).into_iter().filter(|ini| ini.autostarts(&desktops)).collect()
+	}
+
+	/// Listed entries of one source, `ReadError::NotFound` when none of its directories exist
+	pub fn source(&self, source: Source) -> Result<Vec<Ini>, ReadError> {
+		self.read(source).map(|entries| self.listed(entries))
+	}
+
+	/// Listed entries per source, counted in the listing [`Installed::all`] gives so an entry shadowed
+	/// by another source's isn't counted twice. A source that can't be read counts as 0.
+	pub fn counts(&self) -> Vec<(Source, usize)> {
+		let scan = self.scan_files();
+		let mut file_sources = HashMap::with_capacity(scan.files.len());
+		for ((_, path), source) in scan.files.iter().zip(&scan.sources) {
+			file_sources.entry(path.as_path()).or_insert(*source);
+		}
+		let entries = self.listed(self.decode_cached(&scan.files, self.cache.as_ref()));
+		let source_of = |ini: &Ini| ini.file.as_deref().and_then(|file| file_sources.get(file)).copied();
+		Source::ALL.into_iter()
+			.map(|source| (source, entries.iter().filter(|ini| source_of(ini) == Some(source)).count()))
+			.collect()
+	}
+
+	fn sources(&self) -> Vec<Source> {
+		let mut sources: Vec<Source> = Vec::with_capacity(Source::ALL.len());
+		for (source, _) in &self.dirs {
+			if !sources.contains(source) {
+				sources.push(*source);
+			}
+		}
+		sources
+	}
+
+	//The desktop files of every source in scan order, listed before anything is decoded so the results
+	//are collected in one pass into storage reserved for the total. Directories that don't exist are skipped.
+	fn scan_files(&self) -> Scan {
+		let dirs: Vec<(Source, &PathBuf)> = self.sources().into_iter()
+			.flat_map(|source| self.dirs.iter().filter(move |(dir_source, _)| *dir_source == source).map(move |(_, dir)| (source, dir)))
+			.collect();
+		//Directories are listed concurrently, slow mounts then overlap instead of adding up
+		let listed = self.each_parallel(dirs.len(), |index| Some(self.app_files(dirs[index].0, dirs[index].1)));
+		let mut dir_files = Vec::with_capacity(dirs.len());
+		let mut errors = Vec::new();
+		let mut failed_source = None;
+		for ((source, _), files) in dirs.iter().zip(listed.into_iter().flatten()) {
+			//The rest of a failing source is skipped like a single failing source was before
+			if failed_source == Some(*source) {
+				continue
+			}
+			match files {
+				Ok(files) => dir_files.push((*source, files)),
+				Err(ReadError::NotFound(_)) => (),
+				Err(e) => {
+					errors.push(e);
+					failed_source = Some(*source);
+				},
+			}
+		}
+		let read_any = !dir_files.is_empty();
+		let total = dir_files.iter().map(|(_, files)| files.len()).sum();
+		let mut files = Vec::with_capacity(total);
+		let mut sources = Vec::with_capacity(total);
+		for (source, mut dir) in dir_files {
+			sources.extend(std::iter::repeat_n(source, dir.len()));
+			files.append(&mut dir);
+		}
+		Scan { files, sources, errors, read_any }
+	}
+
+	/// Scans every source, keeping whatever could be read along with the errors of the sources that failed,
+	/// sources that aren't installed are not errors
+	pub fn all_with_errors(&self) -> (Vec<Ini>, Vec<RunError>) {
+		let scan = self.scan_files();
+		(self.listed(self.decode_cached(&scan.files, self.cache.as_ref())), scan.errors.into_iter().map(RunError::from).collect())
+	}
+
+	/// The listed entry `query` names, see [`find_entry`] for how it is matched
+	pub fn find(&self, query: &str) -> Result<Ini, RunError> {
+		find_entry(self.all()?, query, self.matching)
+	}
+
+	/// Every listed entry. A source that isn't installed is empty and one that can't be read is skipped,
+	/// reported on stderr when verbose, it's only an error when no source could be read at all.
 	pub fn all(&self) -> Result<Vec<Ini>, RunError> {
-		let mut user_apps = self.user().ok_or(RunError::User)?;
-		let mut sys_apps = self.system().ok_or(RunError::System)?;
-		let mut flatpak_apps = self.flatpak().ok_or(RunError::Flatpak)?;
-		user_apps.append(&mut flatpak_apps);
-		user_apps.append(&mut sys_apps);
-		Ok(user_apps)
+		self.all_through(self.cache.as_ref())
+	}
+
+	/// [`Installed::all`] through the entry cache set with [`Installed::with_cache`], or the one in
+	/// [`EntryCache::default_dir`] without it, so only the desktop files that changed are decoded
+	pub fn all_cached(&self) -> Result<Vec<Ini>, RunError> {
+		let default_cache = self.cache.is_none().then(EntryCache::default_dir).flatten().map(EntryCache::new);
+		self.all_through(self.cache.as_ref().or(default_cache.as_ref()))
+	}
+
+	fn all_through(&self, cache: Option<&EntryCache>) -> Result<Vec<Ini>, RunError> {
+		let mut scan = self.scan_files();
+		if !scan.read_any && !scan.errors.is_empty() {
+			return Err(scan.errors.remove(0).into())
+		}
+		if self.verbose {
+			scan.errors.iter().for_each(|e| eprintln!("Skipped a source: {e}"));
+		}
+		Ok(self.listed(self.decode_cached(&scan.files, cache)))
 	}
 }
 
-pub struct Display(bool);
+/// Prints listings through the [`Renderer`](crate::render::Renderer) of the chosen format
+pub struct Display {
+	details: bool,
+	format: Format,
+	strip_field_codes: bool,
+	recent: Option<History>,
+}
 impl Display {
 	pub const fn new(show_details: bool) -> Self {
-		Self(show_details)
+		Self { details: show_details, format: Format::Text, strip_field_codes: false, recent: None }
+	}
+
+	/// List the most frequently and recently launched apps first, see [`History::sort`]
+	pub fn with_recent(mut self, history: Option<History>) -> Self {
+		self.recent = history;
+		self
+	}
+
+	/// Show Exec in its clean form without field codes in the text details, instead of as written
+	pub const fn with_strip_field_codes(mut self, strip_field_codes: bool) -> Self {
+		self.strip_field_codes = strip_field_codes;
+		self
+	}
+
+	pub const fn with_format(mut self, format: Format) -> Self {
+		self.format = format;
+		self
+	}
+
+	fn render(&self, entries: &[Ini]) {
+		let out = io::stdout();
+		match self.format {
+			Format::Text => render::render(&mut render::Text::new(out, self.details).with_strip_field_codes(self.strip_field_codes), entries),
+			Format::Json => render::render(&mut render::JsonArray::new(out).with_details(self.details), entries),
+			Format::Ndjson => render::render(&mut render::Ndjson::new(out).with_details(self.details), entries),
+		}
 	}
 
 	#[inline]
 	pub fn actions(&self, actions: Actions) {
-		if actions.is_empty() { return; }
-		actions.into_iter().for_each(|(name, act)| {
-			println!("\n\t[Action]\n\t{name}\n\t- Name={}\n\t- Exec={}\n\t- Terminal={}",
-				act.name.unwrap_or("None".to_owned()),
-				act.exec.unwrap_or("None".to_owned()),
-				act.terminal.unwrap_or(false))
-		});
+		let mut text = render::Text::new(io::stdout(), true).with_strip_field_codes(self.strip_field_codes);
+		let mut actions: Vec<_> = actions.into_iter().collect();
+		actions.sort_by(|(a, _), (b, _)| a.cmp(b));
+		actions.iter().for_each(|(id, action)| text.action(id, action));
 	}
 
 	#[inline]
-	pub fn names(&self, entries: Vec<Ini>) {
-		entries.into_iter().for_each(|app| match self.0 {
-			true => {
-				println!("{app}");
-				self.actions(app.actions);
-			},
-			false => println!("{}", app.name)
-		});
+	pub fn names(&self, mut entries: Vec<Ini>) {
+		if let Some(history) = &self.recent {
+			history.sort(&mut entries, History::now());
+		}
+		self.render(&entries);
+	}
+
+	#[inline]
+	pub fn tree(&self, tree: Tree) {
+		let out = io::stdout();
+		match self.format {
+			Format::Text => render::render_tree(&mut render::TreeText::new(out), &tree),
+			Format::Json => println!("{}", crate::category::tree_json(tree)),
+			Format::Ndjson => render::render_tree(&mut render::Ndjson::new(out), &tree),
+		}
 	}
 
+	/// A source that isn't installed lists nothing, which is `[]` as JSON, other read failures are returned
 	#[inline]
-	pub fn entries(&self, entries_maybe: Option<Vec<Ini>>) {
+	pub fn entries(&self, entries_maybe: Result<Vec<Ini>, ReadError>) -> Result<(), ReadError> {
 		match entries_maybe {
-			Some(entries) => self.names(entries),
-			None => eprintln!("Failed to display entries."),
+			Ok(entries) => self.names(entries),
+			Err(ReadError::NotFound(_)) => self.names(Vec::new()),
+			Err(e) => return Err(e),
 		}
+		Ok(())
 	}
 }
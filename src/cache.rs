@@ -0,0 +1,323 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, process, sync::atomic::{AtomicUsize, Ordering}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::apps::{Ini, IniAction};
+use crate::locale::Locale;
+use crate::xdg;
+
+const HEADER: &str = "tal-entries 2\n";
+
+//Tells apart the temp files of saves running at the same time in one process
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// A desktop file as found by a scan, the cache is only used while every one of them is unchanged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedFile {
+	pub id: String,
+	pub path: PathBuf,
+	pub modified: Option<SystemTime>,
+}
+
+/// What the cache holds for a desktop file, its entry or why it failed to decode
+#[derive(Debug, Clone, PartialEq)]
+pub enum CachedEntry {
+	Entry(Box<Ini>),
+	Invalid(String),
+}
+
+fn time_field(time: Option<SystemTime>) -> String {
+	match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+		Some(since) => format!("{}.{:09}", since.as_secs(), since.subsec_nanos()),
+		None => "-".to_owned(),
+	}
+}
+
+//The best matching `Key[locale]` suffix names the locale the entries were decoded in
+fn locale_field(locale: Option<&Locale>) -> String {
+	locale.and_then(|locale| locale.keys().into_iter().next()).unwrap_or_else(|| "-".to_owned())
+}
+
+//Values are written one per line, so the characters splitting fields and lines are escaped
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> Option<String> {
+	let mut unescaped = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unescaped.push(c);
+			continue;
+		}
+		unescaped.push(match chars.next()? {
+			'\\' => '\\',
+			't' => '\t',
+			'n' => '\n',
+			'r' => '\r',
+			_ => return None,
+		});
+	}
+	Some(unescaped)
+}
+
+fn bool_field(value: &str) -> Option<bool> {
+	match value {
+		"true" => Some(true),
+		"false" => Some(false),
+		_ => None,
+	}
+}
+
+//The `key\tvalue` lines of an entry, optional keys only when they are set and lists one line per item
+fn write_entry(out: &mut String, ini: &Ini) {
+	let mut field = |key: &str, value: &str| {
+		out.push_str(key);
+		out.push('\t');
+		out.push_str(&escape(value));
+		out.push('\n');
+	};
+	field("name", &ini.name);
+	field("exec", &ini.exec);
+	for (key, value) in [
+		("untranslated_name", &ini.untranslated_name), ("generic_name", &ini.generic_name), ("comment", &ini.comment),
+		("startup_wm_class", &ini.startup_wm_class), ("icon", &ini.icon), ("try_exec", &ini.try_exec), ("flatpak", &ini.flatpak),
+	] {
+		if let Some(value) = value {
+			field(key, value);
+		}
+	}
+	if let Some(path) = &ini.path {
+		field("path", &path.to_string_lossy());
+	}
+	for (key, set) in [
+		("terminal", ini.terminal), ("no_display", ini.no_display), ("hidden", ini.hidden),
+		("dbus_activatable", ini.dbus_activatable), ("startup_notify", ini.startup_notify),
+	] {
+		if set {
+			field(key, "true");
+		}
+	}
+	if let Some(enabled) = ini.autostart_enabled {
+		field("autostart_enabled", if enabled { "true" } else { "false" });
+	}
+	for (key, items) in [
+		("keyword", &ini.keywords), ("category", &ini.categories), ("only_show_in", &ini.only_show_in),
+		("not_show_in", &ini.not_show_in), ("mime_type", &ini.mime_types),
+	] {
+		items.iter().for_each(|item| field(key, item));
+	}
+	ini.env.iter().for_each(|(name, value)| field("env", &format!("{name}={value}")));
+	//Sorted so an unchanged listing writes the same cache, the HashMap order isn't stable
+	let mut actions: Vec<_> = ini.actions.iter().collect();
+	actions.sort_by_key(|(id, _)| *id);
+	for (id, action) in actions {
+		field("action", id);
+		for (key, value) in [("action_name", &action.name), ("action_exec", &action.exec), ("action_path", &action.path)] {
+			if let Some(value) = value {
+				field(key, value);
+			}
+		}
+		if let Some(terminal) = action.terminal {
+			field("action_terminal", if terminal { "true" } else { "false" });
+		}
+	}
+}
+
+//Sets the field of `key` on `entry`, `None` for a key the cache never writes
+fn read_field(entry: &mut CachedEntry, action: &mut Option<String>, key: &str, value: String) -> Option<()> {
+	if key == "invalid" {
+		*entry = CachedEntry::Invalid(value);
+		return Some(())
+	}
+	let CachedEntry::Entry(ini) = entry else { return None };
+	//The keys of an action belong to the last `action` line
+	if let Some(action_key) = key.strip_prefix("action_") {
+		let action = ini.actions.get_mut(action.as_ref()?)?;
+		match action_key {
+			"name" => action.name = Some(value),
+			"exec" => action.exec = Some(value),
+			"path" => action.path = Some(value),
+			"terminal" => action.terminal = Some(bool_field(&value)?),
+			_ => return None,
+		}
+		return Some(())
+	}
+	match key {
+		"name" => ini.name = value,
+		"exec" => ini.exec = value,
+		"untranslated_name" => ini.untranslated_name = Some(value),
+		"generic_name" => ini.generic_name = Some(value),
+		"comment" => ini.comment = Some(value),
+		"startup_wm_class" => ini.startup_wm_class = Some(value),
+		"icon" => ini.icon = Some(value),
+		"try_exec" => ini.try_exec = Some(value),
+		"flatpak" => ini.flatpak = Some(value),
+		"path" => ini.path = Some(PathBuf::from(value)),
+		"terminal" => ini.terminal = bool_field(&value)?,
+		"no_display" => ini.no_display = bool_field(&value)?,
+		"hidden" => ini.hidden = bool_field(&value)?,
+		"dbus_activatable" => ini.dbus_activatable = bool_field(&value)?,
+		"startup_notify" => ini.startup_notify = bool_field(&value)?,
+		"autostart_enabled" => ini.autostart_enabled = Some(bool_field(&value)?),
+		"keyword" => ini.keywords.push(value),
+		"category" => ini.categories.push(value),
+		"only_show_in" => ini.only_show_in.push(value),
+		"not_show_in" => ini.not_show_in.push(value),
+		"mime_type" => ini.mime_types.push(value),
+		"env" => {
+			let (name, value) = value.split_once('=')?;
+			ini.env.push((name.to_owned(), value.to_owned()));
+		},
+		"action" => {
+			ini.actions.insert(value.clone(), IniAction { name: None, exec: None, terminal: None, path: None });
+			*action = Some(value);
+		},
+		_ => return None,
+	}
+	Some(())
+}
+
+//A cached file by path: its ID, mtime field and entry
+type Records = HashMap<PathBuf, (String, String, CachedEntry)>;
+
+//Takes the entry cached for `file` out of `records` if its ID and mtime are unchanged
+fn take_hit(records: &mut Records, file: &CachedFile) -> Option<CachedEntry> {
+	let (id, modified, _) = records.get(&file.path)?;
+	if *id != file.id || *modified != time_field(file.modified) {
+		return None
+	}
+	records.remove(&file.path).map(|(_, _, entry)| match entry {
+		CachedEntry::Entry(mut ini) => {
+			ini.id = file.id.clone();
+			ini.file = Some(file.path.clone());
+			ini.modified = file.modified;
+			CachedEntry::Entry(ini)
+		},
+		invalid => invalid,
+	})
+}
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The decoded entries of every desktop file of the last scan in a single file, so a listing reads one
+/// file instead of hundreds and only decodes the files that changed. Entries are decoded in a locale,
+/// a cache written for another locale is a miss.
+#[derive(Debug, Clone)]
+pub struct EntryCache {
+	dir: PathBuf,
+	ttl: Option<Duration>,
+	refresh: bool,
+}
+impl EntryCache {
+	pub const FILE_NAME: &str = "entries";
+
+	/// `$XDG_CACHE_HOME/tal`, falling back to `~/.cache/tal`
+	pub fn default_dir() -> Option<PathBuf> {
+		xdg::cache_home().map(|cache_home| cache_home.join("tal"))
+	}
+
+	pub const fn new(dir: PathBuf) -> Self {
+		Self { dir, ttl: None, refresh: false }
+	}
+
+	/// Also refresh a cache older than `ttl` even when no file changed, only mtimes are checked without one
+	pub const fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	/// Treat the cache as missing so every file is read again, the cache is rewritten afterwards
+	pub const fn with_refresh(mut self, refresh: bool) -> Self {
+		self.refresh = refresh;
+		self
+	}
+
+	pub fn path(&self) -> PathBuf {
+		self.dir.join(Self::FILE_NAME)
+	}
+
+	//Every file in the cache, `None` for a cache that is missing, older than the TTL, corrupt, refreshed
+	//or written in another locale
+	fn records(&self, locale: Option<&Locale>) -> Option<Records> {
+		if self.refresh {
+			return None
+		}
+		let path = self.path();
+		if let Some(ttl) = self.ttl {
+			let age = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?.elapsed().ok()?;
+			if age > ttl {
+				return None
+			}
+		}
+		let contents = fs::read_to_string(&path).ok()?;
+		let mut lines = contents.strip_prefix(HEADER)?.lines();
+		if lines.next()?.strip_prefix("locale\t")? != locale_field(locale) {
+			return None
+		}
+		let mut records = HashMap::new();
+		let mut current: Option<(PathBuf, String, String, CachedEntry)> = None;
+		let mut action = None;
+		for line in lines {
+			let (key, value) = line.split_once('\t')?;
+			if key == "file" {
+				let mut fields = value.split('\t');
+				let (id, file_path, modified) = (unescape(fields.next()?)?, unescape(fields.next()?)?, fields.next()?.to_owned());
+				if let Some((file_path, id, modified, entry)) = current.replace((PathBuf::from(file_path), id, modified, CachedEntry::Entry(Box::default()))) {
+					records.insert(file_path, (id, modified, entry));
+				}
+				action = None;
+				continue;
+			}
+			let (_, _, _, entry) = current.as_mut()?;
+			read_field(entry, &mut action, key, unescape(value)?)?;
+		}
+		if let Some((file_path, id, modified, entry)) = current {
+			records.insert(file_path, (id, modified, entry));
+		}
+		Some(records)
+	}
+
+	/// The cached entries of `files` in their order, `None` unless the cache holds exactly these files
+	/// with these mtimes in this locale and is younger than the TTL. A corrupt cache is a miss as well.
+	pub fn load(&self, files: &[CachedFile], locale: Option<&Locale>) -> Option<Vec<CachedEntry>> {
+		let mut records = self.records(locale)?;
+		let entries: Option<Vec<CachedEntry>> = files.iter().map(|file| take_hit(&mut records, file)).collect();
+		entries.filter(|_| records.is_empty())
+	}
+
+	/// The cached entry of each of `files` that is unchanged, `None` for the ones that changed or are
+	/// new, so only those have to be decoded again. Also tells whether the cache holds exactly these files
+	/// with these mtimes, it needs no update then. Every file misses in a corrupt or expired cache.
+	pub fn load_each(&self, files: &[CachedFile], locale: Option<&Locale>) -> (Vec<Option<CachedEntry>>, bool) {
+		let Some(mut records) = self.records(locale) else { return (vec![None; files.len()], false) };
+		let entries: Vec<Option<CachedEntry>> = files.iter().map(|file| take_hit(&mut records, file)).collect();
+		let exact = records.is_empty() && entries.iter().all(Option::is_some);
+		(entries, exact)
+	}
+
+	/// Stores `entries` as the entries of `files` decoded in `locale`, creating the cache directory when needed
+	pub fn save(&self, files: &[CachedFile], entries: &[CachedEntry], locale: Option<&Locale>) -> io::Result<()> {
+		if files.len() != entries.len() {
+			return Err(invalid("every cached file needs its entry"))
+		}
+		let mut out = format!("{HEADER}locale\t{}\n", locale_field(locale));
+		for (file, entry) in files.iter().zip(entries) {
+			let path = file.path.to_str().ok_or_else(|| invalid("desktop file paths that aren't valid UTF-8 can't be cached"))?;
+			out.push_str(&format!("file\t{}\t{}\t{}\n", escape(&file.id), escape(path), time_field(file.modified)));
+			match entry {
+				CachedEntry::Entry(ini) => write_entry(&mut out, ini),
+				CachedEntry::Invalid(message) => out.push_str(&format!("invalid\t{}\n", escape(message))),
+			}
+		}
+		fs::create_dir_all(&self.dir)?;
+		//Written next to the cache and renamed over it, so a reader never sees half a cache. The name is
+		//per process and save, two tal processes writing at once can't mix their writes.
+		let tmp = self.dir.join(format!("{}.{}-{}.tmp", Self::FILE_NAME, process::id(), NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+		fs::write(&tmp, out)?;
+		fs::rename(&tmp, self.path()).inspect_err(|_| {
+			let _ = fs::remove_file(&tmp);
+		})
+	}
+}
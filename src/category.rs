@@ -0,0 +1,62 @@
+use crate::apps::Ini;
+use crate::json::Json;
+use crate::render;
+
+/// Registered main categories from the desktop menu specification
+pub const MAIN_CATEGORIES: [&str; 13] = [
+	"AudioVideo", "Audio", "Video", "Development", "Education", "Game", "Graphics",
+	"Network", "Office", "Science", "Settings", "System", "Utility",
+];
+pub const OTHER: &str = "Other";
+
+pub type Tree = Vec<(&'static str, Vec<Ini>)>;
+
+pub fn main_category(ini: &Ini) -> &'static str {
+	ini.categories.iter()
+		.find_map(|category| MAIN_CATEGORIES.into_iter().find(|main| main == category))
+		.unwrap_or(OTHER)
+}
+
+/// Groups entries under their first main category in menu spec order, empty categories are left out
+pub fn tree(entries: Vec<Ini>) -> Tree {
+	let mut tree: Tree = MAIN_CATEGORIES.into_iter().chain([OTHER]).map(|category| (category, Vec::new())).collect();
+	for ini in entries {
+		let category = main_category(&ini);
+		if let Some((_, bucket)) = tree.iter_mut().find(|(name, _)| *name == category) {
+			bucket.push(ini);
+		}
+	}
+	tree.retain(|(_, bucket)| !bucket.is_empty());
+	tree
+}
+
+/// Every category listed by `entries` with how many entries list it, sorted by name ignoring case.
+/// Spellings differing only in case are counted together under the first one seen.
+pub fn counts(entries: &[Ini]) -> Vec<(String, usize)> {
+	let mut counts: Vec<(String, usize)> = Vec::new();
+	for ini in entries {
+		for (index, category) in ini.categories.iter().enumerate() {
+			//An entry listing a category twice counts once
+			if ini.categories[..index].iter().any(|earlier| earlier.eq_ignore_ascii_case(category)) {
+				continue
+			}
+			match counts.iter_mut().find(|(counted, _)| counted.eq_ignore_ascii_case(category)) {
+				Some((_, count)) => *count += 1,
+				None => counts.push((category.clone(), 1)),
+			}
+		}
+	}
+	counts.sort_by_cached_key(|(category, _)| category.to_ascii_lowercase());
+	counts
+}
+
+/// Maps every category of `tree` to the entry objects of [`render::entry_object`], "Other" is always present so frontends can rely on it
+pub fn tree_json(tree: Tree) -> Json {
+	let mut categories: Vec<(&str, Json)> = tree.into_iter()
+		.map(|(category, entries)| (category, Json::Arr(entries.iter().map(render::entry_object).collect())))
+		.collect();
+	if !categories.iter().any(|(category, _)| *category == OTHER) {
+		categories.push((OTHER, Json::Arr(Vec::new())));
+	}
+	Json::obj(categories)
+}
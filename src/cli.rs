@@ -1,59 +1,857 @@
-use clap::{Parser};
-use std::env;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::{cell::Cell, env, fs, io, num::NonZeroUsize, os::unix::process::ExitStatusExt, path::{Path, PathBuf}, process::ExitCode, thread, time::{Duration, SystemTime}};
 
-use crate::apps::{self};
+use tal::apps::{self, SpawnMode};
+use tal::cache::EntryCache;
+use tal::category;
+use tal::exec;
+use tal::fuzzy::{self, Subsequence};
+use tal::history::History;
+use tal::json::Json;
+use tal::locale::Locale;
+use tal::mime::{MimeApps, MimeGlobs};
+use tal::render::Format;
+use tal::terminal;
+use tal::time;
+use tal::validate::{Severity, Validator, Violation};
+use crate::completions;
+use crate::config::Config;
+use crate::doctor;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+	/// Check the directories, terminal, openers, entries and cache tal relies on
+	Doctor,
+	/// Print the completion script of SHELL, it completes application names through --list-names
+	Completions {
+		#[arg(value_parser = completions::SHELLS)]
+		shell: String,
+	},
+}
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, disable_help_subcommand = true)]
 pub struct Cli {
-	/// Launch applications from the command line
+	#[command(subcommand)]
+	command: Option<Command>,
+	/// Launch an application from the command line, anything after its name is handed to it as
+	/// files or URLs, e.g. `tal mpv one.mkv two.mkv`
+	#[arg(value_name = "NAME [ARGS]...")]
 	input: Option<Vec<String>>,
-	/// List user installed applications that are located in /home/USER/.local/share/applications
+	/// List user installed applications that are located in $XDG_DATA_HOME/applications (~/.local/share/applications)
 	#[arg(long, short)]
 	user: bool,
-	/// List system installed applications that are located in /usr/share/applications
+	/// List system installed applications that are located in the applications directory of every $XDG_DATA_DIRS entry
 	#[arg(long, short)]
 	system: bool,
-	/// List flatpak applications that are located in /var/lib/flatpak/exports/share/applications/
+	/// List flatpak applications that are located in the user and system flatpak exports/share/applications directories
 	#[arg(long, short)]
 	flatpak: bool,
+	/// List snap applications that are located in /var/lib/snapd/desktop/applications and $XDG_DATA_DIRS entries ending in snapd/desktop
+	#[arg(long)]
+	snap: bool,
+	/// Print the names of the listed applications, read one line from stdin and launch the application it
+	/// names like NAME would, for dmenu style pickers. Nothing is launched when the line is empty.
+	#[arg(long)]
+	pick: bool,
+	/// Print the lowercase names of the listed applications one per line, for shell completion
+	#[arg(long)]
+	list_names: bool,
 	/// List both system and user applications
 	#[arg(long, short)]
 	all: bool,
+	/// List XDG autostart entries from ~/.config/autostart and /etc/xdg/autostart
+	#[arg(long)]
+	autostart: bool,
+	/// Launch every enabled XDG autostart entry, delay and phase keys are ignored
+	#[arg(long)]
+	run_autostart: bool,
+	/// Only list entries whose program resolves to a path outside these colon separated directories,
+	/// entries whose program can't be found on PATH are left out
+	#[arg(long, value_name = "DIRS")]
+	exec_outside: Option<String>,
+	/// Only list entries whose desktop file was modified after TIME, an RFC 3339 timestamp like 2024-01-01T00:00:00Z
+	#[arg(long, value_name = "TIME", value_parser = time::parse_rfc3339)]
+	since: Option<SystemTime>,
+	/// Only list entries whose Name, Exec, Comment or action names contain QUERY, ignoring case.
+	/// Exits with a failure when nothing matches.
+	#[arg(long, value_name = "QUERY")]
+	search: Option<String>,
+	/// Only list entries whose Categories include CATEGORY, ignoring case.
+	/// Repeat it or separate categories with commas to require all of them.
+	#[arg(long, value_name = "CATEGORY", value_delimiter = ',')]
+	category: Vec<String>,
+	/// Print every category of the listed entries with how many entries have it
+	#[arg(long)]
+	list_categories: bool,
+	/// Also list and launch NoDisplay and Hidden entries, entries for other desktops and entries whose TryExec isn't installed
+	#[arg(long)]
+	no_filter: bool,
+	/// Match application names to launch ignoring diacritics, so "telephone" launches "Téléphone"
+	#[arg(long)]
+	fold_diacritics: bool,
+	/// Launch the best match when a partial name matches several applications instead of listing them
+	#[arg(long)]
+	first: bool,
+	/// Directory of the entry cache, defaults to $XDG_CACHE_HOME/tal
+	#[arg(long, value_name = "PATH")]
+	cache_dir: Option<PathBuf>,
+	/// Rescan the desktop files once the entry cache is older than SECONDS, even when none of them changed
+	#[arg(long, value_name = "SECONDS")]
+	cache_ttl: Option<u64>,
+	/// Read every desktop file again and rewrite the entry cache from them
+	#[arg(long, conflicts_with = "no_cache")]
+	refresh: bool,
+	/// Read every desktop file instead of the entry cache, and leave the cache untouched
+	#[arg(long, conflicts_with_all = ["cache_dir", "cache_ttl"])]
+	no_cache: bool,
+	/// What makes entries duplicates of each other, later duplicates are left out of listings
+	#[arg(long, value_name = "KEY", value_parser = ["id", "name", "exec"], default_value = "id")]
+	dedup_by: String,
+	/// With --all, list the sources that could be read and report the ones that failed
+	#[arg(long)]
+	keep_going: bool,
+	/// List the most frequently and recently launched applications first, the others by name
+	#[arg(long)]
+	recent: bool,
 	/// Show details about the application entries
 	#[arg(long, short)]
 	details: bool,
-	/// Send application output to stdout
+	/// List all applications grouped by their main category
+	#[arg(long)]
+	tree: bool,
+	/// Print the category tree as JSON, mapping every category to its entry objects
+	#[arg(long)]
+	print_tree_json: bool,
+	/// Print the applications best matching QUERY instead of launching one
+	#[arg(long, value_name = "QUERY")]
+	fuzzy_list: Option<String>,
+	/// Maximum number of matches printed by --fuzzy-list
+	#[arg(long, value_name = "K", default_value_t = 10)]
+	match_limit: usize,
+	/// Print the score in front of every --fuzzy-list match
+	#[arg(long)]
+	scores: bool,
+	/// Count the listed applications of every source
+	#[arg(long)]
+	count: bool,
+	/// Print output as JSON, failures are printed to stderr as JSON objects too
+	#[arg(long)]
+	json: bool,
+	/// Show Exec without its field codes in --details and --find-by-wmclass output
+	#[arg(long, overrides_with = "raw_exec")]
+	strip_field_codes: bool,
+	/// Show Exec exactly as the desktop file writes it, the default
+	#[arg(long, overrides_with = "strip_field_codes")]
+	raw_exec: bool,
+	/// Print listings as one JSON entry object per line
+	#[arg(long, conflicts_with = "json")]
+	ndjson: bool,
+	/// Print the command that would be launched instead of launching it
+	#[arg(long)]
+	dry_run: bool,
+	/// Report desktop files that could not be decoded
+	#[arg(long, short)]
+	verbose: bool,
+	/// Launch through "$SHELL -lc" so aliases and functions from the login shell apply
+	#[arg(long)]
+	shell: bool,
+	/// Print the PID of every started process instead of the launch message
+	#[arg(long)]
+	print_pid: bool,
+	/// Send application output to stdout, wait for it to exit and exit with its status
 	#[arg(long, short)]
 	output: bool,
+	/// Keep launched applications in tal's process group, so closing the terminal also closes them
+	#[arg(long, conflicts_with = "output")]
+	no_detach: bool,
+	/// Maximum number of desktop files read at the same time, defaults to the CPU count
+	#[arg(long, short)]
+	jobs: Option<NonZeroUsize>,
+	/// Terminal for terminal applications, wins over $TERMINAL, terminal detection and the terminal fallback
+	#[arg(long, value_name = "CMD")]
+	terminal: Option<String>,
+	/// Terminal used as a last resort for terminal applications when $TERMINAL is unset and no known terminal is on PATH
+	#[arg(long, value_name = "CMD")]
+	terminal_fallback: Option<String>,
+	/// Open the resolved terminal with DIR as its working directory, the current directory when DIR is omitted
+	#[arg(long, value_name = "DIR")]
+	terminal_here: Option<Option<PathBuf>>,
+	/// Launch the desktop action ID of the named applications instead of their main Exec, matched by
+	/// key or name. Without this flag NAME:ID launches an action as well.
+	#[arg(long, value_name = "ID")]
+	action: Option<String>,
+	/// Start applications with this niceness through nice, from -20 (highest priority) to 19 (lowest)
+	#[arg(long, value_name = "N", allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+	nice: Option<i32>,
+	/// Start applications in this I/O scheduling class through ionice, Linux only
+	#[arg(long, value_name = "CLASS", value_parser = ["idle", "best-effort", "realtime"])]
+	ionice: Option<String>,
+	/// Run CMD inside the resolved terminal, CMD is split with the same quoting rules as Exec
+	#[arg(long, value_name = "CMD")]
+	exec: Option<String>,
+	/// Launch the applications named in FILE, one per line, blank lines and lines starting with # are ignored
+	#[arg(long, value_name = "FILE")]
+	from_file: Option<PathBuf>,
+	/// Launch the desktop file at PATH without looking through the installed applications, a NAME
+	/// ending in .desktop that is an existing file is launched the same way
+	#[arg(long = "file", value_name = "PATH")]
+	desktop_file: Option<PathBuf>,
+	/// Launch the applications of a batch one at a time, waiting MS milliseconds before each one after the first
+	#[arg(long, value_name = "MS")]
+	stagger: Option<u64>,
+	/// Pass every file in DIR to the launched applications, filling the %F/%U (or %f/%u) field codes
+	#[arg(long, value_name = "DIR")]
+	args_from_dir: Option<PathBuf>,
+	/// Only pass the files of --args-from-dir whose name matches PATTERN, e.g. "*.jpg"
+	#[arg(long, value_name = "PATTERN", requires = "args_from_dir")]
+	glob: Option<String>,
+	/// Best-effort: open known terminal programs (htop, vim, ...) in a terminal even when their entry says Terminal=false,
+	/// the list can be replaced with terminal_tools in the config
+	#[arg(long)]
+	guess_terminal: bool,
+	/// Skip applications that need a terminal when no terminal is available instead of failing
+	#[arg(long)]
+	skip_terminal_apps: bool,
+	/// Print the entries whose StartupWMClass matches CLASS, exits with failure when none do
+	#[arg(long, value_name = "CLASS")]
+	find_by_wmclass: Option<String>,
+	/// Open FILE with the default application of its type from mimeapps.list, or with its only handler.
+	/// Lists the handlers instead when there are several and none is the default.
+	#[arg(long, value_name = "FILE", conflicts_with = "open_with")]
+	open: Option<PathBuf>,
+	/// List the applications whose MimeType matches FILE's type
+	#[arg(long, value_name = "FILE")]
+	open_with: Option<PathBuf>,
+	/// With --open-with, read one of the listed names from stdin and open FILE with it
+	#[arg(long, requires = "open_with")]
+	select: bool,
+	/// Write every listed entry to FILE as desktop file groups, each under a comment naming its source file
+	#[arg(long, value_name = "FILE")]
+	export: Option<PathBuf>,
+	/// Check every discovered desktop file against the specification, exits with failure when any has errors
+	#[arg(long)]
+	validate_all: bool,
+	/// Print the entry launching NAME would pick, the files it shadows and the other entries with that name, without launching
+	#[arg(long, value_name = "NAME")]
+	resolve: Option<String>,
+	/// Launch the most recently launched application again
+	#[arg(long)]
+	repeat_last: bool,
+	/// Print which terminal would be used for terminal applications and exit
+	#[arg(long)]
+	probe_terminal: bool,
+	/// Resolve localized names with this locale (e.g. de_DE.UTF-8) and export it to launched applications
+	#[arg(long)]
+	locale: Option<String>,
 }
 
-pub fn parser() -> Option<()> {
+pub fn parser() -> ExitCode {
 	let cli_parser = Cli::parse();
+	let config = Config::load().unwrap_or_else(|e| {
+		report_message("Config", &e.to_string(), cli_parser.json);
+		Config::default()
+	});
+	let matching = apps::MatchOptions { fold_diacritics: cli_parser.fold_diacritics, first: cli_parser.first };
+	let mut installed = match cli_parser.jobs.or(config.jobs) {
+		Some(jobs) => apps::Installed::new(jobs),
+		None => apps::Installed::default(),
+	}.with_verbose(cli_parser.verbose)
+		.with_dedup(apps::DedupKey::from_name(&cli_parser.dedup_by).unwrap_or_default())
+		.with_matching(matching)
+		.with_filter(!cli_parser.no_filter);
+	let cache_dir = cli_parser.cache_dir.clone().or(config.cache_dir.clone()).or_else(EntryCache::default_dir);
+	let cache_ttl = cli_parser.cache_ttl.map(Duration::from_secs).or(config.cache_ttl);
+	let cache = cache_dir.clone().filter(|_| !cli_parser.no_cache).map(|dir| EntryCache::new(dir).with_ttl(cache_ttl).with_refresh(cli_parser.refresh));
+	installed = installed.with_cache(cache);
+	if let Some(locale) = &cli_parser.locale {
+		installed = installed.with_locale(Locale::parse(locale));
+	}
+	if let Some(priority) = &config.source_priority {
+		installed = installed.with_source_priority(priority);
+	}
+	let terminal_fallback = cli_parser.terminal_fallback.or(config.terminal_fallback);
+	let shell = cli_parser.shell.then(|| env::var("SHELL").unwrap_or("/bin/sh".to_owned()));
+	//With --file naming the application every positional is an argument for it
+	let (input_name, forwarded) = match cli_parser.input.as_deref().and_then(<[String]>::split_first) {
+		Some(_) if cli_parser.desktop_file.is_some() => (None, cli_parser.input.clone().unwrap_or_default()),
+		Some((name, forwarded)) => (Some(name.clone()), forwarded.to_vec()),
+		None => (None, Vec::new()),
+	};
+	let mut args = match &cli_parser.args_from_dir {
+		Some(dir) => match exec::dir_args(dir, cli_parser.glob.as_deref()) {
+			Ok(files) => files,
+			Err(e) => {
+				report_message("ReadFailed", &format!("Failed to read {}: {e}", dir.display()), cli_parser.json);
+				return ExitCode::FAILURE
+			},
+		},
+		None => Vec::new(),
+	};
+	args.extend(forwarded);
+	let terminal_tools = cli_parser.guess_terminal.then(|| config.terminal_tools.clone()
+		.unwrap_or_else(|| terminal::TERMINAL_TOOLS.iter().map(|tool| tool.to_string()).collect()));
+	let spawner = |name: String| apps::Spawn::new(name, env::var("TERMINAL").ok())
+		.with_args(args.clone())
+		.with_terminal(cli_parser.terminal.clone())
+		.with_terminal_fallback(terminal_fallback.clone())
+		.with_locale(cli_parser.locale.clone())
+		.with_shell(shell.clone())
+		.with_terminal_tools(terminal_tools.clone())
+		.with_priority(cli_parser.nice, cli_parser.ionice.as_deref().and_then(exec::IoClass::from_name));
+	let mode = match (cli_parser.output, cli_parser.no_detach) {
+		(true, _) => SpawnMode::Attached,
+		(false, true) => SpawnMode::Background,
+		(false, false) => SpawnMode::Detached,
+	};
 
-	if let Some(app_names) = cli_parser.input {
-		app_names.into_iter().for_each(|app_name| {
-			if let Err(e) = apps::Spawn::new(app_name, env::var("TERMINAL").ok()).run(cli_parser.output) {
-				eprintln!("{e}")
+	let display = apps::Display::new(cli_parser.details).with_format(match (cli_parser.json, cli_parser.ndjson) {
+		(true, _) => Format::Json,
+		(_, true) => Format::Ndjson,
+		_ => Format::Text,
+	}).with_strip_field_codes(cli_parser.strip_field_codes).with_recent(cli_parser.recent.then(History::load));
+
+	match &cli_parser.command {
+		Some(Command::Doctor) => return doctor::run(&installed, &spawner(String::new()), cache_dir.as_deref()),
+		Some(Command::Completions { shell }) => {
+			//The shell is checked by clap already
+			print!("{}", completions::script(shell, &Cli::command()).unwrap_or_default());
+			return ExitCode::SUCCESS
+		},
+		None => {},
+	}
+	if cli_parser.probe_terminal {
+		match spawner(String::new()).resolve_terminal() {
+			Some(term) => {
+				println!("Terminal={} (from {})", term.program, term.origin);
+				match term.path() {
+					Some(path) => println!("\t- Path={}", path.display()),
+					None => println!("\t- Path=None (not found on PATH)"),
+				}
+				match term.exec_args() {
+					[] => println!("\t- ExecFlag=None"),
+					exec_args => println!("\t- ExecFlag={}", exec_args.join(" ")),
+				}
+			},
+			None => println!("No terminal found, $TERMINAL is unset, no terminal fallback is available and no known terminal is on PATH."),
+		}
+		return ExitCode::SUCCESS
+	}
+	if let Some(dir) = cli_parser.terminal_here {
+		let Some(dir) = dir.or_else(|| env::current_dir().ok()) else {
+			report_message("ReadFailed", "The current directory is not accessible.", cli_parser.json);
+			return ExitCode::FAILURE
+		};
+		let spawn = spawner("terminal".to_owned());
+		let result = match cli_parser.dry_run {
+			true => spawn.resolve_terminal_here(dir).map(|resolved| print_resolved(&[resolved], cli_parser.json)),
+			false => spawn.terminal_here(dir, mode).map(drop),
+		};
+		return match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => fail(&e, cli_parser.json),
+		}
+	}
+	if let Some(command) = cli_parser.exec {
+		//Escaped so a `%` of the command stays literal instead of reading as a field code
+		let app = apps::Ini { name: command.clone(), exec: command.replace('%', "%%"), terminal: true, ..Default::default() };
+		let spawn = spawner(command);
+		let result = match cli_parser.dry_run {
+			true => spawn.resolve(&app).map(|commands| print_resolved(&commands, cli_parser.json)),
+			false => spawn.launch(app, mode),
+		};
+		return match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => fail(&e, cli_parser.json),
+		}
+	}
+	if let Some(wm_class) = &cli_parser.find_by_wmclass {
+		let matches: Vec<apps::Ini> = match installed.all() {
+			Ok(entries) => entries.into_iter().filter(|app| app.matches_wm_class(wm_class)).collect(),
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		matches.iter().for_each(|app| match cli_parser.json {
+			true => println!("{}", Json::obj([("name", app.name.as_str()), ("id", &app.id), ("exec", &app.exec)])),
+			false => println!("Name={}\n\t- Id={}\n\t- Exec={}", app.name, app.id, match cli_parser.strip_field_codes {
+				true => exec::strip_field_codes(&app.exec),
+				false => app.exec.clone(),
+			}),
+		});
+		return match matches.is_empty() {
+			true => ExitCode::FAILURE,
+			false => ExitCode::SUCCESS,
+		}
+	}
+	let open_file = |app: Result<apps::Ini, apps::RunError>, file: &Path| {
+		let result = app.and_then(|app| {
+			let spawn = spawner(app.name.clone()).with_args(vec![file.to_string_lossy().into_owned()]).with_history(true);
+			match cli_parser.dry_run {
+				true => spawn.resolve(&app).map(|commands| print_resolved(&commands, cli_parser.json)),
+				false => spawn.launch(app, mode),
+			}
+		});
+		match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => fail(&e, cli_parser.json),
+		}
+	};
+	if let Some(file) = &cli_parser.open_with {
+		let Some(mime_type) = MimeGlobs::load().guess(file) else {
+			report_message("UnknownMimeType", &format!("Could not tell the type of {}.", file.display()), cli_parser.json);
+			return ExitCode::FAILURE
+		};
+		let handlers: Vec<apps::Ini> = match installed.all() {
+			Ok(entries) => MimeApps::load().handlers(&mime_type, &entries).into_iter().cloned().collect(),
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		if !cli_parser.select {
+			match cli_parser.json {
+				true => println!("{}", Json::from(handlers.iter().map(|app| app.name.as_str()).collect::<Vec<_>>())),
+				false => handlers.iter().for_each(|app| println!("{}", app.name)),
+			}
+			return match handlers.is_empty() {
+				true => ExitCode::FAILURE,
+				false => ExitCode::SUCCESS,
+			}
+		}
+		let mut choice = String::new();
+		if let Err(e) = io::stdin().read_line(&mut choice) {
+			report_message("ReadFailed", &format!("Failed to read the selection from stdin: {e}"), cli_parser.json);
+			return ExitCode::FAILURE
+		}
+		let choice = choice.trim();
+		return open_file(apps::find_entry(handlers, choice, matching), file)
+	}
+	if let Some(file) = &cli_parser.open {
+		let Some(mime_type) = MimeGlobs::load().guess(file) else {
+			report_message("UnknownMimeType", &format!("Could not tell the type of {}.", file.display()), cli_parser.json);
+			return ExitCode::FAILURE
+		};
+		let entries = match installed.all() {
+			Ok(entries) => entries,
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		let mime_apps = MimeApps::load();
+		let handlers = mime_apps.handlers(&mime_type, &entries);
+		let chosen = match (mime_apps.default_for(&mime_type, &entries), handlers.as_slice()) {
+			(Some(default), _) => default,
+			(None, [handler]) => handler,
+			(None, []) => {
+				report_message("NoHandler", &format!("No application opens {mime_type} files."), cli_parser.json);
+				return ExitCode::FAILURE
+			},
+			//Several handlers and no default, picking one would be a guess
+			(None, _) => {
+				match cli_parser.json {
+					true => println!("{}", Json::from(handlers.iter().map(|app| app.name.as_str()).collect::<Vec<_>>())),
+					false => handlers.iter().for_each(|app| println!("{}", app.name)),
+				}
+				return ExitCode::FAILURE
+			},
+		};
+		return open_file(Ok(chosen.clone()), file)
+	}
+	if let Some(export_path) = &cli_parser.export {
+		let entries = match installed.all() {
+			Ok(entries) => entries,
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		let exported: Vec<String> = entries.iter().map(|app| match &app.file {
+			Some(file) => format!("# {}\n{}", file.display(), app.to_desktop_string()),
+			None => app.to_desktop_string(),
+		}).collect();
+		if let Err(e) = fs::write(export_path, exported.join("\n")) {
+			report_message("WriteFailed", &format!("Failed to write {}: {e}", export_path.display()), cli_parser.json);
+			return ExitCode::FAILURE
+		}
+		println!("Exported {} entries to {}.", entries.len(), export_path.display());
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.validate_all {
+		let files = match installed.desktop_files() {
+			Ok(files) => files,
+			Err(e) => return fail(&e.into(), cli_parser.json),
+		};
+		let validator = match &config.strict_booleans {
+			Some(keys) => Validator::new(keys.clone()),
+			None => Validator::default(),
+		};
+		let reports: Vec<(PathBuf, Vec<Violation>)> = files.iter()
+			.map(|(_, path)| (path.clone(), validator.validate_file(path)))
+			.filter(|(_, violations)| !violations.is_empty())
+			.collect();
+		let mut totals = [0; 2];
+		for (i, severity) in [Severity::Error, Severity::Warning].into_iter().enumerate() {
+			let mut found = reports.iter()
+				.flat_map(|(path, violations)| violations.iter().filter(|v| v.severity == severity).map(move |v| (path, v)))
+				.peekable();
+			if found.peek().is_none() { continue; }
+			println!("{}:", match severity {
+				Severity::Error => "Errors",
+				Severity::Warning => "Warnings",
+			});
+			found.for_each(|(path, violation)| {
+				totals[i] += 1;
+				match violation.line {
+					Some(_) => println!("\t{}:{violation}", path.display()),
+					None => println!("\t{}: {violation}", path.display()),
+				}
+			});
+		}
+		let [errors, warnings] = totals;
+		println!("{errors} error(s) and {warnings} warning(s) in {} of {} file(s).", reports.len(), files.len());
+		return match errors {
+			0 => ExitCode::SUCCESS,
+			_ => ExitCode::FAILURE,
+		}
+	}
+	if let Some(name) = &cli_parser.resolve {
+		let spawn = spawner(name.clone());
+		let result = spawn.find(&installed).and_then(|app| {
+			let others: Vec<_> = spawn.candidates(&installed)?.into_iter().filter(|other| other.id != app.id).collect();
+			Ok((spawn.resolve(&app)?, installed.shadowed(&app.id), app, others))
+		});
+		let (commands, shadowed, app, others) = match result {
+			Ok(resolution) => resolution,
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		let path_string = |path: &PathBuf| path.to_string_lossy().into_owned();
+		let file = app.file.as_ref().map(path_string);
+		match cli_parser.json {
+			true => println!("{}", Json::obj([
+				("name", Json::from(app.name.as_str())),
+				("id", app.id.as_str().into()),
+				("file", file.into()),
+				("commands", commands.iter().map(|resolved| resolved.to_json()).collect::<Vec<_>>().into()),
+				("shadows", shadowed.iter().map(path_string).collect::<Vec<_>>().into()),
+				("other_matches", others.iter().map(|other| Json::obj([
+					("id", Json::from(other.id.as_str())),
+					("file", other.file.as_ref().map(path_string).into()),
+				])).collect::<Vec<_>>().into()),
+			])),
+			false => {
+				println!("Name={}\n\t- Id={}\n\t- File={}", app.name, app.id, file.as_deref().unwrap_or("None"));
+				commands.iter().for_each(|resolved| println!("\t- Command={resolved}"));
+				shadowed.iter().for_each(|path| println!("\t- Shadows={}", path.display()));
+				others.iter().for_each(|other| match &other.file {
+					Some(path) => println!("\t- AlsoMatches={} ({})", other.id, path.display()),
+					None => println!("\t- AlsoMatches={}", other.id),
+				});
+			},
+		}
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.repeat_last {
+		let Some(last) = History::load().last().cloned() else {
+			report_message("NoHistory", "No launch history yet, launch an application first.", cli_parser.json);
+			return ExitCode::FAILURE
+		};
+		let result = installed.all().and_then(|entries| {
+			entries.into_iter().find(|app| app.id == last.id || (app.id.is_empty() && app.name == last.name))
+				.ok_or(apps::RunError::NotFound(last.name.clone()))
+		}).and_then(|app| {
+			let spawn = spawner(app.name.clone()).with_history(true);
+			match cli_parser.dry_run {
+				true => spawn.resolve(&app).map(|commands| print_resolved(&commands, cli_parser.json)),
+				false => spawn.launch(app, mode),
+			}
+		});
+		return match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => fail(&e, cli_parser.json),
+		}
+	}
+	let mut app_names: Vec<String> = cli_parser.desktop_file.iter().map(|path| path.to_string_lossy().into_owned())
+		.chain(input_name)
+		.collect();
+	if let Some(path) = &cli_parser.from_file {
+		match fs::read_to_string(path) {
+			Ok(contents) => app_names.extend(contents.lines()
+				.map(str::trim)
+				.filter(|line| !line.is_empty() && !line.starts_with('#'))
+				.map(str::to_owned)),
+			Err(e) => {
+				report_message("ReadFailed", &format!("Failed to read {}: {e}", path.display()), cli_parser.json);
+				return ExitCode::FAILURE
+			},
+		}
+	}
+	if !app_names.is_empty() {
+		let mut exit_code = None;
+		app_names.into_iter().enumerate().for_each(|(launch, app_name)| {
+			if let Some(stagger) = cli_parser.stagger && launch > 0 && !cli_parser.dry_run {
+				thread::sleep(Duration::from_millis(stagger));
+			}
+			let is_file = (launch == 0 && cli_parser.desktop_file.is_some())
+				|| (app_name.ends_with(".desktop") && Path::new(&app_name).is_file());
+			let spawn = spawner(app_name.clone()).with_history(true);
+			let target = match is_file {
+				true => spawn.read_file(Path::new(&app_name)).and_then(|app| match cli_parser.action.as_deref() {
+					Some(action) => app.with_action(action),
+					None => Ok(app),
+				}),
+				false => spawn.find_target(&installed, cli_parser.action.as_deref()),
+			};
+			let result = target.and_then(|app| {
+				//Without a terminal these would only fail with NoTerminal
+				if cli_parser.skip_terminal_apps && app.terminal && spawn.resolve_terminal().is_none() {
+					return Ok(())
+				}
+				match cli_parser.dry_run {
+					true => spawn.resolve(&app).map(|commands| print_resolved(&commands, cli_parser.json)),
+					false if cli_parser.print_pid => spawn.spawn(&app, mode)
+						.map(|children| children.iter().for_each(|child_proc| println!("{}", child_proc.id()))),
+					false => spawn.launch(app, mode),
+				}
+			});
+			//Every name is still tried, the first failure decides the exit status
+			if let Err(e) = result {
+				let code = fail(&e, cli_parser.json);
+				exit_code.get_or_insert(code);
+			};
+		});
+		return exit_code.unwrap_or(ExitCode::SUCCESS)
+	}
+	if let Some(query) = &cli_parser.fuzzy_list {
+		match installed.all() {
+			Ok(entries) => {
+				let ranked = fuzzy::rank(&Subsequence, &config.weights, query, &entries);
+				ranked.into_iter().take(cli_parser.match_limit).for_each(|(score, app)| match cli_parser.scores {
+					true => println!("{score}\t{}", app.name),
+					false => println!("{}", app.name),
+				});
+			},
+			Err(e) => return fail(&e, cli_parser.json),
+		}
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.print_tree_json {
+		match installed.all() {
+			Ok(entries) => println!("{}", category::tree_json(category::tree(entries))),
+			Err(e) => return fail(&e, cli_parser.json),
+		}
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.tree {
+		match installed.all() {
+			Ok(entries) => display.tree(category::tree(entries)),
+			Err(e) => return fail(&e, cli_parser.json),
+		}
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.count {
+		let counts = installed.counts();
+		let total: usize = counts.iter().map(|(_, count)| count).sum();
+		match cli_parser.json {
+			true => {
+				let tallies = counts.into_iter().map(|(source, count)| (source.name(), count));
+				println!("{}", Json::obj(tallies.chain([("total", total)])));
+			},
+			false => {
+				counts.into_iter().for_each(|(source, count)| println!("{}: {count}", source.name()));
+				println!("total: {total}");
+			}
+		}
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.run_autostart {
+		let mut exit_code = None;
+		installed.autostart().into_iter().for_each(|app| {
+			if let Err(e) = spawner(app.name.clone()).launch(app, mode) {
+				let code = fail(&e, cli_parser.json);
+				exit_code.get_or_insert(code);
 			};
 		});
-		return None
+		return exit_code.unwrap_or(ExitCode::SUCCESS)
+	}
+	if cli_parser.autostart {
+		display.names(installed.autostart());
+		return ExitCode::SUCCESS
+	}
+	let exec_outside: Option<Vec<PathBuf>> = cli_parser.exec_outside.as_ref()
+		.map(|dirs| dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from).collect());
+	let listed_any = Cell::new(false);
+	let listed = |mut entries: Vec<apps::Ini>| {
+		if let Some(prefixes) = &exec_outside {
+			entries.retain(|app| app.program_path().is_some_and(|program| !prefixes.iter().any(|prefix| program.starts_with(prefix))));
+		}
+		if let Some(since) = cli_parser.since {
+			entries.retain(|app| app.modified.is_some_and(|modified| modified > since));
+		}
+		if let Some(query) = &cli_parser.search {
+			entries = apps::filter(entries, query);
+		}
+		if !cli_parser.category.is_empty() {
+			entries = apps::filter_categories(entries, &cli_parser.category);
+		}
+		listed_any.set(listed_any.get() || !entries.is_empty());
+		entries
+	};
+	//A search is only a success when it found something
+	let listed_status = || match cli_parser.search.is_some() && !listed_any.get() {
+		true => ExitCode::FAILURE,
+		false => ExitCode::SUCCESS,
+	};
+	//The entries of the selected sources, or of all of them when none is
+	let selected_entries = || {
+		let selected: Vec<apps::Source> = [
+			(cli_parser.user, apps::Source::User), (cli_parser.system, apps::Source::System),
+			(cli_parser.flatpak, apps::Source::Flatpak), (cli_parser.snap, apps::Source::Snap),
+		].into_iter().filter_map(|(selected, source)| selected.then_some(source)).collect();
+		match selected.is_empty() {
+			true => installed.all(),
+			false => Ok(selected.into_iter().flat_map(|source| match installed.source(source) {
+				Ok(entries) => entries,
+				Err(apps::ReadError::NotFound(_)) => Vec::new(),
+				Err(e) => {
+					report(&e.into(), cli_parser.json);
+					Vec::new()
+				},
+			}).collect()),
+		}
+	};
+	if cli_parser.list_categories {
+		let entries = selected_entries();
+		match entries {
+			Ok(entries) => {
+				let counts = category::counts(&listed(entries));
+				match cli_parser.json {
+					true => println!("{}", Json::obj(counts)),
+					false => counts.into_iter().for_each(|(category, count)| println!("{category}: {count}")),
+				}
+			},
+			Err(e) => return fail(&e, cli_parser.json),
+		}
+		return listed_status()
+	}
+	if cli_parser.list_names {
+		let mut names: Vec<String> = match selected_entries() {
+			Ok(entries) => listed(entries).into_iter().map(|app| app.name.to_lowercase()).collect(),
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		names.sort();
+		names.dedup();
+		//A name with a newline would complete as two
+		names.iter().filter(|name| !name.contains('\n')).for_each(|name| println!("{name}"));
+		return ExitCode::SUCCESS
+	}
+	if cli_parser.pick {
+		let entries = match selected_entries() {
+			Ok(entries) => listed(entries),
+			Err(e) => return fail(&e, cli_parser.json),
+		};
+		entries.iter().for_each(|app| println!("{}", app.name));
+		let mut choice = String::new();
+		if let Err(e) = io::stdin().read_line(&mut choice) {
+			report_message("ReadFailed", &format!("Failed to read the selection from stdin: {e}"), cli_parser.json);
+			return ExitCode::FAILURE
+		}
+		//Nothing picked, like a menu closed with escape
+		let choice = choice.trim();
+		if choice.is_empty() {
+			return ExitCode::SUCCESS
+		}
+		let spawn = spawner(choice.to_owned()).with_history(true);
+		let result = apps::find_entry(entries, choice, matching).and_then(|app| match cli_parser.dry_run {
+			true => spawn.resolve(&app).map(|commands| print_resolved(&commands, cli_parser.json)),
+			false => spawn.launch(app, mode),
+		});
+		return match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => fail(&e, cli_parser.json),
+		}
+	}
+	if cli_parser.all && cli_parser.keep_going {
+		let (entries, errors) = installed.all_with_errors();
+		display.names(listed(entries));
+		if errors.is_empty() {
+			return listed_status()
+		}
+		match cli_parser.json {
+			true => errors.iter().for_each(|e| report(e, true)),
+			false => {
+				eprintln!("{} source(s) failed:", errors.len());
+				errors.into_iter().for_each(|e| eprintln!("\t{e}"));
+			},
+		}
+		return ExitCode::FAILURE
 	}
 	if cli_parser.all {
-		match apps::Installed.all() {
-			Ok(entries) => apps::Display::new(cli_parser.details).names(entries),
-			Err(e) => eprintln!("{e}"),
+		match installed.all() {
+			Ok(entries) => display.names(listed(entries)),
+			Err(e) => return fail(&e, cli_parser.json),
 		}
-		return None
+		return listed_status()
 	}
+	let mut read_failure = None;
+	let mut list_source = |entries: Result<Vec<apps::Ini>, apps::ReadError>| if let Err(e) = display.entries(entries.map(&listed)) {
+		let code = fail(&e.into(), cli_parser.json);
+		read_failure.get_or_insert(code);
+	};
 	if cli_parser.user {
-		apps::Display::new(cli_parser.details).entries(apps::Installed.user());
+		list_source(installed.user());
 	}
 	if cli_parser.system {
-		apps::Display::new(cli_parser.details).entries(apps::Installed.system());
+		list_source(installed.system());
 	}
 	if cli_parser.flatpak {
-		apps::Display::new(cli_parser.details).entries(apps::Installed.flatpak());
+		list_source(installed.flatpak());
+	}
+	if cli_parser.snap {
+		list_source(installed.snap());
+	}
+	read_failure.unwrap_or_else(listed_status)
+}
+
+//Exit statuses of failures, an attached application that failed exits with its own
+const EXIT_NOT_FOUND: u8 = 1;
+const EXIT_LAUNCH_FAILED: u8 = 2;
+const EXIT_UNREADABLE_SOURCE: u8 = 3;
+
+fn failure_code(e: &apps::RunError) -> ExitCode {
+	match e {
+		apps::RunError::Exited(_, status) => status.code().or_else(|| status.signal().map(|signal| 128 + signal))
+			.and_then(|code| u8::try_from(code).ok())
+			.map_or(ExitCode::FAILURE, ExitCode::from),
+		apps::RunError::NotFound(_) | apps::RunError::ActionNotFound(..) | apps::RunError::Ambiguous(..) => ExitCode::from(EXIT_NOT_FOUND),
+		apps::RunError::Read(_) => ExitCode::from(EXIT_UNREADABLE_SOURCE),
+		_ => ExitCode::from(EXIT_LAUNCH_FAILED),
+	}
+}
+
+//What --dry-run prints: comments naming the selected desktop file and working directory, then every command shell quoted
+fn print_resolved(commands: &[apps::ResolvedCommand], json: bool) {
+	if json {
+		return commands.iter().for_each(|resolved| println!("{}", resolved.to_json()))
+	}
+	let Some(first) = commands.first() else { return };
+	if let Some(file) = &first.file {
+		println!("# {}", file.display());
 	}
-	None
-}
\ No newline at end of file
+	//Commands run where tal runs unless Path= says otherwise
+	if let Some(cwd) = first.cwd.as_ref().filter(|cwd| env::current_dir().ok().as_ref() != Some(*cwd)) {
+		println!("# in {}", cwd.display());
+	}
+	commands.iter().for_each(|resolved| println!("{resolved}"));
+}
+
+//Reports `e` and gives the exit status it maps to
+fn fail(e: &apps::RunError, json: bool) -> ExitCode {
+	report(e, json);
+	failure_code(e)
+}
+
+//Failures go to stderr, as a JSON object per line when --json is set
+fn report(e: &apps::RunError, json: bool) {
+	match json {
+		true => eprintln!("{}", e.to_json()),
+		false => eprintln!("{e}"),
+	}
+}
+
+fn report_message(kind: &str, message: &str, json: bool) {
+	match json {
+		true => eprintln!("{}", Json::obj([("error", kind), ("message", message)])),
+		false => eprintln!("{message}"),
+	}
+}
@@ -0,0 +1,94 @@
+use clap::{Arg, Command};
+
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+//Every `--long` and `-s` spelling of the options, the application names come from `--list-names`
+fn flags(command: &Command) -> Vec<String> {
+	command.get_arguments()
+		.flat_map(|arg| arg.get_long().map(|long| format!("--{long}")).into_iter().chain(arg.get_short().map(|short| format!("-{short}"))))
+		.chain(["--help".to_owned(), "--version".to_owned()])
+		.collect()
+}
+
+//The first line of an option's help, for the shells that show descriptions
+fn summary(arg: &Arg) -> String {
+	arg.get_help().map(|help| help.to_string()).unwrap_or_default().lines().next().unwrap_or_default().to_owned()
+}
+
+fn subcommands(command: &Command) -> Vec<&str> {
+	command.get_subcommands().map(Command::get_name).collect()
+}
+
+//Names are escaped with printf %q, so a name with spaces is inserted as one word
+fn bash(command: &Command) -> String {
+	let bin = command.get_name();
+	format!(r#"_{bin}() {{
+	local cur=${{COMP_WORDS[COMP_CWORD]}}
+	COMPREPLY=()
+	if [[ $cur == -* ]]; then
+		COMPREPLY=($(compgen -W "{flags}" -- "$cur"))
+		return
+	fi
+	if [[ $COMP_CWORD -eq 1 ]]; then
+		COMPREPLY=($(compgen -W "{subcommands}" -- "$cur"))
+	fi
+	#What was typed with its escapes removed, so `visual\ s` matches "visual studio code"
+	local typed=${{cur//\\/}} name
+	while IFS= read -r name; do
+		[[ $name == "$typed"* ]] && COMPREPLY+=("$(printf '%q' "$name")")
+	done < <({bin} --list-names 2>/dev/null)
+}}
+complete -F _{bin} {bin}
+"#, flags = flags(command).join(" "), subcommands = subcommands(command).join(" "))
+}
+
+//compadd quotes what it inserts itself, names only need splitting on newlines
+fn zsh(command: &Command) -> String {
+	let bin = command.get_name();
+	format!(r#"#compdef {bin}
+_{bin}() {{
+	if [[ $PREFIX == -* ]]; then
+		compadd -- {flags}
+		return
+	fi
+	local -a names
+	names=("${{(@f)$({bin} --list-names 2>/dev/null)}}")
+	(( CURRENT == 2 )) && compadd -- {subcommands}
+	compadd -a names
+}}
+if [[ $funcstack[1] == _{bin} ]]; then
+	_{bin} "$@"
+else
+	compdef _{bin} {bin}
+fi
+"#, flags = flags(command).join(" "), subcommands = subcommands(command).join(" "))
+}
+
+//Fish keeps every line of a command substitution as one completion and escapes it on insertion
+fn fish(command: &Command) -> String {
+	let bin = command.get_name();
+	let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+	let mut script = format!("complete -c {bin} -f -a '({bin} --list-names 2>/dev/null)'\n");
+	for subcommand in command.get_subcommands() {
+		let about = subcommand.get_about().map(|about| about.to_string()).unwrap_or_default();
+		script.push_str(&format!("complete -c {bin} -n __fish_use_subcommand -a {} -d {}\n", subcommand.get_name(), quote(&about)));
+	}
+	for arg in command.get_arguments() {
+		let Some(long) = arg.get_long() else { continue };
+		let short = arg.get_short().map(|short| format!(" -s {short}")).unwrap_or_default();
+		//Options taking a value complete files, like the paths most of them are
+		let takes_value = if arg.get_action().takes_values() { " -r -F" } else { "" };
+		script.push_str(&format!("complete -c {bin} -l {long}{short}{takes_value} -d {}\n", quote(&summary(arg))));
+	}
+	script
+}
+
+/// The completion script of `shell` for `command`, `None` for a shell without one
+pub fn script(shell: &str, command: &Command) -> Option<String> {
+	match shell {
+		"bash" => Some(bash(command)),
+		"zsh" => Some(zsh(command)),
+		"fish" => Some(fish(command)),
+		_ => None,
+	}
+}
@@ -0,0 +1,120 @@
+use std::{fs, io, num::NonZeroUsize, path::PathBuf, time::Duration};
+use tal::{apps::Source, fuzzy::Weights, xdg};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+	#[error("Failed to read the config file {0:?}.\n{1}")]
+	Read(PathBuf, io::Error),
+	#[error("Config line {0} is not a valid \"key = value\" pair: {1:?}")]
+	Syntax(usize, String),
+	#[error("Config key {0:?} expects {1}.")]
+	Type(String, &'static str),
+	#[error("Unknown config key {0:?}.")]
+	UnknownKey(String),
+	#[error("source_priority must name each of \"user\", \"flatpak\", \"snap\" and \"system\" exactly once, got {0:?}.")]
+	SourcePriority(Vec<String>),
+}
+
+#[derive(Debug)]
+enum Value {
+	Str(String),
+	Int(i64),
+	List(Vec<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+	pub jobs: Option<NonZeroUsize>,
+	pub terminal_fallback: Option<String>,
+	pub weights: Weights,
+	pub source_priority: Option<Vec<Source>>,
+	pub terminal_tools: Option<Vec<String>>,
+	pub strict_booleans: Option<Vec<String>>,
+	pub cache_dir: Option<PathBuf>,
+	pub cache_ttl: Option<Duration>,
+}
+impl Config {
+	pub const FILE_NAME: &str = "tal/config.toml";
+
+	pub fn path() -> Option<PathBuf> {
+		xdg::config_home().map(|config_dir| config_dir.join(Self::FILE_NAME))
+	}
+
+	pub fn load() -> Result<Self, ConfigError> {
+		let Some(path) = Self::path() else { return Ok(Self::default()) };
+		match fs::read_to_string(&path) {
+			Ok(contents) => Self::parse(&contents),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(e) => Err(ConfigError::Read(path, e)),
+		}
+	}
+
+	fn value(raw: &str) -> Option<Value> {
+		if let Some(list) = raw.strip_prefix('[').and_then(|list| list.strip_suffix(']')) {
+			let items: Option<Vec<String>> = list.split(',').map(str::trim).filter(|item| !item.is_empty())
+				.map(|item| item.strip_prefix('"')?.strip_suffix('"').map(|s| s.to_owned()))
+				.collect();
+			return items.map(Value::List)
+		}
+		if let Some(quoted) = raw.strip_prefix('"') {
+			return quoted.strip_suffix('"').map(|s| Value::Str(s.to_owned()))
+		}
+		raw.parse().ok().map(Value::Int)
+	}
+
+	//A source left out would quietly drop to the bottom, so a typo in a name is an error instead
+	fn source_priority(names: Vec<String>) -> Result<Vec<Source>, ConfigError> {
+		let sources: Option<Vec<Source>> = names.iter().map(|name| Source::from_name(name)).collect();
+		match sources {
+			Some(sources) if sources.len() == Source::ALL.len() && Source::ALL.iter().all(|source| sources.contains(source)) => Ok(sources),
+			_ => Err(ConfigError::SourcePriority(names)),
+		}
+	}
+
+	pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+		let mut config = Self::default();
+		let mut table = String::new();
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') { continue; }
+			if let Some(table_name) = line.strip_prefix('[').and_then(|header| header.strip_suffix(']')) {
+				table = format!("{}.", table_name.trim());
+				continue;
+			}
+
+			let (key, value) = line.split_once('=')
+				.and_then(|(key, raw)| Some((format!("{table}{}", key.trim()), Self::value(raw.trim())?)))
+				.ok_or_else(|| ConfigError::Syntax(line_num + 1, line.to_owned()))?;
+			let key = key.as_str();
+			match (key, value) {
+				("jobs", Value::Int(n)) => config.jobs = Some(usize::try_from(n).ok()
+					.and_then(NonZeroUsize::new)
+					.ok_or(ConfigError::Type(key.to_owned(), "a positive integer"))?),
+				("terminal_fallback", Value::Str(term)) => config.terminal_fallback = Some(term),
+				("weights.name", Value::Int(n)) => config.weights.name = n,
+				("weights.generic_name", Value::Int(n)) => config.weights.generic_name = n,
+				("weights.keywords", Value::Int(n)) => config.weights.keywords = n,
+				("weights.comment", Value::Int(n)) => config.weights.comment = n,
+				("weights.name" | "weights.generic_name" | "weights.keywords" | "weights.comment", _) => {
+					return Err(ConfigError::Type(key.to_owned(), "an integer"))
+				},
+				("source_priority", Value::List(names)) => config.source_priority = Some(Self::source_priority(names)?),
+				("terminal_tools", Value::List(tools)) => config.terminal_tools = Some(tools),
+				("strict_booleans", Value::List(keys)) => config.strict_booleans = Some(keys),
+				("cache_dir", Value::Str(dir)) => config.cache_dir = Some(PathBuf::from(dir)),
+				("cache_ttl", Value::Int(seconds)) => config.cache_ttl = Some(u64::try_from(seconds).map(Duration::from_secs)
+					.map_err(|_| ConfigError::Type(key.to_owned(), "a number of seconds"))?),
+				("cache_dir", _) => return Err(ConfigError::Type(key.to_owned(), "a path")),
+				("cache_ttl", _) => return Err(ConfigError::Type(key.to_owned(), "a number of seconds")),
+				("strict_booleans", _) => return Err(ConfigError::Type(key.to_owned(), "a list of key names")),
+				("terminal_tools", _) => return Err(ConfigError::Type(key.to_owned(), "a list of program names")),
+				("source_priority", _) => return Err(ConfigError::Type(key.to_owned(), "a list of source names")),
+				("jobs", _) => return Err(ConfigError::Type(key.to_owned(), "a positive integer")),
+				("terminal_fallback", _) => return Err(ConfigError::Type(key.to_owned(), "a string")),
+				_ => return Err(ConfigError::UnknownKey(key.to_owned())),
+			}
+		}
+		Ok(config)
+	}
+}
@@ -0,0 +1,108 @@
+use std::{fmt, fs, io::ErrorKind, path::Path, process::ExitCode};
+
+use tal::apps::{Installed, Source, Spawn};
+use tal::exec::which;
+use tal::history::History;
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+	Ok,
+	Warn,
+	Fail,
+}
+impl fmt::Display for Status {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Ok => "OK  ",
+			Self::Warn => "WARN",
+			Self::Fail => "FAIL",
+		})
+	}
+}
+
+#[derive(Default)]
+struct Report(Vec<Status>);
+impl Report {
+	fn check(&mut self, status: Status, message: String) {
+		println!("{status} {message}");
+		self.0.push(status);
+	}
+}
+
+fn dir_status(dir: &Path) -> (Status, String) {
+	match fs::read_dir(dir) {
+		Ok(_) => (Status::Ok, "exists".to_owned()),
+		Err(e) if e.kind() == ErrorKind::NotFound => (Status::Warn, "does not exist".to_owned()),
+		Err(e) => (Status::Fail, format!("can't be read: {e}")),
+	}
+}
+
+//Creates the directory when needed and writes a probe file next to where the cache would go
+fn writable(dir: &Path) -> Result<(), std::io::Error> {
+	fs::create_dir_all(dir)?;
+	let probe = dir.join(".tal-doctor");
+	fs::write(&probe, "")?;
+	fs::remove_file(probe)
+}
+
+/// Prints an OK/WARN/FAIL line per check, fails when any check failed
+pub fn run(installed: &Installed, spawn: &Spawn, cache_dir: Option<&Path>) -> ExitCode {
+	let mut report = Report::default();
+
+	for (source, dir) in installed.dirs() {
+		let (status, detail) = dir_status(dir);
+		report.check(status, format!("{} applications directory {} {detail}", source.name(), dir.display()));
+	}
+	match Config::path() {
+		Some(path) if path.is_file() => report.check(Status::Ok, format!("Config file {}", path.display())),
+		Some(path) => report.check(Status::Ok, format!("No config file at {}, using the defaults", path.display())),
+		None => report.check(Status::Warn, "No config directory, $XDG_CONFIG_HOME and $HOME are unset".to_owned()),
+	}
+	match History::path() {
+		Some(path) => report.check(Status::Ok, format!("Launch history {}", path.display())),
+		None => report.check(Status::Warn, "No launch history location, $XDG_DATA_HOME and $HOME are unset".to_owned()),
+	}
+
+	match spawn.resolve_terminal() {
+		Some(term) => match term.path() {
+			Some(path) => report.check(Status::Ok, format!("Terminal {} (from {}) at {}", term.program, term.origin, path.display())),
+			None => report.check(Status::Warn, format!("Terminal {} (from {}) is not on PATH", term.program, term.origin)),
+		},
+		None => report.check(Status::Fail, "No terminal, $TERMINAL is unset, no terminal fallback is available and no known terminal is on PATH".to_owned()),
+	}
+	for (opener, purpose) in [("xdg-open", "opening files and URLs"), ("gdbus", "D-Bus activation")] {
+		match which(opener) {
+			Some(path) => report.check(Status::Ok, format!("{opener} for {purpose} at {}", path.display())),
+			None => report.check(Status::Warn, format!("{opener} for {purpose} is not on PATH")),
+		}
+	}
+
+	for source in Source::ALL {
+		match installed.source(source) {
+			Ok(entries) => report.check(Status::Ok, format!("{} {} entries", entries.len(), source.name())),
+			Err(e) => report.check(Status::Warn, format!("No {} entries: {}", source.name(), e.to_string().lines().next().unwrap_or_default())),
+		}
+	}
+	let decode_errors = installed.decode_errors();
+	match decode_errors.is_empty() {
+		true => report.check(Status::Ok, "Every desktop file decodes".to_owned()),
+		false => {
+			report.check(Status::Warn, format!("{} desktop file(s) can't be decoded and are skipped", decode_errors.len()));
+			decode_errors.iter().for_each(|(path, e)| println!("\t{}: {}", path.display(), e.to_string().replace('\n', " ")));
+		},
+	}
+
+	match cache_dir {
+		Some(dir) => match writable(dir) {
+			Ok(()) => report.check(Status::Ok, format!("Entry cache {} is writable", dir.display())),
+			Err(e) => report.check(Status::Warn, format!("Entry cache {} is not writable, listings scan every file: {e}", dir.display())),
+		},
+		None => report.check(Status::Warn, "No entry cache directory, $XDG_CACHE_HOME and $HOME are unset".to_owned()),
+	}
+
+	match report.0.iter().max() {
+		Some(Status::Fail) => ExitCode::FAILURE,
+		_ => ExitCode::SUCCESS,
+	}
+}
@@ -0,0 +1,475 @@
+use std::{env, ffi::OsStr, fs, io, os::unix::fs::PermissionsExt, path::{Path, PathBuf}};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("the double quote at byte {0} is never closed")]
+pub struct UnterminatedQuote(pub usize);
+
+//The tokens of an Exec value and the opening quote that is never closed, if one isn't
+fn tokenize(exec: &str) -> (Vec<String>, Option<UnterminatedQuote>) {
+	let mut tokens = Vec::new();
+	let mut token: Option<String> = None;
+	let mut open_quote: Option<usize> = None;
+	let mut chars = exec.char_indices();
+	while let Some((at, c)) = chars.next() {
+		match c {
+			'"' => {
+				open_quote = match open_quote {
+					Some(_) => None,
+					None => Some(at),
+				};
+				token.get_or_insert_default();
+			},
+			'\\' if open_quote.is_some() => {
+				let escaped = chars.next().map_or('\\', |(_, escaped)| escaped);
+				let token = token.get_or_insert_default();
+				if !matches!(escaped, '"' | '`' | '$' | '\\') {
+					token.push('\\');
+				}
+				token.push(escaped);
+			},
+			c if c.is_whitespace() && open_quote.is_none() => tokens.extend(token.take()),
+			c => token.get_or_insert_default().push(c),
+		}
+	}
+	tokens.extend(token);
+	(tokens, open_quote.map(UnterminatedQuote))
+}
+
+/// Splits an Exec value into its argv tokens, double quoted arguments keep their spaces
+/// and `\"`, `` \` ``, `\$` and `\\` unescape inside them. An unclosed quote runs to the end.
+pub fn exec_split(exec: &str) -> Vec<String> {
+	tokenize(exec).0
+}
+
+/// Like [`exec_split`] but an unclosed quote is an error, for when the tokens are about to be run
+pub fn exec_tokenize(exec: &str) -> Result<Vec<String>, UnterminatedQuote> {
+	match tokenize(exec) {
+		(tokens, None) => Ok(tokens),
+		(_, Some(e)) => Err(e),
+	}
+}
+
+//Words neither Exec nor a POSIX shell would split or expand, they are left unquoted
+fn is_plain(arg: &str) -> bool {
+	!arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || "_-@%+=:,./".contains(c))
+}
+
+/// Quotes an argument only when it contains characters that would need it
+pub fn quote_arg(arg: &str) -> String {
+	if is_plain(arg) {
+		return arg.to_owned()
+	}
+	let mut quoted = String::with_capacity(arg.len() + 2);
+	quoted.push('"');
+	for c in arg.chars() {
+		if matches!(c, '"' | '`' | '$' | '\\') {
+			quoted.push('\\');
+		}
+		quoted.push(c);
+	}
+	quoted.push('"');
+	quoted
+}
+
+/// Joins argv back into a minimally quoted command line
+pub fn join_args<S: AsRef<str>>(args: &[S]) -> String {
+	args.iter().map(|arg| quote_arg(arg.as_ref())).collect::<Vec<_>>().join(" ")
+}
+
+//The scheme of a `scheme://...` URL
+fn url_scheme(arg: &str) -> Option<&str> {
+	arg.split_once("://").map(|(scheme, _)| scheme)
+		.filter(|scheme| scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+}
+
+fn percent_decode(encoded: &str) -> String {
+	let bytes = encoded.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			},
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			},
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// What `%f`/`%F` receive for an argument: plain paths as they are, `file://` URLs as their decoded
+/// local path and `None` for any other URL, those can only be passed through `%u`/`%U`
+pub fn local_path(arg: &str) -> Option<String> {
+	let Some(scheme) = url_scheme(arg) else { return Some(arg.to_owned()) };
+	if !scheme.eq_ignore_ascii_case("file") {
+		return None
+	}
+	let rest = &arg[scheme.len() + 3..];
+	//Only an empty host or localhost is this machine
+	let path = match rest.find('/') {
+		Some(0) => rest,
+		Some(slash) if rest[..slash].eq_ignore_ascii_case("localhost") => &rest[slash..],
+		_ => return None,
+	};
+	Some(percent_decode(path))
+}
+
+#[inline]
+fn expand_token(token: &str, file: Option<&str>) -> String {
+	let mut expanded = String::with_capacity(token.len());
+	let mut chars = token.chars();
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			expanded.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('%') => expanded.push('%'),
+			Some('f') => if let Some(path) = file.and_then(local_path) { expanded.push_str(&path) },
+			Some('u') => if let Some(file) = file { expanded.push_str(file) },
+			//Every other field code is dropped
+			_ => (),
+		}
+	}
+	expanded
+}
+
+#[inline]
+fn field_codes(token: &str) -> Vec<char> {
+	let mut codes = Vec::new();
+	let mut chars = token.chars();
+	while let Some(c) = chars.next() {
+		if c == '%' && let Some(code) = chars.next() {
+			codes.push(code);
+		}
+	}
+	codes
+}
+
+/// Substitutes the Exec field codes in place: `%F`/`%U` expand to every argument at their position,
+/// `%f`/`%u` to the first one and `%%` to a literal `%`. The file codes get local paths, see [`local_path`],
+/// while the URL codes get the arguments as they are.
+pub fn expand_field_codes(tokens: Vec<String>, args: &[String]) -> Vec<String> {
+	let mut argv = Vec::with_capacity(tokens.len() + args.len());
+	for token in tokens {
+		match token.as_str() {
+			"%F" => argv.extend(args.iter().filter_map(|arg| local_path(arg))),
+			"%U" => argv.extend(args.iter().cloned()),
+			_ if token.contains('%') => {
+				let expanded = expand_token(&token, args.first().map(String::as_str));
+				if !expanded.is_empty() {
+					argv.push(expanded);
+				}
+			},
+			_ => argv.push(token),
+		}
+	}
+	argv
+}
+
+/// The clean form of an Exec value, with its field codes dropped and `%%` unescaped
+pub fn strip_field_codes(exec: &str) -> String {
+	join_args(&expand_field_codes(exec_split(exec), &[]))
+}
+
+/// Quotes an argument for a POSIX shell, leaving plain words untouched
+pub fn shell_quote(arg: &str) -> String {
+	match is_plain(arg) {
+		true => arg.to_owned(),
+		false => format!("'{}'", arg.replace('\'', "'\\''")),
+	}
+}
+
+/// [`shell_quote`] for the first word of a command line, a plain `NAME=value` there would be read
+/// as a variable assignment instead of the program
+pub fn shell_quote_program(program: &str) -> String {
+	match program.contains('=') {
+		true => format!("'{}'", program.replace('\'', "'\\''")),
+		false => shell_quote(program),
+	}
+}
+
+/// Runs argv through `shell -lc` so login shell aliases, functions and PATH changes apply
+pub fn shell_wrap(shell: &str, argv: Vec<String>) -> Vec<String> {
+	let command_line = argv.iter().enumerate()
+		.map(|(index, arg)| if index == 0 { shell_quote_program(arg) } else { shell_quote(arg) })
+		.collect::<Vec<_>>().join(" ");
+	vec![shell.to_owned(), "-lc".to_owned(), command_line]
+}
+
+/// I/O scheduling classes of `ionice -c`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+	Realtime,
+	BestEffort,
+	Idle,
+}
+impl IoClass {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"realtime" => Some(Self::Realtime),
+			"best-effort" => Some(Self::BestEffort),
+			"idle" => Some(Self::Idle),
+			_ => None,
+		}
+	}
+
+	pub const fn number(&self) -> u8 {
+		match self {
+			Self::Realtime => 1,
+			Self::BestEffort => 2,
+			Self::Idle => 3,
+		}
+	}
+}
+
+/// Runs argv through `ionice -c <class>` and `nice -n <niceness>` so it starts with adjusted priorities.
+/// Both tools are Linux/util-linux and coreutils specific, a tool missing from PATH is left out with a warning
+/// and the command starts with the default priority instead.
+pub fn priority_wrap(argv: Vec<String>, niceness: Option<i32>, io_class: Option<IoClass>) -> Vec<String> {
+	let mut prefix = Vec::new();
+	let available = |tool: &str| match which(tool) {
+		Some(_) => true,
+		None => {
+			eprintln!("{tool} is not available, starting without it.");
+			false
+		},
+	};
+	if let Some(io_class) = io_class && available("ionice") {
+		prefix.extend(["ionice".to_owned(), "-c".to_owned(), io_class.number().to_string()]);
+	}
+	if let Some(niceness) = niceness && available("nice") {
+		prefix.extend(["nice".to_owned(), "-n".to_owned(), niceness.to_string()]);
+	}
+	prefix.extend(argv);
+	prefix
+}
+
+/// Tidies the flatpak `@@`/`@@u`/`@@i` ... `@@` file forwarding markers of an expanded argv.
+/// With `--file-forwarding` flatpak reads the markers itself, so they are kept around forwarded files
+/// and a pair left empty by the expansion is dropped. Without it they would reach the app verbatim,
+/// so only the files between them are kept.
+pub fn forward_files(argv: Vec<String>) -> Vec<String> {
+	let is_marker = |arg: &str| matches!(arg, "@@" | "@@u" | "@@i");
+	if !argv.iter().any(|arg| is_marker(arg)) {
+		return argv
+	}
+	let flatpak_forwards = argv.iter().any(|arg| arg == "--file-forwarding");
+	let mut forwarded = Vec::with_capacity(argv.len());
+	let mut args = argv.into_iter();
+	while let Some(arg) = args.next() {
+		if !is_marker(&arg) {
+			forwarded.push(arg);
+			continue;
+		}
+		let files: Vec<String> = args.by_ref().take_while(|arg| arg != "@@").collect();
+		if flatpak_forwards && !files.is_empty() {
+			forwarded.push(arg);
+			forwarded.extend(files);
+			forwarded.push("@@".to_owned());
+		} else {
+			forwarded.extend(files);
+		}
+	}
+	forwarded
+}
+
+/// Substitutes the codes describing the entry itself: `%c` becomes its Name, `%k` its desktop file
+/// and a standalone `%i` the two arguments `--icon <Icon>`. `%k` and `%i` are dropped without a file
+/// or Icon. The result still goes through [`expand_field_codes`].
+pub fn expand_entry_codes(tokens: Vec<String>, name: &str, file: Option<&str>, icon: Option<&str>) -> Vec<String> {
+	//Substituted text is escaped so a `%` in it survives the field code expansion
+	let escape = |text: &str| text.replace('%', "%%");
+	let mut expanded_tokens = Vec::with_capacity(tokens.len());
+	for token in tokens {
+		if !token.contains('%') {
+			expanded_tokens.push(token);
+			continue;
+		}
+		if token == "%i" {
+			expanded_tokens.extend(icon.into_iter().flat_map(|icon| ["--icon".to_owned(), escape(icon)]));
+			continue;
+		}
+		let mut expanded = String::with_capacity(token.len());
+		let mut chars = token.chars();
+		while let Some(c) = chars.next() {
+			if c != '%' {
+				expanded.push(c);
+				continue;
+			}
+			match chars.next() {
+				Some('c') => expanded.push_str(&escape(name)),
+				Some('k') => expanded.push_str(&file.map(escape).unwrap_or_default()),
+				Some(code) => {
+					expanded.push('%');
+					expanded.push(code);
+				},
+				None => expanded.push('%'),
+			}
+		}
+		//A token that was nothing but a missing `%k` is dropped like other empty codes
+		if !expanded.is_empty() {
+			expanded_tokens.push(expanded);
+		}
+	}
+	expanded_tokens
+}
+
+/// Builds one argv per launch: the single-form `%f`/`%u` codes launch one instance per argument,
+/// while the list-form `%F`/`%U` launch a single instance with all of them. An Exec without any of
+/// them gets the arguments appended.
+pub fn expand_invocations(tokens: Vec<String>, args: &[String]) -> Vec<Vec<String>> {
+	let codes: Vec<char> = tokens.iter().flat_map(|token| field_codes(token)).collect();
+	let single_form = codes.iter().any(|code| matches!(code, 'f' | 'u'));
+	let list_form = codes.iter().any(|code| matches!(code, 'F' | 'U'));
+	let invocations = match (single_form, list_form) {
+		(true, false) if args.len() > 1 => args.iter().map(|arg| expand_field_codes(tokens.clone(), std::slice::from_ref(arg))).collect(),
+		(false, false) => {
+			let mut argv = expand_field_codes(tokens, &[]);
+			argv.extend(args.iter().cloned());
+			vec![argv]
+		},
+		_ => vec![expand_field_codes(tokens, args)],
+	};
+	invocations.into_iter().map(forward_files).collect()
+}
+
+/// Looks up a program in the given colon separated PATH, a program containing a `/` is checked as is
+pub fn which_in(program: &str, path: &OsStr) -> Option<PathBuf> {
+	if program.contains('/') {
+		return Path::new(program).is_file().then(|| PathBuf::from(program))
+	}
+	env::split_paths(path).map(|dir| dir.join(program)).find(|candidate| candidate.is_file())
+}
+
+/// Like [`which_in`], but the file also needs an execute bit, the check TryExec asks for
+pub fn executable_in(program: &str, path: &OsStr) -> Option<PathBuf> {
+	if program.contains('/') {
+		return is_executable(Path::new(program)).then(|| PathBuf::from(program))
+	}
+	env::split_paths(path).map(|dir| dir.join(program)).find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+	fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// The program of Exec's argv, past an `env` prefix with its options and `NAME=VALUE` assignments
+/// like the `env BAMF_DESKTOP_FILE_HINT=... /snap/bin/spotify %U` of snaps
+pub fn exec_program(tokens: &[String]) -> Option<&str> {
+	let mut tokens = tokens.iter().map(String::as_str);
+	let first = tokens.next()?;
+	if Path::new(first).file_name() != Some(OsStr::new("env")) {
+		return Some(first)
+	}
+	let mut options = true;
+	while let Some(token) = tokens.next() {
+		if !options || !token.starts_with('-') {
+			match token.contains('=') {
+				true => continue,
+				false => return Some(token),
+			}
+		}
+		if token == "--" {
+			options = false;
+			continue;
+		}
+		if let Some(long) = token.strip_prefix("--") {
+			let (name, value) = long.split_once('=').map_or((long, None), |(name, value)| (name, Some(value)));
+			match name {
+				//The command line `-S` splits starts with the program
+				"split-string" => return value.or_else(|| tokens.next())?.split_whitespace().next(),
+				"unset" | "chdir" if value.is_none() => { tokens.next(); },
+				_ => {},
+			}
+			continue;
+		}
+		//In a group of short options `-u`, `-C` and `-S` take the rest of the group or the next token as their value
+		for (index, flag) in token.char_indices().skip(1) {
+			let rest = &token[index + flag.len_utf8()..];
+			match flag {
+				'S' => return Some(rest).filter(|rest| !rest.is_empty()).or_else(|| tokens.next())?.split_whitespace().next(),
+				'u' | 'C' => {
+					if rest.is_empty() {
+						tokens.next();
+					}
+					break
+				},
+				_ => {},
+			}
+		}
+	}
+	None
+}
+
+pub fn which(program: &str) -> Option<PathBuf> {
+	which_in(program, &env::var_os("PATH")?)
+}
+
+/// Shell style wildcard match where `*` matches any run of characters and `?` a single one
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+	let (mut pattern_i, mut name_i) = (0, 0);
+	//Where to resume when the characters after the last `*` stop matching
+	let mut backtrack: Option<(usize, usize)> = None;
+	while name_i < name.len() {
+		match pattern.get(pattern_i) {
+			Some('*') => {
+				backtrack = Some((pattern_i, name_i));
+				pattern_i += 1;
+			},
+			Some(c) if *c == '?' || *c == name[name_i] => {
+				pattern_i += 1;
+				name_i += 1;
+			},
+			_ => match backtrack {
+				Some((star_i, star_name_i)) => {
+					pattern_i = star_i + 1;
+					name_i = star_name_i + 1;
+					backtrack = Some((star_i, star_name_i + 1));
+				},
+				None => return false,
+			},
+		}
+	}
+	pattern[pattern_i..].iter().all(|c| *c == '*')
+}
+
+/// The regular files directly inside `dir`, sorted by name and optionally filtered by a glob on the file name
+pub fn dir_args(dir: &Path, glob: Option<&str>) -> io::Result<Vec<String>> {
+	let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+		.filter_map(Result::ok)
+		.filter(|entry| entry.path().is_file())
+		.filter(|entry| glob.is_none_or(|glob| entry.file_name().to_str().is_some_and(|name| glob_match(glob, name))))
+		.map(|entry| entry.path())
+		.collect();
+	files.sort();
+	Ok(files.into_iter().map(|file| file.to_string_lossy().into_owned()).collect())
+}
+
+/// `arg` as a URI, anything already carrying a scheme is kept and paths become percent-encoded `file://` URIs
+pub fn file_uri(arg: &str) -> String {
+	if url_scheme(arg).is_some() {
+		return arg.to_owned()
+	}
+	let path = Path::new(arg);
+	let absolute = match path.is_absolute() {
+		true => path.to_path_buf(),
+		false => env::current_dir().map_or_else(|_| path.to_path_buf(), |cwd| cwd.join(path)),
+	};
+	let mut uri = String::from("file://");
+	for byte in absolute.to_string_lossy().bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+			_ => uri.push_str(&format!("%{byte:02X}")),
+		}
+	}
+	uri
+}
@@ -0,0 +1,29 @@
+//Base letters of U+00C0 to U+017F, `.` where the letter has no canonical decomposition
+const LATIN_BASES: [&str; 2] = [
+	concat!("AAAAAA.CEEEEIIII", ".NOOOOO..UUUUY..", "aaaaaa.ceeeeiiii", ".nooooo..uuuuy.y"),
+	concat!(
+		"AaAaAaCcCcCcCcDd", "..EeEeEeEeEeGgGg", "GgGgHh..IiIiIiIi", "I...JjKk.LlLlLl.",
+		"...NnNnNn...OoOo", "Oo..RrRrRrSsSsSs", "SsTtTt..UuUuUuUu", "UuUuWwYyYZzZzZz.",
+	),
+];
+
+const fn is_combining_mark(c: char) -> bool {
+	matches!(c, '\u{0300}'..='\u{036f}' | '\u{1ab0}'..='\u{1aff}' | '\u{1dc0}'..='\u{1dff}' | '\u{20d0}'..='\u{20ff}' | '\u{fe20}'..='\u{fe2f}')
+}
+
+fn base_letter(c: char) -> Option<char> {
+	let (table, offset) = match c {
+		'\u{00c0}'..='\u{00ff}' => (LATIN_BASES[0], 0xc0),
+		'\u{0100}'..='\u{017f}' => (LATIN_BASES[1], 0x100),
+		_ => return None,
+	};
+	let base = table.as_bytes()[c as usize - offset] as char;
+	(base != '.').then_some(base)
+}
+
+/// `text` with its diacritics removed, like NFD followed by dropping the combining marks: `Téléphone`
+/// folds to `Telephone`. Precomposed letters are folded for Latin-1 and Latin Extended-A, combining
+/// marks in any script. Letters that don't decompose, like `ø` or `ß`, stay.
+pub fn fold_diacritics(text: &str) -> String {
+	text.chars().filter(|c| !is_combining_mark(*c)).map(|c| base_letter(c).unwrap_or(c)).collect()
+}
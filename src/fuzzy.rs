@@ -0,0 +1,82 @@
+use crate::apps::Ini;
+
+/// Scores how well `candidate` matches `query`, `None` when it doesn't match at all
+pub trait Scorer {
+	fn score(&self, query: &str, candidate: &str) -> Option<i64>;
+}
+
+/// Case-insensitive subsequence matching, rewarding consecutive runs, word starts and early matches
+pub struct Subsequence;
+impl Subsequence {
+	const MATCH: i64 = 16;
+	const CONSECUTIVE: i64 = 12;
+	const WORD_START: i64 = 8;
+	const GAP: i64 = 1;
+	const MAX_LEADING_PENALTY: i64 = 6;
+}
+impl Scorer for Subsequence {
+	fn score(&self, query: &str, candidate: &str) -> Option<i64> {
+		let query: Vec<char> = query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()).collect();
+		let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+		if query.is_empty() {
+			return Some(0)
+		}
+		let mut score = 0;
+		let mut query_i = 0;
+		let mut last_match: Option<usize> = None;
+		for (i, c) in candidate.iter().enumerate() {
+			if query_i == query.len() { break; }
+			if *c != query[query_i] { continue; }
+
+			score += Self::MATCH;
+			if i == 0 || !candidate[i - 1].is_alphanumeric() {
+				score += Self::WORD_START;
+			}
+			match last_match {
+				Some(last) if last + 1 == i => score += Self::CONSECUTIVE,
+				Some(last) => score -= (i - last - 1) as i64 * Self::GAP,
+				None => score -= (i as i64).min(Self::MAX_LEADING_PENALTY),
+			}
+			last_match = Some(i);
+			query_i += 1;
+		}
+		(query_i == query.len()).then_some(score)
+	}
+}
+
+/// Multipliers applied to the score of each searched field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+	pub name: i64,
+	pub generic_name: i64,
+	pub keywords: i64,
+	pub comment: i64,
+}
+impl Default for Weights {
+	fn default() -> Self {
+		Self { name: 8, generic_name: 4, keywords: 2, comment: 1 }
+	}
+}
+impl Weights {
+	/// Sums the weighted best score of every field, `None` when no field matches
+	pub fn score(&self, scorer: &dyn Scorer, query: &str, ini: &Ini) -> Option<i64> {
+		let best = |fields: &mut dyn Iterator<Item = &String>| fields.filter_map(|field| scorer.score(query, field)).max();
+		let names = &mut [Some(&ini.name), ini.untranslated_name.as_ref()].into_iter().flatten();
+		let weighted = [
+			best(names).map(|score| score * self.name),
+			best(&mut ini.generic_name.iter()).map(|score| score * self.generic_name),
+			best(&mut ini.keywords.iter()).map(|score| score * self.keywords),
+			best(&mut ini.comment.iter()).map(|score| score * self.comment),
+		];
+		weighted.into_iter().flatten().reduce(|total, score| total + score)
+	}
+}
+
+/// Best matching entries first, ties prefer the shorter name and then discovery order
+pub fn rank<'a>(scorer: &dyn Scorer, weights: &Weights, query: &str, entries: &'a [Ini]) -> Vec<(i64, &'a Ini)> {
+	let mut ranked: Vec<(i64, &Ini)> = entries.iter()
+		.filter_map(|ini| weights.score(scorer, query, ini).map(|score| (score, ini)))
+		.collect();
+	ranked.sort_by_key(|(score, ini)| (-score, ini.name.chars().count()));
+	ranked
+}
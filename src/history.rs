@@ -0,0 +1,105 @@
+use std::{cmp::Reverse, fs, io, path::PathBuf, process, sync::atomic::{AtomicUsize, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::apps::Ini;
+use crate::xdg;
+
+//Tells apart the temp files of saves running at the same time in one process
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// After this many seconds a launch counts half as much, a week
+pub const HALF_LIFE: u64 = 7 * 24 * 60 * 60;
+
+/// How often and how recently an app was launched: `count` halved for every [`HALF_LIFE`] since the last launch
+pub fn frecency(count: u64, last_launch: u64, now: u64) -> f64 {
+	let age = now.saturating_sub(last_launch) as f64;
+	count as f64 * 0.5_f64.powf(age / HALF_LIFE as f64)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+	pub id: String,
+	pub name: String,
+	pub count: u64,
+	/// Seconds since the unix epoch
+	pub last_launch: u64,
+}
+
+/// Launch counts stored one app per line as `last_launch\tcount\tid\tname`
+#[derive(Debug, Default)]
+pub struct History {
+	pub launches: Vec<Launch>,
+}
+impl History {
+	pub const FILE_NAME: &str = "tal/history";
+
+	pub fn path() -> Option<PathBuf> {
+		xdg::data_home().map(|data_home| data_home.join(Self::FILE_NAME))
+	}
+
+	pub fn now() -> u64 {
+		SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+	}
+
+	/// Lines that fail to parse are skipped so a damaged file never blocks launching
+	pub fn parse(contents: &str) -> Self {
+		let launches = contents.lines().filter_map(|line| {
+			let mut fields = line.splitn(4, '\t');
+			let last_launch = fields.next()?.parse().ok()?;
+			let count = fields.next()?.parse().ok()?;
+			let id = fields.next()?.to_owned();
+			let name = fields.next()?.to_owned();
+			Some(Launch { id, name, count, last_launch })
+		}).collect();
+		Self { launches }
+	}
+
+	pub fn load() -> Self {
+		Self::path().and_then(|path| fs::read_to_string(path).ok()).map(|contents| Self::parse(&contents)).unwrap_or_default()
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		let path = Self::path().ok_or(io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		let contents: String = self.launches.iter()
+			.map(|launch| format!("{}\t{}\t{}\t{}\n", launch.last_launch, launch.count, launch.id, launch.name.replace(['\t', '\n'], " ")))
+			.collect();
+		//Written next to the history and renamed over it so a crash never leaves a half written file. The
+		//name is per process and save, so tal processes launching at once can't publish each other's writes.
+		let tmp_path = path.with_extension(format!("{}-{}.tmp", process::id(), NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+		fs::write(&tmp_path, contents)?;
+		fs::rename(&tmp_path, path).inspect_err(|_| {
+			let _ = fs::remove_file(&tmp_path);
+		})
+	}
+
+	pub fn record(&mut self, app: &Ini, now: u64) {
+		let key = if app.id.is_empty() { &app.name } else { &app.id };
+		match self.launches.iter_mut().find(|launch| &launch.id == key) {
+			Some(launch) => {
+				launch.count += 1;
+				launch.last_launch = now;
+				launch.name = app.name.clone();
+			},
+			None => self.launches.push(Launch { id: key.clone(), name: app.name.clone(), count: 1, last_launch: now }),
+		}
+	}
+
+	/// The [`frecency`] of `app`, 0 for apps never launched
+	pub fn score(&self, app: &Ini, now: u64) -> f64 {
+		let key = if app.id.is_empty() { &app.name } else { &app.id };
+		self.launches.iter().find(|launch| &launch.id == key)
+			.map_or(0.0, |launch| frecency(launch.count, launch.last_launch, now))
+	}
+
+	/// Orders `entries` by descending score, apps with equal scores and those never launched by name
+	pub fn sort(&self, entries: &mut [Ini], now: u64) {
+		//Scores are never negative, so their bits order like the scores themselves
+		entries.sort_by_cached_key(|app| (Reverse(self.score(app, now).to_bits()), app.name.to_lowercase()));
+	}
+
+	pub fn last(&self) -> Option<&Launch> {
+		self.launches.iter().max_by_key(|launch| launch.last_launch)
+	}
+}
@@ -0,0 +1,238 @@
+use std::{collections::HashMap, env, fs, io, path::{Path, PathBuf}, sync::{Mutex, MutexGuard}};
+
+use crate::xdg;
+
+/// Every theme falls back to this one
+pub const FALLBACK_THEME: &str = "hicolor";
+const EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SizeType {
+	Fixed,
+	Scalable,
+	Threshold,
+}
+
+#[derive(Debug)]
+struct ThemeDir {
+	path: String,
+	size: u32,
+	min_size: u32,
+	max_size: u32,
+	threshold: u32,
+	size_type: SizeType,
+}
+impl ThemeDir {
+	fn matches(&self, size: u32) -> bool {
+		match self.size_type {
+			SizeType::Fixed => self.size == size,
+			SizeType::Scalable => (self.min_size..=self.max_size).contains(&size),
+			SizeType::Threshold => (self.size.saturating_sub(self.threshold)..=self.size + self.threshold).contains(&size),
+		}
+	}
+
+	fn distance(&self, size: u32) -> u32 {
+		let (min, max) = match self.size_type {
+			SizeType::Fixed => (self.size, self.size),
+			SizeType::Scalable => (self.min_size, self.max_size),
+			SizeType::Threshold => (self.size.saturating_sub(self.threshold), self.size + self.threshold),
+		};
+		min.saturating_sub(size).max(size.saturating_sub(max))
+	}
+}
+
+#[derive(Debug, Default)]
+struct Theme {
+	dirs: Vec<ThemeDir>,
+	inherits: Vec<String>,
+}
+impl Theme {
+	//Only the groups and keys the lookup needs are read from index.theme
+	fn parse(contents: &str) -> Self {
+		let mut groups: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+		let mut group = "";
+		for line in contents.lines().map(str::trim) {
+			if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+				group = name;
+			} else if let Some((key, val)) = line.split_once('=') {
+				groups.entry(group).or_default().insert(key.trim(), val.trim());
+			}
+		}
+		let list = |val: Option<&&str>| -> Vec<String> {
+			val.map(|val| val.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_owned).collect()).unwrap_or_default()
+		};
+		let Some(header) = groups.get("Icon Theme") else { return Self::default() };
+		let dirs = list(header.get("Directories")).into_iter().filter_map(|path| {
+			let keys = groups.get(path.as_str())?;
+			let number = |key: &str| keys.get(key).and_then(|val| val.parse().ok());
+			let size = number("Size")?;
+			let size_type = match keys.get("Type").copied() {
+				Some("Fixed") => SizeType::Fixed,
+				Some("Scalable") => SizeType::Scalable,
+				_ => SizeType::Threshold,
+			};
+			Some(ThemeDir {
+				min_size: number("MinSize").unwrap_or(size),
+				max_size: number("MaxSize").unwrap_or(size),
+				threshold: number("Threshold").unwrap_or(2),
+				path, size, size_type,
+			})
+		}).collect();
+		Self { dirs, inherits: list(header.get("Inherits")) }
+	}
+}
+
+/// Icon theme lookup over a list of base directories, following the icon theme specification
+#[derive(Debug, Clone)]
+pub struct IconLookup {
+	base_dirs: Vec<PathBuf>,
+}
+impl Default for IconLookup {
+	fn default() -> Self {
+		Self::new(Self::default_base_dirs())
+	}
+}
+impl IconLookup {
+	pub const fn new(base_dirs: Vec<PathBuf>) -> Self {
+		Self { base_dirs }
+	}
+
+	/// `~/.icons`, then `icons` under the XDG data directories, then `/usr/share/pixmaps`
+	pub fn default_base_dirs() -> Vec<PathBuf> {
+		env::home_dir().map(|home| home.join(".icons")).into_iter()
+			.chain(xdg::data_home().map(|dir| dir.join("icons")))
+			.chain(xdg::data_dirs().into_iter().map(|dir| dir.join("icons")))
+			.chain([PathBuf::from("/usr/share/pixmaps")])
+			.collect()
+	}
+
+	fn theme(&self, name: &str) -> Option<Theme> {
+		self.base_dirs.iter()
+			.find_map(|base| fs::read_to_string(base.join(name).join("index.theme")).ok())
+			.map(|contents| Theme::parse(&contents))
+	}
+
+	fn find_file(&self, dir: &Path, name: &str) -> Option<PathBuf> {
+		EXTENSIONS.iter().map(|ext| dir.join(format!("{name}.{ext}"))).find(|path| path.is_file())
+	}
+
+	fn in_theme(&self, theme_name: &str, theme: &Theme, name: &str, size: u32) -> Option<PathBuf> {
+		let theme_roots: Vec<PathBuf> = self.base_dirs.iter().map(|base| base.join(theme_name)).collect();
+		let in_dir = |dir: &ThemeDir| theme_roots.iter().find_map(|root| self.find_file(&root.join(&dir.path), name));
+		theme.dirs.iter().filter(|dir| dir.matches(size)).find_map(in_dir)
+			.or_else(|| {
+				let mut closest: Vec<&ThemeDir> = theme.dirs.iter().collect();
+				closest.sort_by_key(|dir| dir.distance(size));
+				closest.into_iter().find_map(in_dir)
+			})
+	}
+
+	//Depth first through Inherits, each theme is visited once
+	fn in_theme_tree(&self, theme_name: &str, name: &str, size: u32, visited: &mut Vec<String>) -> Option<PathBuf> {
+		if visited.iter().any(|seen| seen == theme_name) {
+			return None
+		}
+		visited.push(theme_name.to_owned());
+		let theme = self.theme(theme_name)?;
+		self.in_theme(theme_name, &theme, name, size)
+			.or_else(|| theme.inherits.iter().find_map(|parent| self.in_theme_tree(parent, name, size, visited)))
+	}
+
+	/// Path of icon `name` closest to `size` in `theme`, its parents, hicolor and then the unthemed base directories
+	pub fn resolve(&self, name: &str, size: u32, theme: &str) -> Option<PathBuf> {
+		if name.is_empty() {
+			return None
+		}
+		if Path::new(name).is_absolute() {
+			return Path::new(name).is_file().then(|| PathBuf::from(name))
+		}
+		let mut visited = Vec::new();
+		self.in_theme_tree(theme, name, size, &mut visited)
+			.or_else(|| self.in_theme_tree(FALLBACK_THEME, name, size, &mut visited))
+			.or_else(|| self.base_dirs.iter().find_map(|base| self.find_file(base, name)))
+	}
+}
+
+/// Resolves with the default base directories, see [`IconLookup::resolve`]
+pub fn resolve_icon(name: &str, size: u32, theme: &str) -> Option<PathBuf> {
+	IconLookup::default().resolve(name, size, theme)
+}
+
+type CacheKey = (String, u32, String);
+
+/// Remembers resolved icons by `(name, size, theme)`, misses are cached too.
+/// Safe to share between threads, entries of other themes are dropped whenever a different theme is requested.
+#[derive(Debug, Default)]
+pub struct IconCache {
+	lookup: IconLookup,
+	entries: Mutex<HashMap<CacheKey, Option<PathBuf>>>,
+	theme: Mutex<Option<String>>,
+}
+impl IconCache {
+	pub fn new(lookup: IconLookup) -> Self {
+		Self { lookup, entries: Mutex::default(), theme: Mutex::default() }
+	}
+
+	pub fn resolve(&self, name: &str, size: u32, theme: &str) -> Option<PathBuf> {
+		{
+			let mut current_theme = self.theme.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+			if current_theme.as_deref() != Some(theme) {
+				self.lock().retain(|(_, _, cached_theme), _| cached_theme == theme);
+				*current_theme = Some(theme.to_owned());
+			}
+		}
+		let key = (name.to_owned(), size, theme.to_owned());
+		if let Some(cached) = self.lock().get(&key) {
+			return cached.clone()
+		}
+		//Looked up without holding the lock so other threads aren't blocked on the filesystem
+		let resolved = self.lookup.resolve(name, size, theme);
+		self.lock().insert(key, resolved.clone());
+		resolved
+	}
+
+	pub fn clear(&self) {
+		self.lock().clear();
+	}
+
+	pub fn len(&self) -> usize {
+		self.lock().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lock().is_empty()
+	}
+
+	fn lock(&self) -> MutexGuard<'_, HashMap<CacheKey, Option<PathBuf>>> {
+		self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	/// Writes the resolved icons as `theme\tsize\tname\tpath` lines, misses are not kept
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let contents: String = self.lock().iter()
+			.filter_map(|((name, size, theme), resolved)| Some(format!("{theme}\t{size}\t{name}\t{}\n", resolved.as_ref()?.display())))
+			.collect();
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(path, contents)
+	}
+
+	/// Reads a cache written by [`IconCache::save`], entries whose file is gone are dropped
+	pub fn load(lookup: IconLookup, path: &Path) -> io::Result<Self> {
+		let contents = fs::read_to_string(path)?;
+		let cache = Self::new(lookup);
+		{
+			let mut entries = cache.lock();
+			for line in contents.lines() {
+				let mut fields = line.splitn(4, '\t');
+				let (Some(theme), Some(size), Some(name), Some(resolved)) = (fields.next(), fields.next(), fields.next(), fields.next()) else { continue };
+				let (Ok(size), resolved) = (size.parse(), PathBuf::from(resolved)) else { continue };
+				if resolved.is_file() {
+					entries.insert((name.to_owned(), size, theme.to_owned()), Some(resolved));
+				}
+			}
+		}
+		Ok(cache)
+	}
+}
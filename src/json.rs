@@ -0,0 +1,83 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Int(i64),
+	Str(String),
+	Arr(Vec<Json>),
+	Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+	pub fn obj<K: Into<String>, V: Into<Json>>(pairs: impl IntoIterator<Item = (K, V)>) -> Self {
+		Self::Obj(pairs.into_iter().map(|(key, val)| (key.into(), val.into())).collect())
+	}
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+	f.write_str("\"")?;
+	for c in s.chars() {
+		match c {
+			'"' => f.write_str("\\\"")?,
+			'\\' => f.write_str("\\\\")?,
+			'\n' => f.write_str("\\n")?,
+			'\r' => f.write_str("\\r")?,
+			'\t' => f.write_str("\\t")?,
+			c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{c}")?,
+		}
+	}
+	f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Null => f.write_str("null"),
+			Self::Bool(b) => write!(f, "{b}"),
+			Self::Int(n) => write!(f, "{n}"),
+			Self::Str(s) => write_str(f, s),
+			Self::Arr(items) => {
+				f.write_str("[")?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 { f.write_str(",")?; }
+					write!(f, "{item}")?;
+				}
+				f.write_str("]")
+			},
+			Self::Obj(pairs) => {
+				f.write_str("{")?;
+				for (i, (key, val)) in pairs.iter().enumerate() {
+					if i > 0 { f.write_str(",")?; }
+					write_str(f, key)?;
+					write!(f, ":{val}")?;
+				}
+				f.write_str("}")
+			},
+		}
+	}
+}
+
+impl From<bool> for Json {
+	fn from(b: bool) -> Self { Self::Bool(b) }
+}
+impl From<i32> for Json {
+	fn from(n: i32) -> Self { Self::Int(n.into()) }
+}
+impl From<usize> for Json {
+	fn from(n: usize) -> Self { Self::Int(n as i64) }
+}
+impl From<&str> for Json {
+	fn from(s: &str) -> Self { Self::Str(s.to_owned()) }
+}
+impl From<String> for Json {
+	fn from(s: String) -> Self { Self::Str(s) }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+	fn from(maybe: Option<T>) -> Self { maybe.map_or(Self::Null, Into::into) }
+}
+impl<T: Into<Json>> From<Vec<T>> for Json {
+	fn from(items: Vec<T>) -> Self { Self::Arr(items.into_iter().map(Into::into).collect()) }
+}
@@ -0,0 +1,19 @@
+pub mod apps;
+pub mod cache;
+pub mod category;
+pub mod exec;
+pub mod fold;
+pub mod fuzzy;
+pub mod history;
+pub mod icon;
+pub mod json;
+pub mod locale;
+pub mod mime;
+pub mod render;
+pub mod startup;
+pub mod terminal;
+pub mod time;
+pub mod validate;
+pub mod xdg;
+
+pub use apps::{DecodeError, Ini, IniAction, Installed, RunError, Spawn, SpawnMode, parse_desktop_entry};
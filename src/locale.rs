@@ -0,0 +1,53 @@
+use std::env;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+	lang: String,
+	country: Option<String>,
+	modifier: Option<String>,
+}
+impl Locale {
+	/// Parses `lang_COUNTRY.ENCODING@MODIFIER`, returns `None` for the C/POSIX locale
+	pub fn parse(raw: &str) -> Option<Self> {
+		let (rest, modifier) = match raw.split_once('@') {
+			Some((rest, modifier)) => (rest, Some(modifier.to_owned())),
+			None => (raw, None),
+		};
+		let rest = rest.split_once('.').map_or(rest, |(rest, _encoding)| rest);
+		let (lang, country) = match rest.split_once('_') {
+			Some((lang, country)) => (lang, Some(country.to_owned())),
+			None => (rest, None),
+		};
+		match lang {
+			"" | "C" | "POSIX" => None,
+			_ => Some(Self { lang: lang.to_owned(), country, modifier }),
+		}
+	}
+
+	pub fn from_env() -> Option<Self> {
+		["LC_ALL", "LC_MESSAGES", "LANG"].into_iter()
+			.filter_map(|var| env::var(var).ok())
+			.find(|val| !val.is_empty())
+			.and_then(|val| Self::parse(&val))
+	}
+
+	/// The `Key[locale]` suffixes to try, best match first
+	pub fn keys(&self) -> Vec<String> {
+		let mut keys = Vec::with_capacity(4);
+		if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+			keys.push(format!("{}_{country}@{modifier}", self.lang));
+		}
+		if let Some(country) = &self.country {
+			keys.push(format!("{}_{country}", self.lang));
+		}
+		if let Some(modifier) = &self.modifier {
+			keys.push(format!("{}@{modifier}", self.lang));
+		}
+		keys.push(self.lang.clone());
+		keys
+	}
+
+	pub fn rank(&self, key_locale: &str) -> Option<usize> {
+		self.keys().iter().position(|key| key == key_locale)
+	}
+}
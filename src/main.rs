@@ -1,8 +1,10 @@
-mod apps;
-mod cli;
+use std::process::ExitCode;
 
-fn main() {
-	if let Some(()) = cli::parser() {
+mod cli;
+mod completions;
+mod config;
+mod doctor;
 
-	}
-}
\ No newline at end of file
+fn main() -> ExitCode {
+	cli::parser()
+}
@@ -0,0 +1,154 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::apps::{Ini, current_desktops};
+use crate::exec::glob_match;
+use crate::xdg;
+
+pub const DIRECTORY: &str = "inode/directory";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Glob {
+	weight: u32,
+	mime_type: String,
+	pattern: String,
+	case_sensitive: bool,
+}
+
+/// File name globs from the shared-mime-info `globs2` files
+#[derive(Debug, Clone, Default)]
+pub struct MimeGlobs {
+	globs: Vec<Glob>,
+}
+impl MimeGlobs {
+	/// Parses `weight:type:glob[:flags]` lines, later lines never override earlier ones
+	pub fn parse(contents: &str) -> Self {
+		let globs = contents.lines()
+			.filter(|line| !line.starts_with('#'))
+			.filter_map(|line| {
+				let mut fields = line.splitn(4, ':');
+				let weight = fields.next()?.parse().ok()?;
+				let mime_type = fields.next()?.to_owned();
+				let pattern = fields.next()?.to_owned();
+				let case_sensitive = fields.next().is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+				Some(Glob { weight, mime_type, pattern, case_sensitive })
+			})
+			.collect();
+		Self { globs }
+	}
+
+	/// `mime/globs2` of every XDG data directory, the user's own first
+	pub fn load() -> Self {
+		let globs = xdg::data_home().into_iter().chain(xdg::data_dirs())
+			.filter_map(|dir| fs::read_to_string(dir.join("mime/globs2")).ok())
+			.flat_map(|contents| Self::parse(&contents).globs)
+			.collect();
+		Self { globs }
+	}
+
+	/// The highest weighted match, ties go to the longest pattern
+	pub fn match_name(&self, file_name: &str) -> Option<&str> {
+		let lowercase = file_name.to_lowercase();
+		self.globs.iter()
+			.filter(|glob| match glob.case_sensitive {
+				true => glob_match(&glob.pattern, file_name),
+				false => glob_match(&glob.pattern.to_lowercase(), &lowercase),
+			})
+			.max_by_key(|glob| (glob.weight, glob.pattern.len()))
+			.map(|glob| glob.mime_type.as_str())
+	}
+
+	/// Guesses the type of `path` from its name, directories are `inode/directory`
+	pub fn guess(&self, path: &Path) -> Option<String> {
+		if path.is_dir() {
+			return Some(DIRECTORY.to_owned())
+		}
+		self.match_name(path.file_name()?.to_str()?).map(|mime_type| mime_type.to_owned())
+	}
+}
+
+//Desktop file IDs listed for each MIME type by one group of mimeapps.list files
+type Associations = Vec<(String, Vec<String>)>;
+
+fn listed<'a>(associations: &'a Associations, mime_type: &str) -> impl Iterator<Item = &'a str> {
+	associations.iter().filter(move |(listed, _)| listed == mime_type).flat_map(|(_, ids)| ids.iter().map(String::as_str))
+}
+
+/// The default applications and associations of `mimeapps.list` files
+#[derive(Debug, Clone, Default)]
+pub struct MimeApps {
+	defaults: Associations,
+	added: Associations,
+	removed: Associations,
+}
+impl MimeApps {
+	pub const FILE_NAME: &str = "mimeapps.list";
+
+	/// Parses the `[Default Applications]`, `[Added Associations]` and `[Removed Associations]` groups
+	pub fn parse(contents: &str) -> Self {
+		let mut apps = Self::default();
+		let mut group = None;
+		for line in contents.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue
+			}
+			if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+				group = match name {
+					"Default Applications" => Some(&mut apps.defaults),
+					"Added Associations" => Some(&mut apps.added),
+					"Removed Associations" => Some(&mut apps.removed),
+					_ => None,
+				};
+				continue
+			}
+			let (Some(associations), Some((mime_type, ids))) = (group.as_deref_mut(), line.split_once('=')) else { continue };
+			let ids = ids.split(';').map(str::trim).filter(|id| !id.is_empty()).map(|id| id.to_owned()).collect();
+			associations.push((mime_type.trim().to_owned(), ids));
+		}
+		apps
+	}
+
+	/// The files in the order the specification gives them precedence, desktop specific ones first in
+	/// every directory
+	pub fn paths() -> Vec<PathBuf> {
+		let desktops = current_desktops();
+		let config_dirs = xdg::config_home().into_iter().chain(xdg::config_dirs());
+		let data_dirs = xdg::data_home().into_iter().chain(xdg::data_dirs()).map(|dir| dir.join("applications"));
+		config_dirs.chain(data_dirs)
+			.flat_map(|dir| desktops.iter()
+				.map(|desktop| dir.join(format!("{}-{}", desktop.to_lowercase(), Self::FILE_NAME)))
+				.chain([dir.join(Self::FILE_NAME)])
+				.collect::<Vec<_>>())
+			.collect()
+	}
+
+	/// Every readable file of [`MimeApps::paths`], earlier defaults win over later ones
+	pub fn load() -> Self {
+		Self::from_files(Self::paths().into_iter().filter_map(|path| fs::read_to_string(path).ok()))
+	}
+
+	/// Merges the contents of several files, the first ones taking precedence
+	pub fn from_files(files: impl IntoIterator<Item = String>) -> Self {
+		let mut apps = Self::default();
+		for contents in files {
+			let parsed = Self::parse(&contents);
+			apps.defaults.extend(parsed.defaults);
+			apps.added.extend(parsed.added);
+			apps.removed.extend(parsed.removed);
+		}
+		apps
+	}
+
+	/// The first default of `mime_type` that is among `entries`, defaults that aren't installed are skipped
+	pub fn default_for<'a>(&self, mime_type: &str, entries: &'a [Ini]) -> Option<&'a Ini> {
+		listed(&self.defaults, mime_type).find_map(|id| entries.iter().find(|ini| ini.id == id))
+	}
+
+	/// The entries handling `mime_type` by their MimeType key or an added association, without the ones
+	/// whose association was removed. Removals apply regardless of which file made the association.
+	pub fn handlers<'a>(&self, mime_type: &str, entries: &'a [Ini]) -> Vec<&'a Ini> {
+		entries.iter()
+			.filter(|ini| ini.handles(mime_type) || listed(&self.added, mime_type).any(|id| ini.id == id))
+			.filter(|ini| !listed(&self.removed, mime_type).any(|id| ini.id == id))
+			.collect()
+	}
+}
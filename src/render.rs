@@ -0,0 +1,229 @@
+use std::io::Write;
+
+use crate::apps::{Details, Ini, IniAction};
+use crate::category::Tree;
+use crate::exec::strip_field_codes;
+use crate::json::Json;
+
+/// Output formats of the provided renderers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+	#[default]
+	Text,
+	Json,
+	Ndjson,
+}
+
+/// Receives discovered entries for presentation, the text, tree and JSON output are implementations
+/// and frontends can plug in their own. Write errors are the renderer's to handle, the provided
+/// renderers stop writing silently, e.g. once a pipe is closed.
+pub trait Renderer {
+	fn entry(&mut self, ini: &Ini);
+
+	/// An action of the entry last passed to [`Renderer::entry`]
+	fn action(&mut self, _id: &str, _action: &IniAction<String>) {}
+
+	/// Starts a category, the entries that follow belong to it until the next one
+	fn category(&mut self, _name: &str) {}
+
+	/// Called once after everything was rendered
+	fn finish(&mut self) {}
+}
+
+//Sorted so the output is stable, the HashMap order isn't
+fn sorted_actions(ini: &Ini) -> Vec<(&String, &IniAction<String>)> {
+	let mut actions: Vec<_> = ini.actions.iter().collect();
+	actions.sort_by_key(|(id, _)| *id);
+	actions
+}
+
+fn render_entry(renderer: &mut impl Renderer, ini: &Ini) {
+	renderer.entry(ini);
+	sorted_actions(ini).into_iter().for_each(|(id, action)| renderer.action(id, action));
+}
+
+/// Renders every entry with its actions
+pub fn render(renderer: &mut impl Renderer, entries: &[Ini]) {
+	entries.iter().for_each(|ini| render_entry(renderer, ini));
+	renderer.finish();
+}
+
+/// Renders every category followed by its entries
+pub fn render_tree(renderer: &mut impl Renderer, tree: &Tree) {
+	for (category, entries) in tree {
+		renderer.category(category);
+		entries.iter().for_each(|ini| render_entry(renderer, ini));
+	}
+	renderer.finish();
+}
+
+/// A name per line, or every entry with its actions when showing details
+pub struct Text<W: Write> {
+	out: W,
+	details: bool,
+	strip_field_codes: bool,
+}
+impl<W: Write> Text<W> {
+	pub const fn new(out: W, details: bool) -> Self {
+		Self { out, details, strip_field_codes: false }
+	}
+
+	/// Show every Exec in its clean form, see [`strip_field_codes`]
+	pub const fn with_strip_field_codes(mut self, strip_field_codes: bool) -> Self {
+		self.strip_field_codes = strip_field_codes;
+		self
+	}
+}
+impl<W: Write> Renderer for Text<W> {
+	fn entry(&mut self, ini: &Ini) {
+		let _ = match (self.details, self.strip_field_codes) {
+			(true, true) => writeln!(self.out, "{}", Details { ini, exec: &strip_field_codes(&ini.exec) }),
+			(true, false) => writeln!(self.out, "{ini}"),
+			(false, _) => writeln!(self.out, "{}", ini.name),
+		};
+	}
+
+	fn action(&mut self, id: &str, action: &IniAction<String>) {
+		if !self.details { return; }
+		let exec = action.exec.as_deref().map(|exec| match self.strip_field_codes {
+			true => strip_field_codes(exec),
+			false => exec.to_owned(),
+		});
+		let _ = writeln!(self.out, "\n\t[Action]\n\t{id}\n\t- Name={}\n\t- Exec={}\n\t- Terminal={}",
+			action.name.as_deref().unwrap_or("None"),
+			exec.as_deref().unwrap_or("None"),
+			action.terminal.unwrap_or(false));
+	}
+}
+
+/// Category names with their entries' names indented below them
+pub struct TreeText<W: Write> {
+	out: W,
+}
+impl<W: Write> TreeText<W> {
+	pub const fn new(out: W) -> Self {
+		Self { out }
+	}
+}
+impl<W: Write> Renderer for TreeText<W> {
+	fn entry(&mut self, ini: &Ini) {
+		let _ = writeln!(self.out, "\t{}", ini.name);
+	}
+
+	fn category(&mut self, name: &str) {
+		let _ = writeln!(self.out, "{name}");
+	}
+}
+
+fn action_json(id: &str, action: &IniAction<String>) -> Json {
+	Json::obj([
+		("id", Json::from(id)),
+		("name", action.name.clone().into()),
+		("exec", action.exec.clone().into()),
+		("terminal", action.terminal.into()),
+	])
+}
+
+//Adds an action to the `actions` array of an entry object
+fn push_action(entry: &mut Json, action: Json) {
+	let Json::Obj(fields) = entry else { return };
+	if let Some((_, Json::Arr(actions))) = fields.iter_mut().find(|(key, _)| key == "actions") {
+		actions.push(action);
+	}
+}
+
+//Every entry object has an `actions` array, empty for an entry without any
+fn entry_json(ini: &Ini, details: bool) -> Json {
+	let mut json = match details {
+		true => ini.to_json_details(),
+		false => ini.to_json(),
+	};
+	if let Json::Obj(fields) = &mut json {
+		fields.push(("actions".to_owned(), Json::Arr(Vec::new())));
+	}
+	json
+}
+
+/// The entry object [`JsonArray`] and [`Ndjson`] write for `ini`, with its actions
+pub fn entry_object(ini: &Ini) -> Json {
+	let mut json = entry_json(ini, false);
+	sorted_actions(ini).into_iter().for_each(|(id, action)| push_action(&mut json, action_json(id, action)));
+	json
+}
+
+/// A single JSON array of entry objects, written once rendering finishes
+pub struct JsonArray<W: Write> {
+	out: W,
+	entries: Vec<Json>,
+	details: bool,
+}
+impl<W: Write> JsonArray<W> {
+	pub const fn new(out: W) -> Self {
+		Self { out, entries: Vec::new(), details: false }
+	}
+
+	/// Write every key of the entries, see [`Ini::to_json_details`]
+	pub const fn with_details(mut self, details: bool) -> Self {
+		self.details = details;
+		self
+	}
+}
+impl<W: Write> Renderer for JsonArray<W> {
+	fn entry(&mut self, ini: &Ini) {
+		self.entries.push(entry_json(ini, self.details));
+	}
+
+	fn action(&mut self, id: &str, action: &IniAction<String>) {
+		if let Some(entry) = self.entries.last_mut() {
+			push_action(entry, action_json(id, action));
+		}
+	}
+
+	fn finish(&mut self) {
+		let _ = writeln!(self.out, "{}", Json::Arr(std::mem::take(&mut self.entries)));
+	}
+}
+
+/// One JSON entry object per line, each written as soon as its actions are known
+pub struct Ndjson<W: Write> {
+	out: W,
+	pending: Option<Json>,
+	details: bool,
+}
+impl<W: Write> Ndjson<W> {
+	pub const fn new(out: W) -> Self {
+		Self { out, pending: None, details: false }
+	}
+
+	/// Write every key of the entries, see [`Ini::to_json_details`]
+	pub const fn with_details(mut self, details: bool) -> Self {
+		self.details = details;
+		self
+	}
+
+	fn flush_pending(&mut self) {
+		if let Some(entry) = self.pending.take() {
+			let _ = writeln!(self.out, "{entry}");
+		}
+	}
+}
+impl<W: Write> Renderer for Ndjson<W> {
+	fn entry(&mut self, ini: &Ini) {
+		self.flush_pending();
+		self.pending = Some(entry_json(ini, self.details));
+	}
+
+	fn action(&mut self, id: &str, action: &IniAction<String>) {
+		if let Some(entry) = &mut self.pending {
+			push_action(entry, action_json(id, action));
+		}
+	}
+
+	fn category(&mut self, _name: &str) {
+		self.flush_pending();
+	}
+
+	fn finish(&mut self) {
+		self.flush_pending();
+	}
+}
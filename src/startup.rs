@@ -0,0 +1,61 @@
+use std::{env, fmt, fs, process, sync::atomic::{AtomicU32, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+
+//Launches made by this process so far, keeps IDs unique within one millisecond
+static LAUNCHES: AtomicU32 = AtomicU32::new(0);
+
+fn hostname() -> String {
+	fs::read_to_string("/proc/sys/kernel/hostname").ok()
+		.or_else(|| env::var("HOSTNAME").ok())
+		.map(|name| name.trim().to_owned())
+		.filter(|name| !name.is_empty())
+		.unwrap_or_else(|| "localhost".to_owned())
+}
+
+//Quotes a value of a startup notification message, the specification escapes `"` and `\` with `\`
+fn quoted(value: &str) -> String {
+	format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A startup notification ID, handed to entries with StartupNotify=true as `$DESKTOP_STARTUP_ID`.
+///
+/// The launched application ends the startup sequence itself once its window is mapped. One that
+/// never does leaves the desktop showing a busy cursor until it times out, so a frontend that keeps
+/// running can end it by broadcasting [`StartupId::remove_message`] as a `_NET_STARTUP_INFO` client
+/// message on the root window. tal has no display connection and can't send it on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StartupId(String);
+impl StartupId {
+	/// A new ID unique to this host, process and launch: `tal-<hostname>-<pid>-<launch>_TIME<millis>`.
+	/// The timestamp is the launch time in milliseconds, the specification names it as a hint only.
+	pub fn new() -> Self {
+		let launch = LAUNCHES.fetch_add(1, Ordering::Relaxed);
+		let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis());
+		//Whitespace would need quoting everywhere the ID is passed along
+		let hostname: String = hostname().chars().map(|c| if c.is_whitespace() { '_' } else { c }).collect();
+		Self(format!("tal-{hostname}-{}-{launch}_TIME{millis}", process::id()))
+	}
+
+	/// An ID received from elsewhere, such as the frontend that started tal
+	pub const fn from_string(id: String) -> Self {
+		Self(id)
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	/// The message that ends this startup sequence
+	pub fn remove_message(&self) -> String {
+		format!("remove: ID={}", quoted(&self.0))
+	}
+}
+impl Default for StartupId {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl fmt::Display for StartupId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
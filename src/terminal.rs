@@ -0,0 +1,112 @@
+use std::{env, ffi::OsStr, fmt, path::{Path, PathBuf}};
+
+use crate::exec::{exec_program, which, which_in};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+	/// Named on the command line with `--terminal`
+	Flag,
+	Env,
+	Fallback,
+	/// The first of [`KNOWN_TERMINALS`] found on PATH
+	Detected,
+}
+impl fmt::Display for Origin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Flag => "--terminal",
+			Self::Env => "$TERMINAL",
+			Self::Fallback => "terminal fallback",
+			Self::Detected => "detected on PATH",
+		})
+	}
+}
+
+/// Terminals looked for on PATH when nothing else names one, the distribution's choice first
+pub const KNOWN_TERMINALS: &[&str] = &[
+	"x-terminal-emulator", "kitty", "alacritty", "foot", "wezterm", "ghostty", "gnome-terminal", "ptyxis", "kgx",
+	"konsole", "xfce4-terminal", "mate-terminal", "terminator", "tilix", "lxterminal", "urxvt", "st", "xterm",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminal {
+	pub program: String,
+	pub origin: Origin,
+}
+impl Terminal {
+	/// Arguments placed between the terminal and the command it should run
+	const EXEC_ARGS: &[(&str, &[&str])] = &[
+		("gnome-terminal", &["--"]),
+		("ptyxis", &["--"]),
+		("kgx", &["--"]),
+		("wezterm", &["start", "--"]),
+		("kitty", &[]),
+		("foot", &[]),
+		("xfce4-terminal", &["-x"]),
+		("terminator", &["-x"]),
+		("mate-terminal", &["-x"]),
+	];
+
+	pub const fn new(program: String, origin: Origin) -> Self {
+		Self { program, origin }
+	}
+
+	pub fn exec_args(&self) -> &'static [&'static str] {
+		let program_name = Path::new(&self.program).file_name().and_then(|name| name.to_str()).unwrap_or(&self.program);
+		Self::EXEC_ARGS.iter()
+			.find(|(name, _)| *name == program_name)
+			.map_or(&["-e"], |(_, args)| args)
+	}
+
+	pub fn path(&self) -> Option<PathBuf> {
+		which(&self.program)
+	}
+
+	/// Prefixes `argv` so it runs inside this terminal
+	pub fn wrap(&self, argv: Vec<String>) -> Vec<String> {
+		let exec_args = self.exec_args();
+		let mut wrapped = Vec::with_capacity(argv.len() + exec_args.len() + 1);
+		wrapped.push(self.program.clone());
+		wrapped.extend(exec_args.iter().map(|arg| arg.to_string()));
+		wrapped.extend(argv);
+		wrapped
+	}
+}
+
+/// `$TERMINAL` wins, then the first of [`KNOWN_TERMINALS`] on `path` when `detect` is set. The fallback
+/// is the last resort and only used when it exists on `path`.
+pub fn resolve_in(env_terminal: Option<String>, fallback: Option<String>, detect: bool, path: &OsStr) -> Option<Terminal> {
+	env_terminal.filter(|term| !term.is_empty()).map(|term| Terminal::new(term, Origin::Env))
+		.or_else(|| detect.then(|| detect_in(path)).flatten())
+		.or_else(|| fallback.filter(|term| which_in(term, path).is_some()).map(|term| Terminal::new(term, Origin::Fallback)))
+}
+
+/// [`resolve_in`] on PATH
+pub fn resolve(env_terminal: Option<String>, fallback: Option<String>, detect: bool) -> Option<Terminal> {
+	resolve_in(env_terminal, fallback, detect, &env::var_os("PATH").unwrap_or_default())
+}
+
+/// The first of [`KNOWN_TERMINALS`] on `path`
+pub fn detect_in(path: &OsStr) -> Option<Terminal> {
+	KNOWN_TERMINALS.iter()
+		.find(|term| which_in(term, path).is_some())
+		.map(|term| Terminal::new(term.to_string(), Origin::Detected))
+}
+
+pub fn detect() -> Option<Terminal> {
+	detect_in(&env::var_os("PATH")?)
+}
+
+/// Programs that only make sense inside a terminal, used by [`is_terminal_tool`] when no list is configured
+pub const TERMINAL_TOOLS: &[&str] = &[
+	"htop", "btop", "top", "vim", "nvim", "vi", "nano", "micro", "hx", "mc", "ranger", "nnn", "lf", "yazi",
+	"ncdu", "less", "man", "tmux", "screen", "mutt", "neomutt", "aerc", "w3m", "lynx", "irssi", "weechat",
+	"cmus", "ncmpcpp", "alsamixer", "nmtui",
+];
+
+/// Best-effort guess whether `argv` runs a terminal program, looking past a leading `env` and its options and assignments
+pub fn is_terminal_tool<S: AsRef<str>>(argv: &[String], tools: &[S]) -> bool {
+	exec_program(argv)
+		.and_then(|program| Path::new(program).file_name()?.to_str())
+		.is_some_and(|name| tools.iter().any(|tool| tool.as_ref() == name))
+}
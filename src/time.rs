@@ -0,0 +1,76 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("{0:?} is not an RFC 3339 timestamp like 2024-01-01T00:00:00Z")]
+pub struct InvalidTimestamp(pub String);
+
+//Days between 1970-01-01 and the given proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+	match month {
+		2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
+	}
+}
+
+fn number(digits: &str) -> Option<i64> {
+	match !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+		true => digits.parse().ok(),
+		false => None,
+	}
+}
+
+//Seconds since the epoch, negative before it
+fn epoch_seconds(timestamp: &str) -> Option<i64> {
+	let (date, time) = timestamp.split_once(['T', 't', ' '])?;
+	let mut date_fields = date.splitn(3, '-');
+	let year = date_fields.next().filter(|year| year.len() == 4).and_then(number)?;
+	let month = date_fields.next().filter(|month| month.len() == 2).and_then(number)?;
+	let day = date_fields.next().filter(|day| day.len() == 2).and_then(number)?;
+	if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+		return None
+	}
+
+	let (clock, offset) = match time.strip_suffix(['Z', 'z']) {
+		Some(clock) => (clock, 0),
+		None => {
+			let sign_at = time.rfind(['+', '-'])?;
+			let (clock, offset) = time.split_at(sign_at);
+			let (hours, minutes) = offset[1..].split_once(':')?;
+			let (hours, minutes) = (number(hours).filter(|_| hours.len() == 2)?, number(minutes).filter(|_| minutes.len() == 2)?);
+			if hours > 23 || minutes > 59 {
+				return None
+			}
+			let offset = hours * 3600 + minutes * 60;
+			(clock, if time[sign_at..].starts_with('-') { -offset } else { offset })
+		},
+	};
+	//Fractional seconds are allowed but don't change the second
+	let clock = clock.split_once('.').map_or(Some(clock), |(clock, fraction)| number(fraction).map(|_| clock))?;
+	let mut clock_fields = clock.splitn(3, ':');
+	let mut field = |max| clock_fields.next().filter(|field| field.len() == 2).and_then(number).filter(|value| *value <= max);
+	let (hour, minute, second) = (field(23)?, field(59)?, field(60)?);
+	Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// Parses an RFC 3339 timestamp such as `2024-01-01T00:00:00Z` or `2024-01-01T02:00:00.5+02:00`
+pub fn parse_rfc3339(timestamp: &str) -> Result<SystemTime, InvalidTimestamp> {
+	let invalid = || InvalidTimestamp(timestamp.to_owned());
+	let seconds = epoch_seconds(timestamp).ok_or_else(invalid)?;
+	let since_epoch = Duration::from_secs(seconds.unsigned_abs());
+	match seconds < 0 {
+		true => UNIX_EPOCH.checked_sub(since_epoch),
+		false => UNIX_EPOCH.checked_add(since_epoch),
+	}.ok_or_else(invalid)
+}
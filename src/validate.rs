@@ -0,0 +1,207 @@
+use std::{collections::{HashMap, HashSet}, fmt, fs, path::Path};
+
+use crate::category::MAIN_CATEGORIES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Error => "error",
+			Self::Warning => "warning",
+		})
+	}
+}
+
+/// A spec violation, `line` is 1-based and `None` when it concerns the file as a whole
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+	pub severity: Severity,
+	pub line: Option<usize>,
+	pub message: String,
+}
+impl fmt::Display for Violation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "{line}: {}", self.message),
+			None => f.write_str(&self.message),
+		}
+	}
+}
+
+const TYPES: [&str; 3] = ["Application", "Link", "Directory"];
+/// Boolean keys defined by the specification, the keys [`Validator`] checks strictly by default
+pub const BOOLEAN_KEYS: [&str; 7] = ["Terminal", "NoDisplay", "Hidden", "DBusActivatable", "StartupNotify", "PrefersNonDefaultGPU", "SingleMainWindow"];
+const LIST_KEYS: [&str; 7] = ["OnlyShowIn", "NotShowIn", "Actions", "MimeType", "Categories", "Implements", "Keywords"];
+
+fn valid_key(key: &str) -> bool {
+	let (name, locale) = match key.split_once('[') {
+		Some((name, locale)) => (name, locale.strip_suffix(']')),
+		None => (key, Some("")),
+	};
+	!name.is_empty()
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+		&& locale.is_some_and(|locale| !locale.contains(['[', ']']))
+}
+
+/// Checks desktop files against the Desktop Entry specification
+#[derive(Debug, Clone)]
+pub struct Validator {
+	strict_booleans: Vec<String>,
+}
+impl Default for Validator {
+	fn default() -> Self {
+		Self::new(BOOLEAN_KEYS.iter().map(|key| key.to_string()).collect())
+	}
+}
+impl Validator {
+	/// Only the keys in `strict_booleans` must be exactly `true` or `false`, so vendor booleans
+	/// with their own spellings pass unless they are listed
+	pub const fn new(strict_booleans: Vec<String>) -> Self {
+		Self { strict_booleans }
+	}
+
+	/// Line violations come in file order, followed by the ones about the file as a whole
+	pub fn validate(&self, contents: &str) -> Vec<Violation> {
+		validate_with(contents, &self.strict_booleans)
+	}
+
+	/// Like [`Validator::validate`], a file that can't be read or isn't UTF-8 is a single error
+	pub fn validate_file(&self, path: &Path) -> Vec<Violation> {
+		let error = |message| vec![Violation { severity: Severity::Error, line: None, message }];
+		match fs::read(path) {
+			Ok(bytes) => match String::from_utf8(bytes) {
+				Ok(contents) => self.validate(&contents),
+				Err(_) => error("The file is not valid UTF-8".to_owned()),
+			},
+			Err(e) => error(format!("Failed to read the file: {e}")),
+		}
+	}
+}
+
+/// Validates with the default [`Validator`]
+pub fn validate(contents: &str) -> Vec<Violation> {
+	Validator::default().validate(contents)
+}
+
+/// Validates a file with the default [`Validator`]
+pub fn validate_file(path: &Path) -> Vec<Violation> {
+	Validator::default().validate_file(path)
+}
+
+fn validate_with(contents: &str, strict_booleans: &[String]) -> Vec<Violation> {
+	let mut violations = Vec::new();
+	let mut report = |severity, line, message: String| violations.push(Violation { severity, line, message });
+
+	let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+	let mut group: Option<&str> = None;
+	let mut seen_groups = HashSet::new();
+	let mut seen_keys = HashSet::new();
+	let mut header: HashMap<&str, (usize, &str)> = HashMap::new();
+	for (line_index, line) in contents.lines().enumerate() {
+		let line_num = Some(line_index + 1);
+		let trimmed = line.trim();
+		if trimmed.is_empty() || trimmed.starts_with('#') { continue; }
+
+		if let Some(header_line) = trimmed.strip_prefix('[') {
+			let Some(name) = header_line.strip_suffix(']') else {
+				report(Severity::Error, line_num, format!("Malformed group header {trimmed:?}"));
+				continue;
+			};
+			if group.is_none() && name != "Desktop Entry" {
+				report(Severity::Error, line_num, format!("The first group must be [Desktop Entry], found [{name}]"));
+			}
+			if !seen_groups.insert(name) {
+				report(Severity::Error, line_num, format!("Group [{name}] appears more than once"));
+			}
+			if name != "Desktop Entry" && !name.starts_with("Desktop Action ") && !name.starts_with("X-") {
+				report(Severity::Warning, line_num, format!("Unknown group [{name}], extension groups should start with X-"));
+			}
+			group = Some(name);
+			seen_keys.clear();
+			continue;
+		}
+		let Some(group) = group else {
+			report(Severity::Error, line_num, "Key outside of any group".to_owned());
+			continue;
+		};
+		let Some((key, value)) = line.split_once('=').map(|(key, value)| (key.trim(), value.trim())) else {
+			report(Severity::Error, line_num, format!("{trimmed:?} is not a group header, comment or key=value pair"));
+			continue;
+		};
+		if !valid_key(key) {
+			report(Severity::Error, line_num, format!("Invalid key name {key:?}"));
+			continue;
+		}
+		if !seen_keys.insert(key) {
+			report(Severity::Error, line_num, format!("Key {key} appears more than once in [{group}]"));
+		}
+		let base_key = key.split('[').next().unwrap_or(key);
+		if group == "Desktop Entry" && base_key == key {
+			header.insert(key, (line_index + 1, value));
+		}
+
+		if strict_booleans.iter().any(|strict| strict == base_key) {
+			match value {
+				"true" | "false" => (),
+				"0" | "1" => report(Severity::Warning, line_num, format!("{key}={value} is deprecated, use true or false")),
+				_ => report(Severity::Error, line_num, format!("{key}={value:?} is not a boolean")),
+			}
+		}
+		if LIST_KEYS.contains(&base_key) && !value.is_empty() && !value.ends_with(';') {
+			report(Severity::Warning, line_num, format!("The {key} list should end with a semicolon"));
+		}
+		if base_key == "Exec" {
+			let mut chars = value.chars();
+			while let Some(c) = chars.next() {
+				if c != '%' { continue; }
+				match chars.next() {
+					Some('f' | 'F' | 'u' | 'U' | 'i' | 'c' | 'k' | '%') => (),
+					Some(code @ ('d' | 'D' | 'n' | 'N' | 'v' | 'm')) => report(Severity::Warning, line_num, format!("Field code %{code} is deprecated")),
+					Some(code) => report(Severity::Error, line_num, format!("Unknown field code %{code}")),
+					None => report(Severity::Error, line_num, "Exec ends with a lone %".to_owned()),
+				}
+			}
+		}
+		if base_key == "Icon" && !Path::new(value).is_absolute()
+			&& Path::new(value).extension().is_some_and(|ext| ext == "png" || ext == "svg" || ext == "xpm")
+		{
+			report(Severity::Warning, line_num, format!("Icon {value:?} is not an absolute path and should be given without its extension"));
+		}
+		if key == "Encoding" {
+			report(Severity::Warning, line_num, "The Encoding key is deprecated".to_owned());
+		}
+	}
+
+	if !seen_groups.contains("Desktop Entry") {
+		report(Severity::Error, None, "The file has no [Desktop Entry] group".to_owned());
+		return violations
+	}
+	let entry_type = header.get("Type").map(|(_, value)| *value);
+	match entry_type {
+		None => report(Severity::Error, None, "Required key Type is missing".to_owned()),
+		Some(entry_type) if !TYPES.contains(&entry_type) => {
+			report(Severity::Error, header.get("Type").map(|(line, _)| *line), format!("Unknown Type {entry_type:?}"));
+		},
+		_ => (),
+	}
+	if !header.contains_key("Name") {
+		report(Severity::Error, None, "Required key Name is missing".to_owned());
+	}
+	let dbus_activatable = header.get("DBusActivatable").is_some_and(|(_, value)| *value == "true");
+	if entry_type == Some("Application") && !header.contains_key("Exec") && !dbus_activatable {
+		report(Severity::Error, None, "Applications need an Exec key unless DBusActivatable=true".to_owned());
+	}
+	if entry_type == Some("Link") && !header.contains_key("URL") {
+		report(Severity::Error, None, "Links need a URL key".to_owned());
+	}
+	if let Some((line, categories)) = header.get("Categories")
+		&& !categories.split(';').any(|category| MAIN_CATEGORIES.contains(&category))
+	{
+		report(Severity::Warning, Some(*line), "Categories has no registered main category".to_owned());
+	}
+	violations
+}
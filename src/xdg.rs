@@ -0,0 +1,51 @@
+use std::{env, ffi::OsStr, path::{Path, PathBuf}};
+
+/// Used when `$XDG_DATA_DIRS` is unset or empty
+pub const DEFAULT_DATA_DIRS: &str = "/usr/local/share:/usr/share";
+/// Used when `$XDG_CONFIG_DIRS` is unset or empty
+pub const DEFAULT_CONFIG_DIRS: &str = "/etc/xdg";
+
+/// `$XDG_DATA_HOME`, falling back to `~/.local/share` when it is unset, empty or relative
+pub fn resolve_data_home(data_home: Option<&OsStr>, home: Option<&Path>) -> Option<PathBuf> {
+	data_home.map(PathBuf::from).filter(|dir| dir.is_absolute())
+		.or_else(|| home.map(|home| home.join(".local/share")))
+}
+
+/// Every directory of `$XDG_DATA_DIRS` in order, relative entries are ignored as the specification asks
+/// and [`DEFAULT_DATA_DIRS`] is used when nothing usable is left
+pub fn resolve_data_dirs(data_dirs: Option<&OsStr>) -> Vec<PathBuf> {
+	let dirs: Vec<PathBuf> = data_dirs.map(|dirs| env::split_paths(dirs).filter(|dir| dir.is_absolute()).collect()).unwrap_or_default();
+	match dirs.is_empty() {
+		true => env::split_paths(DEFAULT_DATA_DIRS).collect(),
+		false => dirs,
+	}
+}
+
+pub fn data_home() -> Option<PathBuf> {
+	resolve_data_home(env::var_os("XDG_DATA_HOME").as_deref(), env::home_dir().as_deref())
+}
+
+pub fn data_dirs() -> Vec<PathBuf> {
+	resolve_data_dirs(env::var_os("XDG_DATA_DIRS").as_deref())
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config` when it is unset, empty or relative
+pub fn config_home() -> Option<PathBuf> {
+	env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|dir| dir.is_absolute())
+		.or_else(|| env::home_dir().map(|home| home.join(".config")))
+}
+
+/// `$XDG_CACHE_HOME`, falling back to `~/.cache` when it is unset, empty or relative
+pub fn cache_home() -> Option<PathBuf> {
+	env::var_os("XDG_CACHE_HOME").map(PathBuf::from).filter(|dir| dir.is_absolute())
+		.or_else(|| env::home_dir().map(|home| home.join(".cache")))
+}
+
+/// Every absolute directory of `$XDG_CONFIG_DIRS` in order, [`DEFAULT_CONFIG_DIRS`] when none is left
+pub fn config_dirs() -> Vec<PathBuf> {
+	let dirs: Vec<PathBuf> = env::var_os("XDG_CONFIG_DIRS").map(|dirs| env::split_paths(&dirs).filter(|dir| dir.is_absolute()).collect()).unwrap_or_default();
+	match dirs.is_empty() {
+		true => env::split_paths(DEFAULT_CONFIG_DIRS).collect(),
+		false => dirs,
+	}
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use std::{fs, thread, time::{Duration, UNIX_EPOCH}};
+use common::{TempDir, entry};
+use tal::{apps::{Ini, IniAction, Installed}, cache::{CachedEntry, CachedFile, EntryCache}, locale::Locale};
+
+fn cached_file(id: &str, path: std::path::PathBuf) -> CachedFile {
+	let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+	CachedFile { id: id.to_owned(), path, modified }
+}
+
+fn cached(name: &str, exec: &str) -> CachedEntry {
+	CachedEntry::Entry(Box::new(Ini { name: name.to_owned(), exec: exec.to_owned(), ..Default::default() }))
+}
+
+//The name of every cached entry, `None` for an invalid one
+fn cached_names(entries: &[CachedEntry]) -> Vec<Option<&str>> {
+	entries.iter().map(|entry| match entry {
+		CachedEntry::Entry(ini) => Some(ini.name.as_str()),
+		CachedEntry::Invalid(_) => None,
+	}).collect()
+}
+
+#[test]
+fn cache_hits_only_while_files_are_unchanged() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let vim = cached_file("vim.desktop", apps.write("vim.desktop", &entry("Vim", "vim %F")));
+	let cache = EntryCache::new(cache_dir.path().to_owned());
+	assert!(cache.load(std::slice::from_ref(&vim), None).is_none());
+
+	cache.save(std::slice::from_ref(&vim), &[cached("Vim", "vim %F")], None).unwrap();
+	let loaded = cache.load(std::slice::from_ref(&vim), None).unwrap();
+	let CachedEntry::Entry(ini) = &loaded[0] else { panic!("{loaded:?}") };
+	assert_eq!((ini.id.as_str(), ini.name.as_str(), ini.file.as_ref()), ("vim.desktop", "Vim", Some(&vim.path)));
+	assert_eq!(ini.modified, vim.modified);
+
+	let touched = CachedFile { modified: Some(UNIX_EPOCH), ..vim.clone() };
+	assert!(cache.load(&[touched], None).is_none());
+	let htop = cached_file("htop.desktop", apps.write("htop.desktop", &entry("Htop", "htop")));
+	assert!(cache.load(&[vim.clone(), htop], None).is_none());
+	assert!(cache.load(&[], None).is_none());
+
+	fs::write(cache.path(), "tal-entries 1\nvim.desktop\tgarbage").unwrap();
+	assert!(cache.load(std::slice::from_ref(&vim), None).is_none());
+	fs::write(cache.path(), format!("tal-entries 2\nlocale\t-\nfile\tvim.desktop\t{}\t-\nunknown\tkey\n", vim.path.display())).unwrap();
+	assert!(cache.load(&[vim], None).is_none());
+}
+
+#[test]
+fn cached_entries_keep_every_field() {
+	let cache_dir = TempDir::new();
+	let cache = EntryCache::new(cache_dir.path().to_owned());
+	let file = CachedFile { id: "odd\tid.desktop".to_owned(), path: "/apps/odd.desktop".into(), modified: Some(UNIX_EPOCH + Duration::from_nanos(1_500)) };
+	let mut ini = Ini {
+		name: "Editor\nwith lines".to_owned(), untranslated_name: Some("Editor".to_owned()), generic_name: Some("Text Editor".to_owned()),
+		comment: Some("Edit\ttext \\ files".to_owned()), keywords: vec!["text".to_owned(), "code".to_owned()], exec: "editor %F".to_owned(),
+		terminal: true, categories: vec!["Utility".to_owned()], no_display: true, hidden: true, only_show_in: vec!["GNOME".to_owned()],
+		not_show_in: vec!["KDE".to_owned()], autostart_enabled: Some(false), startup_wm_class: Some("editor".to_owned()),
+		icon: Some(String::new()), mime_types: vec!["text/plain".to_owned()], dbus_activatable: true, startup_notify: true,
+		try_exec: Some("editor".to_owned()), env: vec![("GDK_SCALE".to_owned(), "a=b".to_owned())], path: Some("/tmp".into()),
+		flatpak: Some("org.editor".to_owned()), ..Default::default()
+	};
+	ini.actions.insert("new".to_owned(), IniAction { name: Some("New".to_owned()), exec: Some("editor --new".to_owned()), terminal: Some(false), path: Some("/srv".to_owned()) });
+	ini.actions.insert("bare".to_owned(), IniAction { name: None, exec: None, terminal: None, path: None });
+	let invalid = CachedFile { id: "broken.desktop".to_owned(), path: "/apps/broken.desktop".into(), modified: None };
+	let files = [file.clone(), invalid];
+	cache.save(&files, &[CachedEntry::Entry(Box::new(ini.clone())), CachedEntry::Invalid("The entry has no Name key.".to_owned())], None).unwrap();
+
+	let loaded = cache.load(&files, None).unwrap();
+	let CachedEntry::Entry(read) = &loaded[0] else { panic!("{loaded:?}") };
+	let expected = Ini { id: file.id.clone(), file: Some(file.path.clone()), modified: file.modified, ..ini };
+	assert_eq!(read.to_json_details(), expected.to_json_details());
+	assert_eq!(read.to_desktop_string(), expected.to_desktop_string());
+	assert_eq!((read.untranslated_name.as_deref(), read.modified), (Some("Editor"), file.modified));
+	assert_eq!(loaded[1], CachedEntry::Invalid("The entry has no Name key.".to_owned()));
+}
+
+#[test]
+fn caches_of_another_locale_miss() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let vim = [cached_file("vim.desktop", apps.write("vim.desktop", &entry("Vim", "vim %F")))];
+	let cache = EntryCache::new(cache_dir.path().to_owned());
+	let german = Locale::parse("de_DE.UTF-8");
+	cache.save(&vim, &[cached("Vim", "vim %F")], german.as_ref()).unwrap();
+
+	assert!(cache.load(&vim, german.as_ref()).is_some());
+	assert!(cache.load(&vim, Locale::parse("de_AT").as_ref()).is_none());
+	assert!(cache.load(&vim, None).is_none());
+}
+
+#[test]
+fn cache_older_than_the_ttl_is_refreshed() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let vim = [cached_file("vim.desktop", apps.write("vim.desktop", &entry("Vim", "vim %F")))];
+	EntryCache::new(cache_dir.path().to_owned()).save(&vim, &[cached("Vim", "vim %F")], None).unwrap();
+	thread::sleep(Duration::from_millis(20));
+
+	assert!(EntryCache::new(cache_dir.path().to_owned()).with_ttl(Some(Duration::from_secs(3600))).load(&vim, None).is_some());
+	assert!(EntryCache::new(cache_dir.path().to_owned()).with_ttl(Some(Duration::from_millis(10))).load(&vim, None).is_none());
+}
+
+#[test]
+fn refresh_rereads_files_and_rewrites_the_cache() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let vim = [cached_file("vim.desktop", apps.write("vim.desktop", &entry("Vim", "vim %F")))];
+	//A cache whose entry no longer matches the file, with an unchanged mtime
+	EntryCache::new(cache_dir.path().to_owned()).save(&vim, &[cached("Stale", "stale")], None).unwrap();
+	let names = |refresh| Installed::from_paths([apps.path().to_owned()]).with_locale(None)
+		.with_cache(Some(EntryCache::new(cache_dir.path().to_owned()).with_refresh(refresh)))
+		.all().unwrap().into_iter().map(|app| app.name).collect::<Vec<_>>();
+
+	assert_eq!(names(false), ["Stale"]);
+	assert!(EntryCache::new(cache_dir.path().to_owned()).with_refresh(true).load(&vim, None).is_none());
+	assert_eq!(names(true), ["Vim"]);
+	assert_eq!(names(false), ["Vim"]);
+}
+
+#[test]
+fn cold_caches_are_written_from_the_listing_and_not_read_twice() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let vim = apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	apps.write("broken.desktop", "[Desktop Entry]\nExec=broken\n");
+	let installed = Installed::from_paths([apps.path().to_owned()]).with_locale(None).with_cache(Some(EntryCache::new(cache_dir.path().to_owned())));
+	assert_eq!(installed.all().unwrap().len(), 1);
+
+	let files = [cached_file("broken.desktop", apps.path().join("broken.desktop")), cached_file("vim.desktop", vim)];
+	let loaded = EntryCache::new(cache_dir.path().to_owned()).load(&files, None).unwrap();
+	assert_eq!(cached_names(&loaded), [None, Some("Vim")]);
+	//Only the cache itself is left behind, no temp file
+	assert_eq!(fs::read_dir(cache_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn cached_listings_follow_edits() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	let installed = || Installed::from_paths([apps.path().to_owned()]).with_cache(Some(EntryCache::new(cache_dir.path().to_owned())));
+	let names = || installed().all().unwrap().into_iter().map(|app| app.name).collect::<Vec<_>>();
+
+	assert_eq!(names(), ["Vim"]);
+	assert!(cache_dir.path().join(EntryCache::FILE_NAME).is_file());
+	assert_eq!(names(), ["Vim"]);
+	let vim = apps.write("vim.desktop", &entry("Neovim", "nvim %F"));
+	//Make sure the mtime moves even on coarse timestamp filesystems
+	fs::File::options().write(true).open(vim).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(1)).unwrap();
+	assert_eq!(names(), ["Neovim"]);
+}
+
+#[test]
+fn unchanged_files_are_reused_when_others_change() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	let cache = EntryCache::new(cache_dir.path().to_owned());
+	let vim = cached_file("vim.desktop", apps.write("vim.desktop", &entry("Vim", "vim %F")));
+	let htop = cached_file("htop.desktop", apps.write("htop.desktop", &entry("Htop", "htop")));
+	assert_eq!(cache.load_each(std::slice::from_ref(&vim), None), (vec![None], false));
+	cache.save(&[vim.clone(), htop.clone()], &[cached("Vim", "vim %F"), cached("Htop", "htop")], None).unwrap();
+
+	let touched = CachedFile { modified: Some(UNIX_EPOCH), ..htop.clone() };
+	let nano = cached_file("nano.desktop", apps.write("nano.desktop", &entry("Nano", "nano")));
+	let (entries, exact) = cache.load_each(&[vim.clone(), touched, nano], None);
+	assert_eq!(entries.iter().map(|entry| entry.as_ref().map(|entry| cached_names(std::slice::from_ref(entry))[0])).collect::<Vec<_>>(), [Some(Some("Vim")), None, None]);
+	assert!(!exact);
+	assert!(!cache.load_each(std::slice::from_ref(&vim), None).1);
+	assert!(cache.load_each(&[htop.clone(), vim.clone()], None).1);
+	assert!(cache.save(&[vim, htop], &[cached("Vim", "vim")], None).is_err());
+}
+
+#[test]
+fn partly_stale_listings_only_read_the_changed_files() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	apps.write("htop.desktop", &entry("Htop", "htop"));
+	let installed = || Installed::from_paths([apps.path().to_owned()]).with_cache(Some(EntryCache::new(cache_dir.path().to_owned())));
+	let names = || {
+		let mut names: Vec<String> = installed().all().unwrap().into_iter().map(|app| app.name).collect();
+		names.sort();
+		names
+	};
+	assert_eq!(names(), ["Htop", "Vim"]);
+
+	//A cached file whose mtime is unchanged is served from the cache, even though its contents moved on
+	let vim = apps.path().join("vim.desktop");
+	let vim_modified = fs::metadata(&vim).unwrap().modified().unwrap();
+	fs::write(&vim, entry("Neovim", "nvim %F")).unwrap();
+	fs::File::options().write(true).open(&vim).unwrap().set_modified(vim_modified).unwrap();
+	apps.write("nano.desktop", &entry("Nano", "nano"));
+	assert_eq!(names(), ["Htop", "Nano", "Vim"]);
+
+	fs::remove_file(apps.path().join("nano.desktop")).unwrap();
+	assert_eq!(names(), ["Htop", "Vim"]);
+}
+
+#[test]
+fn all_cached_reads_through_the_entry_cache() {
+	let apps = TempDir::new();
+	let cache_dir = TempDir::new();
+	apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	let installed = Installed::from_paths([apps.path().to_owned()]).with_cache(Some(EntryCache::new(cache_dir.path().to_owned())));
+
+	assert_eq!(installed.all_cached().unwrap().into_iter().map(|app| app.name).collect::<Vec<_>>(), ["Vim"]);
+	assert!(cache_dir.path().join(EntryCache::FILE_NAME).is_file());
+	assert_eq!(installed.all_cached().unwrap().len(), 1);
+}
@@ -0,0 +1,55 @@
+use tal::{apps::{Ini, IniAction, filter_categories}, category::{counts, tree, tree_json}};
+
+fn ini(name: &str, categories: &[&str]) -> Ini {
+	Ini { name: name.to_owned(), categories: categories.iter().map(|c| c.to_string()).collect(), ..Default::default() }
+}
+
+#[test]
+fn tree_json_always_has_other() {
+	let json = tree_json(tree(vec![ini("Vim", &["Utility", "TextEditor"])])).to_string();
+	assert!(json.starts_with(r#"{"Utility":[{"id":"","name":"Vim""#));
+	assert!(json.ends_with(r#""Other":[]}"#));
+}
+
+#[test]
+fn tree_json_entries_carry_their_actions() {
+	let mut vim = ini("Vim", &["Utility"]);
+	vim.actions.insert("new".to_owned(), IniAction { name: Some("New".to_owned()), exec: Some("vim -n".to_owned()), terminal: None, path: None });
+	let json = tree_json(tree(vec![vim, ini("Bare", &[])])).to_string();
+	assert!(json.contains(r#""actions":[{"id":"new","name":"New","exec":"vim -n","terminal":null}]}]"#), "{json}");
+	assert!(json.ends_with(r#""actions":[]}]}"#), "{json}");
+}
+
+#[test]
+fn entries_without_a_main_category_go_to_other() {
+	let grouped = tree(vec![ini("Tool", &["X-Vendor"]), ini("Game", &["Game"])]);
+	let names: Vec<(&str, Vec<&str>)> = grouped.iter()
+		.map(|(category, entries)| (*category, entries.iter().map(|app| app.name.as_str()).collect()))
+		.collect();
+	assert_eq!(names, [("Game", vec!["Game"]), ("Other", vec!["Tool"])]);
+}
+
+#[test]
+fn category_filter_ignores_case_and_skips_uncategorized_entries() {
+	let entries = vec![ini("Vim", &["Utility", "TextEditor"]), ini("Code", &["Development", "IDE"]), ini("Bare", &[])];
+	let names = |categories: &[&str]| filter_categories(entries.clone(), categories).into_iter().map(|app| app.name).collect::<Vec<_>>();
+	assert_eq!(names(&["development"]), ["Code"]);
+	assert_eq!(names(&["TextEditor"]), ["Vim"]);
+	assert!(names(&["Game"]).is_empty());
+}
+
+#[test]
+fn category_filters_require_every_whole_category() {
+	let entries = vec![ini("Godot", &["Development", "GameEngine"]), ini("Chess", &["Game", "BoardGame"]), ini("Tetris", &["Game"])];
+	let names = |categories: &[&str]| filter_categories(entries.clone(), categories).into_iter().map(|app| app.name).collect::<Vec<_>>();
+	assert_eq!(names(&["game"]), ["Chess", "Tetris"]);
+	assert_eq!(names(&["Game", "BoardGame"]), ["Chess"]);
+	assert!(names(&["Game", "Development"]).is_empty());
+	assert_eq!(names(&[]), ["Godot", "Chess", "Tetris"]);
+}
+
+#[test]
+fn counts_merge_case_and_count_every_entry_once() {
+	let entries = [ini("Vim", &["Utility", "TextEditor", "utility"]), ini("Calc", &["utility"]), ini("Code", &["Development"])];
+	assert_eq!(counts(&entries), [("Development".to_owned(), 1), ("TextEditor".to_owned(), 1), ("Utility".to_owned(), 2)]);
+}
@@ -0,0 +1,438 @@
+mod common;
+
+use std::{io::Write, process::{Command, Output, Stdio}, time::{Duration, Instant}};
+use common::{TempDir, entry};
+
+fn tal(home: &TempDir, args: &[&str]) -> Output {
+	tal_with_input(home, args, "")
+}
+
+//Runs the tal binary with every XDG directory inside `home` and `input` on stdin
+fn tal_with_input(home: &TempDir, args: &[&str], input: &str) -> Output {
+	run(tal_command(home, args).env_remove("XDG_CURRENT_DESKTOP"), input)
+}
+
+//Runs tal like `tal` does with `desktop` as the current desktop
+fn tal_on(home: &TempDir, desktop: &str, args: &[&str]) -> Output {
+	run(tal_command(home, args).env("XDG_CURRENT_DESKTOP", desktop), "")
+}
+
+fn tal_command(home: &TempDir, args: &[&str]) -> Command {
+	let mut command = Command::new(env!("CARGO_BIN_EXE_tal"));
+	command.args(args)
+		.env("HOME", home.path())
+		.env("XDG_DATA_HOME", home.path().join("data"))
+		.env("XDG_DATA_DIRS", home.path().join("system"))
+		.env("XDG_CONFIG_HOME", home.path().join("config"))
+		.env("XDG_CONFIG_DIRS", home.path().join("sysconfig"))
+		.env("XDG_CACHE_HOME", home.path().join("cache"));
+	command
+}
+
+fn run(command: &mut Command, input: &str) -> Output {
+	let mut child = command
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.expect("failed to run tal");
+	child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+	child.wait_with_output().expect("failed to run tal")
+}
+
+#[test]
+fn missing_applications_exit_with_not_found() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	let output = tal(&home, &["--dry-run", "Nope"]);
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("\"Nope\" does not exist"));
+	assert_eq!(tal(&home, &["--dry-run", "Vim"]).status.code(), Some(0));
+}
+
+#[test]
+fn every_name_is_tried_and_any_failure_fails() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	home.write("data/applications/broken.desktop", &entry("Broken", "broken \"unterminated"));
+	let names = home.write("names", "Nope\nVim\n");
+	let output = tal(&home, &["--dry-run", "--from-file", names.to_str().unwrap()]);
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stdout).ends_with("/vim.desktop\nvim\n"));
+	let names = home.write("names", "Broken\nNope\n");
+	assert_eq!(tal(&home, &["--dry-run", "--from-file", names.to_str().unwrap()]).status.code(), Some(2));
+}
+
+#[test]
+fn unreadable_sources_exit_with_their_own_code() {
+	let home = TempDir::new();
+	//A file where the applications directory belongs can't be listed
+	home.write("data/applications", "");
+	assert_eq!(tal(&home, &["--user"]).status.code(), Some(3));
+	assert_eq!(tal(&home, &["--system"]).status.code(), Some(0));
+}
+
+#[test]
+fn pick_lists_names_and_launches_the_line_read_back() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	home.write("data/applications/vlc.desktop", &entry("VLC", "vlc %U"));
+	let output = tal_with_input(&home, &["--pick", "--user", "--dry-run"], "  vim\n");
+	assert!(output.status.success());
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let lines: Vec<&str> = stdout.lines().collect();
+	assert_eq!(lines.len(), 4);
+	assert!(lines.contains(&"Vim") && lines.contains(&"VLC"));
+	assert_eq!(lines[3], "vim");
+
+	//An empty selection launches nothing
+	let output = tal_with_input(&home, &["--pick", "--dry-run"], "");
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+	assert_eq!(tal_with_input(&home, &["--pick", "--dry-run"], "v\n").status.code(), Some(1));
+}
+
+#[test]
+fn list_names_prints_each_lowercase_name_once() {
+	let home = TempDir::new();
+	home.write("data/applications/code.desktop", &entry("Visual Studio Code", "code"));
+	home.write("system/applications/code-url.desktop", &entry("Visual Studio Code", "code --url"));
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	let output = tal(&home, &["--list-names"]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stdout), "vim\nvisual studio code\n");
+}
+
+#[test]
+fn completion_scripts_complete_names_through_list_names() {
+	let home = TempDir::new();
+	for shell in ["bash", "zsh", "fish"] {
+		let output = tal(&home, &["completions", shell]);
+		assert!(output.status.success());
+		let script = String::from_utf8_lossy(&output.stdout);
+		assert!(script.contains("tal --list-names"), "{shell}");
+		assert!(script.contains("list-names") && script.contains("dry-run"), "{shell}");
+	}
+	assert!(!tal(&home, &["completions", "tcsh"]).status.success());
+}
+
+#[test]
+fn dry_run_prints_the_file_directory_and_quoted_commands() {
+	let home = TempDir::new();
+	let project = home.path().join("project");
+	std::fs::create_dir_all(&project).unwrap();
+	let file = home.write("data/applications/edit.desktop", &format!("[Desktop Entry]\nType=Application\nName=Edit\nExec=editor --title \"My Notes\" %F\nTerminal=true\nPath={}\n\n\
+		[Desktop Action new]\nName=New\nExec=editor --new\n", project.display()));
+	let output = tal(&home, &["--dry-run", "--terminal", "foot", "Edit", "it's.txt"]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("# {}\n# in {}\nfoot editor --title 'My Notes' 'it'\\''s.txt'\n", file.display(), project.display()));
+
+	let output = tal(&home, &["--dry-run", "--terminal", "foot", "--action", "new", "Edit"]);
+	assert!(String::from_utf8_lossy(&output.stdout).ends_with("\nfoot editor --new\n"));
+	assert_eq!(tal(&home, &["--dry-run", "--action", "gone", "Edit"]).status.code(), Some(1));
+
+	let json = tal(&home, &["--dry-run", "--json", "--terminal", "foot", "Edit"]);
+	assert!(String::from_utf8_lossy(&json.stdout).contains(&format!("\"file\":\"{}\"", file.display())));
+}
+
+#[test]
+fn exec_keeps_percent_signs_of_the_command() {
+	let home = TempDir::new();
+	let output = tal(&home, &["--dry-run", "--terminal", "xterm", "--exec", "date +%H \"%d %m\""]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stdout), "xterm -e date +%H '%d %m'\n");
+}
+
+#[test]
+fn file_hands_every_positional_to_the_entry() {
+	let home = TempDir::new();
+	let file = home.write("viewer.desktop", &entry("Viewer", "viewer %F"));
+	let output = tal(&home, &["--dry-run", "--file", file.to_str().unwrap(), "a.png", "b.png"]);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("# {}\nviewer a.png b.png\n", file.display()));
+}
+
+#[test]
+fn source_priority_has_to_name_every_source_once() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	let config_error = |priority: &str| {
+		home.write("config/tal/config.toml", &format!("source_priority = {priority}\n"));
+		let output = tal(&home, &["--user"]);
+		assert!(output.status.success());
+		String::from_utf8_lossy(&output.stderr).contains("source_priority must name each")
+	};
+	assert!(config_error(r#"["flatpak", "usr", "snap", "system"]"#));
+	assert!(config_error(r#"["flatpak", "user", "system"]"#));
+	assert!(config_error(r#"["flatpak", "user", "snap", "system", "user"]"#));
+	assert!(!config_error(r#"["flatpak", "user", "snap", "system"]"#));
+}
+
+#[test]
+fn autostart_leaves_out_hidden_and_disabled_entries() {
+	let home = TempDir::new();
+	home.write("config/autostart/hidden.desktop", &(entry("Hidden", "hidden") + "Hidden=true\n"));
+	home.write("config/autostart/disabled.desktop", &(entry("Disabled", "disabled") + "X-GNOME-Autostart-enabled=false\n"));
+	home.write("config/autostart/enabled.desktop", &(entry("Enabled", "enabled") + "X-GNOME-Autostart-enabled=true\n"));
+	assert_eq!(String::from_utf8_lossy(&tal(&home, &["--autostart"]).stdout), "Enabled\n");
+}
+
+#[test]
+fn autostart_follows_only_show_in() {
+	let home = TempDir::new();
+	home.write("sysconfig/autostart/gnome.desktop", &(entry("Gnome Panel", "gnome-panel") + "OnlyShowIn=GNOME;\n"));
+	home.write("sysconfig/autostart/kde.desktop", &(entry("Kde Panel", "kde-panel") + "OnlyShowIn=KDE;\n"));
+	assert_eq!(String::from_utf8_lossy(&tal_on(&home, "GNOME", &["--autostart"]).stdout), "Gnome Panel\n");
+	assert_eq!(String::from_utf8_lossy(&tal_on(&home, "KDE", &["--autostart"]).stdout), "Kde Panel\n");
+}
+
+#[test]
+fn user_autostart_entries_shadow_system_ones() {
+	let home = TempDir::new();
+	home.write("sysconfig/autostart/agent.desktop", &entry("System Agent", "agent"));
+	home.write("sysconfig/autostart/applet.desktop", &entry("Applet", "applet"));
+	//The user copy of a system entry turns it off for this user only
+	home.write("config/autostart/agent.desktop", &(entry("User Agent", "agent") + "Hidden=true\n"));
+	assert_eq!(String::from_utf8_lossy(&tal(&home, &["--autostart"]).stdout), "Applet\n");
+	home.write("config/autostart/agent.desktop", &entry("User Agent", "agent --user"));
+	assert_eq!(String::from_utf8_lossy(&tal(&home, &["--autostart"]).stdout), "User Agent\nApplet\n");
+}
+
+#[test]
+fn dry_run_keeps_quoted_spaces_and_escaped_quotes_in_one_argument() {
+	let home = TempDir::new();
+	let file = home.write("data/applications/app.desktop", &entry("App", r#""/opt/My App/app" --title "say \"hi\"" %F"#));
+	let output = tal(&home, &["--dry-run", "App", "a b.txt"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("# {}\n'/opt/My App/app' --title 'say \"hi\"' 'a b.txt'\n", file.display()));
+}
+
+#[test]
+fn empty_exec_has_nothing_to_run_even_with_arguments() {
+	let home = TempDir::new();
+	home.write("data/applications/empty.desktop", &entry("Empty", ""));
+	for args in [&["--dry-run", "Empty"][..], &["--dry-run", "Empty", "a.txt"]] {
+		let output = tal(&home, args);
+		assert_eq!(output.status.code(), Some(2));
+		assert!(output.stdout.is_empty());
+		assert!(String::from_utf8_lossy(&output.stderr).contains("\"Empty\" has nothing to execute"));
+	}
+}
+
+#[test]
+fn locale_overrides_the_environment_for_localized_names() {
+	let home = TempDir::new();
+	home.write("data/applications/files.desktop", &(entry("Files", "files") + "Name[de]=Dateien\nName[fr]=Fichiers\n"));
+	let names = |args: &[&str]| run(tal_command(&home, args).env("LC_ALL", "fr_FR.UTF-8").env_remove("XDG_CURRENT_DESKTOP"), "").stdout;
+	assert_eq!(String::from_utf8_lossy(&names(&["--list-names"])), "fichiers\n");
+	assert_eq!(String::from_utf8_lossy(&names(&["--locale", "de_DE.UTF-8", "--list-names"])), "dateien\n");
+	assert_eq!(names(&["--locale", "de_DE.UTF-8", "--dry-run", "Dateien"]), names(&["--dry-run", "Fichiers"]));
+}
+
+#[test]
+fn locale_is_exported_to_the_launched_application() {
+	let home = TempDir::new();
+	home.write("data/applications/env.desktop", &entry("Env", r#"sh -c "echo \$LC_ALL \$LANG""#));
+	let output = run(tal_command(&home, &["--locale", "de_DE.UTF-8", "--output", "Env"]).env("LC_ALL", "fr_FR.UTF-8").env("LANG", "fr_FR.UTF-8"), "");
+	//The launch message races the application's own output
+	assert!(String::from_utf8_lossy(&output.stdout).lines().any(|line| line == "de_DE.UTF-8 de_DE.UTF-8"), "{output:?}");
+	assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn jobs_bound_the_reads_without_changing_the_listing() {
+	let home = TempDir::new();
+	for i in 0..50 {
+		home.write(&format!("system/applications/app-{i}.desktop"), &entry(&format!("App {i}"), "app"));
+		if i % 5 == 0 {
+			home.write(&format!("data/applications/app-{i}.desktop"), &entry(&format!("App {i} (user)"), "app --user"));
+		}
+	}
+	let serial = tal(&home, &["--jobs", "1", "--list-names"]);
+	assert_eq!(String::from_utf8_lossy(&serial.stdout).lines().count(), 50);
+	assert_eq!(tal(&home, &["--jobs", "4", "--list-names"]).stdout, serial.stdout);
+	assert_eq!(tal(&home, &["--jobs", "0", "--list-names"]).status.code(), Some(2));
+
+	home.write("config/tal/config.toml", "jobs = 0\n");
+	let output = tal(&home, &["--list-names"]);
+	assert!(String::from_utf8_lossy(&output.stderr).contains("a positive integer"));
+	assert_eq!(output.stdout, serial.stdout);
+}
+
+#[test]
+fn tree_groups_entries_by_main_category_with_the_rest_under_other() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &(entry("Vim", "vim %F") + "Categories=Utility;TextEditor;\n"));
+	home.write("data/applications/gimp.desktop", &(entry("Gimp", "gimp %U") + "Categories=Graphics;2DGraphics;\n"));
+	home.write("data/applications/calc.desktop", &(entry("Calc", "calc") + "Categories=Utility;Calculator;\n"));
+	home.write("data/applications/thing.desktop", &entry("Thing", "thing"));
+	home.write("data/applications/odd.desktop", &(entry("Odd", "odd") + "Categories=X-Vendor;\n"));
+	let output = tal(&home, &["--tree"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout), "Graphics\n\tGimp\nUtility\n\tCalc\n\tVim\nOther\n\tOdd\n\tThing\n");
+	//Both JSON forms of the tree hold the same entry objects as --json
+	let output = tal(&home, &["--tree", "--json"]);
+	assert_eq!(output.stdout, tal(&home, &["--print-tree-json"]).stdout);
+	let json = String::from_utf8_lossy(&output.stdout);
+	assert!(json.starts_with("{\"Graphics\":[{\"id\":\"gimp.desktop\",\"name\":\"Gimp\""), "{json}");
+	assert_eq!(json.matches("\"actions\":[]").count(), 5);
+}
+
+#[test]
+fn dry_run_json_describes_every_command() {
+	let home = TempDir::new();
+	let project = home.path().join("project");
+	std::fs::create_dir_all(&project).unwrap();
+	let viewer = home.write("data/applications/viewer.desktop", &format!("{}Path={}\n", entry("Viewer", "viewer %f"), project.display()));
+	let edit = home.write("data/applications/edit.desktop", &format!("{}Terminal=true\nPath={}\n", entry("Edit", "editor %F"), project.display()));
+	let output = tal(&home, &["--dry-run", "--json", "Viewer", "a.png", "b.png"]);
+	let command = |arg: &str| format!("{{\"name\":\"Viewer\",\"program\":\"viewer\",\"args\":[\"{arg}\"],\"terminal\":false,\"cwd\":\"{}\",\"file\":\"{}\"}}", project.display(), viewer.display());
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}\n{}\n", command("a.png"), command("b.png")));
+	let output = tal(&home, &["--dry-run", "--json", "--terminal", "foot", "Edit", "a.txt"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{{\"name\":\"Edit\",\"program\":\"foot\",\"args\":[\"editor\",\"a.txt\"],\"terminal\":true,\"cwd\":\"{}\",\"file\":\"{}\"}}\n", project.display(), edit.display()));
+}
+
+#[test]
+fn skip_terminal_apps_only_skips_them_without_a_terminal() {
+	let home = TempDir::new();
+	home.write("data/applications/vim.desktop", &(entry("Vim", "vim %F") + "Terminal=true\n"));
+	home.write("data/applications/gimp.desktop", &entry("Gimp", "gimp %U"));
+	let names = home.write("names", "Vim\nGimp\n");
+	let names = names.to_str().unwrap();
+	let empty_path = home.path().join("bin");
+	std::fs::create_dir_all(&empty_path).unwrap();
+	//An empty PATH and no $TERMINAL leave nothing to detect
+	let batch = |args: &[&str]| run(tal_command(&home, args).env("PATH", &empty_path).env_remove("TERMINAL").env_remove("XDG_CURRENT_DESKTOP"), "");
+	let output = batch(&["--dry-run", "--skip-terminal-apps", "--from-file", names]);
+	assert_eq!(output.status.code(), Some(0));
+	assert!(output.stderr.is_empty());
+	assert!(String::from_utf8_lossy(&output.stdout).ends_with("/gimp.desktop\ngimp\n"));
+	let output = batch(&["--dry-run", "--from-file", names]);
+	assert_eq!(output.status.code(), Some(2));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("\"Vim\" failed to start because it doesn't know what terminal to use"));
+	let output = batch(&["--dry-run", "--skip-terminal-apps", "--terminal", "foot", "--from-file", names]);
+	assert!(String::from_utf8_lossy(&output.stdout).contains("\nfoot vim\n"));
+}
+
+#[test]
+fn stagger_spaces_out_batch_launches_but_not_dry_runs() {
+	let home = TempDir::new();
+	home.write("data/applications/one.desktop", &entry("One", "true"));
+	home.write("data/applications/two.desktop", &entry("Two", "true"));
+	home.write("data/applications/three.desktop", &entry("Three", "true"));
+	let names = home.write("names", "One\nTwo\nThree\n");
+	let timed = |args: &[&str]| {
+		let start = Instant::now();
+		assert!(tal(&home, args).status.success());
+		start.elapsed()
+	};
+	//Three launches have two gaps between them
+	assert!(timed(&["--stagger", "300", "--from-file", names.to_str().unwrap()]) >= Duration::from_millis(600));
+	assert!(timed(&["--dry-run", "--stagger", "5000", "--from-file", names.to_str().unwrap()]) < Duration::from_secs(5));
+}
+
+#[test]
+fn dry_run_expands_entry_codes_and_escaped_percent_signs() {
+	let home = TempDir::new();
+	let file = home.write("data/applications/player.desktop", &(entry("My Player", "player %i --title %c --desktop-file %k --volume 50%% %u") + "Icon=player-icon\n"));
+	let output = tal(&home, &["--dry-run", "My Player", "song.ogg"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout), format!("# {0}\nplayer --icon player-icon --title 'My Player' --desktop-file {0} --volume 50% song.ogg\n", file.display()));
+	//Without an Icon `%i` leaves nothing behind
+	home.write("data/applications/player.desktop", &entry("My Player", "player %i --volume 50%%"));
+	let output = tal(&home, &["--dry-run", "My Player"]);
+	assert!(String::from_utf8_lossy(&output.stdout).ends_with("\nplayer --volume 50%\n"));
+}
+
+#[test]
+fn json_listings_compose_with_sources_and_search() {
+	let home = TempDir::new();
+	let user_vim = home.write("data/applications/vim.desktop", &entry("Vim", "vim --user"));
+	home.write("data/applications/gimp.desktop", &entry("Gimp", "gimp"));
+	let system_vi = home.write("system/applications/vi.desktop", &entry("Vi", "vi"));
+	let ids = |args: &[&str]| {
+		let stdout = String::from_utf8_lossy(&tal(&home, args).stdout).into_owned();
+		assert!(stdout.starts_with('[') && stdout.ends_with("]\n"), "{stdout}");
+		stdout.split("\"file\":\"").skip(1).map(|rest| rest.split('"').next().unwrap().to_owned()).collect::<Vec<_>>()
+	};
+	assert_eq!(ids(&["--json", "--user", "--search", "vi"]), [user_vim.display().to_string()]);
+	assert_eq!(ids(&["--json", "--system", "--search", "vi"]), [system_vi.display().to_string()]);
+	assert_eq!(ids(&["--json", "--user", "--search", "nothing"]), Vec::<String>::new());
+
+	//No flatpak directory at all is an empty listing, not an error
+	let output = tal(&home, &["--json", "--flatpak"]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stdout), "[]\n");
+}
+
+#[test]
+fn launches_start_in_their_own_session_unless_kept_attached() {
+	let home = TempDir::new();
+	home.write("data/applications/ids.desktop", &entry("Ids", r#"sh -c "read -r _ _ _ _ _ sid _ < /proc/\$\$/stat; echo \$\$ \$sid > \"\$0\"" %f"#));
+	let ids = |args: &[&str], out: &str| {
+		let out = home.path().join(out);
+		assert!(tal(&home, &[args, &["Ids", out.to_str().unwrap()]].concat()).status.success());
+		//Detached launches aren't waited on
+		let start = Instant::now();
+		let mut written = String::new();
+		while !written.ends_with('\n') && start.elapsed() < Duration::from_secs(5) {
+			std::thread::sleep(Duration::from_millis(20));
+			written = std::fs::read_to_string(&out).unwrap_or_default();
+		}
+		let ids: Vec<u32> = written.split_whitespace().map(|id| id.parse().unwrap()).collect();
+		(ids[0], ids[1])
+	};
+	let (pid, session) = ids(&[], "detached");
+	assert_eq!(session, pid);
+	let (pid, session) = ids(&["--no-detach"], "background");
+	assert_ne!(session, pid);
+}
+
+#[test]
+fn open_with_follows_added_and_removed_associations() {
+	let home = TempDir::new();
+	home.write("data/mime/globs2", "50:text/plain:*.txt\n");
+	home.write("data/applications/gedit.desktop", &(entry("Gedit", "gedit %U") + "MimeType=text/plain;\n"));
+	home.write("data/applications/kate.desktop", &(entry("Kate", "kate %U") + "MimeType=text/plain;\n"));
+	home.write("data/applications/vim.desktop", &entry("Vim", "vim %F"));
+	let notes = home.write("notes.txt", "");
+	let handlers = || {
+		let mut names: Vec<String> = String::from_utf8_lossy(&tal(&home, &["--open-with", notes.to_str().unwrap()]).stdout).lines().map(str::to_owned).collect();
+		names.sort();
+		names
+	};
+	assert_eq!(handlers(), ["Gedit", "Kate"]);
+	home.write("config/mimeapps.list", "[Added Associations]\ntext/plain=vim.desktop;\n\n[Removed Associations]\ntext/plain=kate.desktop;\n");
+	assert_eq!(handlers(), ["Gedit", "Vim"]);
+}
+
+#[test]
+fn select_matches_the_choice_like_names_given_on_the_command_line() {
+	let home = TempDir::new();
+	home.write("data/mime/globs2", "50:text/plain:*.txt\n");
+	home.write("data/applications/gedit.desktop", &(entry("Gedit", "gedit %U") + "MimeType=text/plain;\n"));
+	home.write("data/applications/kate.desktop", &(entry("Kate", "kate %U") + "MimeType=text/plain;\n"));
+	home.write("data/applications/kwrite.desktop", &(entry("KWrite", "kwrite %U") + "MimeType=text/plain;\n"));
+	home.write("data/applications/editor.desktop", &(entry("Éditeur", "editeur %U") + "MimeType=text/plain;\n"));
+	let notes = home.write("notes.txt", "");
+	let select = |args: &[&str], choice: &str| tal_with_input(&home, &[&["--dry-run", "--select", "--open-with", notes.to_str().unwrap()], args].concat(), choice);
+	assert!(String::from_utf8_lossy(&select(&[], "ged\n").stdout).ends_with(&format!("\ngedit {}\n", notes.display())));
+	assert!(String::from_utf8_lossy(&select(&["--fold-diacritics"], "editeur\n").stdout).ends_with(&format!("\nediteur {}\n", notes.display())));
+	let output = select(&[], "k\n");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("is ambiguous"));
+}
+
+#[test]
+fn launches_at_the_same_time_leave_a_whole_history() {
+	let home = TempDir::new();
+	home.write("data/applications/quick.desktop", &entry("Quick", "true"));
+	let launches: Vec<_> = (0..8).map(|_| tal_command(&home, &["Quick"]).stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap()).collect();
+	for mut launch in launches {
+		assert!(launch.wait().unwrap().success());
+	}
+	let history = std::fs::read_to_string(home.path().join("data/tal/history")).unwrap();
+	//Saves racing each other may drop counts, but never mix their lines
+	let fields: Vec<&str> = history.trim_end().split('\t').collect();
+	assert_eq!(fields[2..], ["quick.desktop", "Quick"]);
+	assert!((1..=8).contains(&fields[1].parse::<u32>().unwrap()));
+	let leftovers = std::fs::read_dir(home.path().join("data/tal")).unwrap().filter(|file| file.as_ref().unwrap().path() != home.path().join("data/tal/history")).count();
+	assert_eq!(leftovers, 0);
+}
@@ -0,0 +1,39 @@
+//Shared by every integration test crate, not all of them use every helper
+#![allow(dead_code)]
+
+use std::{env, fs, path::{Path, PathBuf}, process, sync::atomic::{AtomicUsize, Ordering}};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch directory under the system temp dir that is removed on drop
+pub struct TempDir(PathBuf);
+impl TempDir {
+	pub fn new() -> Self {
+		let dir = env::temp_dir().join(format!("tal-test-{}-{}", process::id(), NEXT_DIR.fetch_add(1, Ordering::Relaxed)));
+		fs::create_dir_all(&dir).expect("failed to create the temp dir");
+		Self(dir)
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.0
+	}
+
+	/// Writes `contents` to `name`, creating parent directories as needed
+	pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+		let path = self.0.join(name);
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).expect("failed to create the entry dir");
+		}
+		fs::write(&path, contents).expect("failed to write the entry");
+		path
+	}
+}
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
+
+pub fn entry(name: &str, exec: &str) -> String {
+	format!("[Desktop Entry]\nType=Application\nName={name}\nExec={exec}\n")
+}
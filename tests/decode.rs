@@ -0,0 +1,208 @@
+use std::{env, fs, path::Path, process};
+use tal::{apps::{ApplicationEntry, DecodeError, Ini}, locale::Locale};
+
+fn decode(contents: &str) -> Result<Ini, DecodeError> {
+	ApplicationEntry::new(contents.to_owned()).decode(None)
+}
+
+#[test]
+fn whitespace_only_file_is_not_an_entry() {
+	assert!(matches!(decode("  \n\t\n"), Err(DecodeError::NotDesktopEntry)));
+	assert!(matches!(decode(""), Err(DecodeError::NotDesktopEntry)));
+}
+
+#[test]
+fn bom_only_file_is_not_an_entry() {
+	assert!(matches!(decode("\u{feff}"), Err(DecodeError::NotDesktopEntry)));
+	assert!(matches!(decode("\u{feff}\n\n"), Err(DecodeError::NotDesktopEntry)));
+}
+
+#[test]
+fn bom_is_stripped_before_the_group_header() {
+	let ini = decode("\u{feff}[Desktop Entry]\nName=Vim\nExec=vim %F\n").unwrap();
+	assert_eq!(ini.name, "Vim");
+}
+
+#[test]
+fn leading_blank_lines_and_comments_come_before_the_group_header() {
+	let ini = decode("\u{feff}\r\n  \r\n# Generated\r\n\t\r\n[Desktop Entry]\r\nName=Vim\r\nExec=vim %F\r\n").unwrap();
+	assert_eq!((ini.name.as_str(), ini.exec.as_str()), ("Vim", "vim %F"));
+	assert!(matches!(decode("\n\nName=Vim\n[Desktop Entry]\nName=Vim\nExec=vim\n"), Err(DecodeError::NotDesktopEntry)));
+	assert!(matches!(decode("\n[Desktop Action new]\nName=New\n"), Err(DecodeError::NotDesktopEntry)));
+}
+
+#[test]
+fn missing_required_keys_are_reported() {
+	assert!(matches!(decode("[Desktop Entry]\nExec=vim\n"), Err(DecodeError::MissingName)));
+	assert!(matches!(decode("[Desktop Entry]\nName=Vim\n"), Err(DecodeError::MissingExec)));
+}
+
+#[test]
+fn startup_wm_class_matches_case_insensitively() {
+	let firefox = decode("[Desktop Entry]\nName=Firefox\nExec=firefox %u\nStartupWMClass=Firefox\n").unwrap();
+	assert_eq!(firefox.startup_wm_class.as_deref(), Some("Firefox"));
+	assert!(firefox.matches_wm_class("firefox"));
+	assert!(!firefox.matches_wm_class("firefox-esr"));
+}
+
+#[test]
+fn every_line_ending_style_decodes() {
+	for newline in ["\n", "\r\n", "\r"] {
+		let contents = ["[Desktop Entry]", "Name=Vim", "Exec=vim %F", "Terminal=true", ""].join(newline);
+		let vim = decode(&contents).unwrap_or_else(|e| panic!("{newline:?} line endings failed: {e}"));
+		assert_eq!((vim.name.as_str(), vim.exec.as_str(), vim.terminal), ("Vim", "vim %F", true));
+	}
+}
+
+#[test]
+fn getters_return_resolved_values() {
+	let contents = "[Desktop Entry]\nName=Files\nName[de]=Dateien\nExec=nautilus --new-window \"%U\"\nIcon=/nonexistent/files.png\n";
+	let files = ApplicationEntry::new(contents.to_owned()).decode(Locale::parse("de_DE.UTF-8").as_ref()).unwrap();
+	assert_eq!(files.resolved_name(), "Dateien");
+	assert_eq!(files.exec_tokens(), ["nautilus", "--new-window", "%U"]);
+	assert_eq!(files.icon_path(48), None);
+
+	let icon = env::temp_dir().join(format!("tal-test-icon-{}.png", process::id()));
+	fs::write(&icon, "").unwrap();
+	let with_icon = Ini { id: "org.gnome.Nautilus.desktop".to_owned(), icon: Some(icon.to_string_lossy().into_owned()), ..files };
+	assert_eq!(with_icon.id(), "org.gnome.Nautilus.desktop");
+	assert_eq!(with_icon.icon_path(48), Some(icon.clone()));
+	fs::remove_file(icon).unwrap();
+}
+
+#[test]
+fn dbus_activatable_entries_need_no_exec() {
+	let app = decode("[Desktop Entry]\nName=Maps\nDBusActivatable=true\n").unwrap();
+	assert!(app.dbus_activatable);
+	assert_eq!(app.exec, "");
+	assert!(matches!(decode("[Desktop Entry]\nName=Maps\nDBusActivatable=false\n"), Err(DecodeError::MissingExec)));
+}
+
+#[test]
+fn tal_env_is_a_list_of_pairs() {
+	let app = decode("[Desktop Entry]\nName=Firefox\nExec=firefox\nX-Tal-Env=MOZ_ENABLE_WAYLAND=1;GDK_SCALE=;=nameless;no-value;OPTS=a=b;\n").unwrap();
+	let env = |pairs: &[(&str, &str)]| pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<_>>();
+	assert_eq!(app.env, env(&[("MOZ_ENABLE_WAYLAND", "1"), ("GDK_SCALE", ""), ("OPTS", "a=b")]));
+	assert_eq!(decode(&app.to_desktop_string()).unwrap().env, app.env);
+}
+
+#[test]
+fn action_names_follow_the_locale() {
+	let contents = "[Desktop Entry]\nName=Firefox\nName[de]=Firefox (de)\nExec=firefox %u\nActions=private;\n\n\
+		[Desktop Action private]\nName[de]=Neues privates Fenster\nName=New Private Window\nName[de_DE]=Privates Fenster\nName[fr]=Fenêtre privée\nExec=firefox --private-window\n";
+	let decoded = |locale: &str| ApplicationEntry::new(contents.to_owned()).decode(Locale::parse(locale).as_ref()).unwrap();
+	let action_name = |locale: &str| decoded(locale).actions["private"].name.clone().unwrap();
+
+	assert_eq!(action_name("de_DE.UTF-8@euro"), "Privates Fenster");
+	assert_eq!(action_name("de_AT.UTF-8"), "Neues privates Fenster");
+	assert_eq!(action_name("fr_CA"), "Fenêtre privée");
+	assert_eq!(action_name("ja_JP.UTF-8"), "New Private Window");
+	assert_eq!(decoded("de_AT").name, "Firefox (de)");
+	assert_eq!(decode(contents).unwrap().actions["private"].name.as_deref(), Some("New Private Window"));
+}
+
+#[test]
+fn keys_of_unrelated_groups_are_ignored() {
+	let app = decode("[Desktop Entry]\nName=Files\nExec=nautilus --new-window %U\nActions=new;\n\n\
+		[X-GNOME-FullName]\nName=GNOME Files\nExec=gnome-files\n\n\
+		[Desktop Action new]\nName=New Window\nExec=nautilus --new-window\n\n\
+		[Install]\nExec=installer\n\n\
+		[Desktop Entry]\nName=Second\nTerminal=true\n").unwrap();
+	assert_eq!(app.name, "Files");
+	assert_eq!(app.exec, "nautilus --new-window %U");
+	assert!(!app.terminal);
+	assert_eq!(app.actions.len(), 1);
+	assert_eq!(app.actions["new"].exec.as_deref(), Some("nautilus --new-window"));
+}
+
+#[test]
+fn only_header_lines_start_a_group() {
+	let app = decode("[Desktop Entry]\nName=Editor\nComment=Action Editor]\nExec=editor\n").unwrap();
+	assert_eq!(app.comment.as_deref(), Some("Action Editor]"));
+	assert_eq!(app.exec, "editor");
+	assert!(app.actions.is_empty());
+}
+
+#[test]
+fn generic_name_and_comment_follow_the_locale() {
+	let contents = "[Desktop Entry]\nName=Files\nName[de]=Dateien\nGenericName=File Manager\nGenericName[de]=Dateiverwaltung\n\
+		GenericName[de_CH]=Dateimanager\nComment=Access and organize files\nComment[fr]=Accéder aux fichiers\nExec=nautilus\n";
+	let decoded = |locale: &str| ApplicationEntry::new(contents.to_owned()).decode(Locale::parse(locale).as_ref()).unwrap();
+
+	let de = decoded("de_DE.UTF-8");
+	assert_eq!((de.name.as_str(), de.untranslated_name.as_deref()), ("Dateien", Some("Files")));
+	assert_eq!(de.generic_name.as_deref(), Some("Dateiverwaltung"));
+	assert_eq!(de.comment.as_deref(), Some("Access and organize files"));
+	assert_eq!(decoded("de_CH").generic_name.as_deref(), Some("Dateimanager"));
+	assert_eq!(decoded("de").generic_name.as_deref(), Some("Dateiverwaltung"));
+	assert_eq!(decoded("fr_FR").comment.as_deref(), Some("Accéder aux fichiers"));
+	let missing = decoded("ja_JP.UTF-8");
+	assert_eq!((missing.name.as_str(), missing.generic_name.as_deref()), ("Files", Some("File Manager")));
+}
+
+#[test]
+fn path_is_kept_unless_empty() {
+	let app = decode("[Desktop Entry]\nName=Game\nExec=./game\nPath=/opt/game\n").unwrap();
+	assert_eq!(app.path.as_deref(), Some(Path::new("/opt/game")));
+	assert_eq!(decode(&app.to_desktop_string()).unwrap().path, app.path);
+	assert_eq!(decode("[Desktop Entry]\nName=Game\nExec=./game\nPath=\n").unwrap().path, None);
+}
+
+#[test]
+fn actions_start_in_their_own_path_or_the_entry_path() {
+	let app = decode("[Desktop Entry]\nName=Game\nExec=./game\nPath=/opt/game\nActions=editor;server;\n\n\
+		[Desktop Action editor]\nName=Editor\nExec=./editor\nPath=/opt/game/tools\n\n\
+		[Desktop Action server]\nName=Server\nExec=./server\n").unwrap();
+	assert_eq!(app.with_action("editor").unwrap().path.as_deref(), Some(Path::new("/opt/game/tools")));
+	assert_eq!(app.with_action("server").unwrap().path.as_deref(), Some(Path::new("/opt/game")));
+	assert_eq!(decode(&app.to_desktop_string()).unwrap().actions, app.actions);
+	assert!(app.to_string().contains("\n\t- Path=/opt/game"));
+}
+
+#[test]
+fn group_headers_may_end_in_whitespace() {
+	let app = decode("[Desktop Entry] \nName=Files\nExec=nautilus\nActions=new;\n\n[Desktop Action new]\t\nName=New Window\nExec=nautilus --new-window\n").unwrap();
+	assert_eq!(app.name, "Files");
+	assert_eq!(app.actions["new"].exec.as_deref(), Some("nautilus --new-window"));
+}
+
+#[test]
+fn headers_with_extra_brackets_are_unknown_groups() {
+	let app = decode("[Desktop Entry]\nName=Files\nExec=nautilus\n\n[Desktop Action new][junk]\nName=Junk\nExec=junk\n\n\
+		[Desktop Action]\nExec=nameless\n\n[Desktop Action new]\nName=New Window\nExec=nautilus --new-window\n").unwrap();
+	assert_eq!(app.exec, "nautilus");
+	assert_eq!(app.actions.keys().collect::<Vec<_>>(), ["new"]);
+	assert_eq!(app.actions["new"].name.as_deref(), Some("New Window"));
+}
+
+#[test]
+fn parse_desktop_entry_is_decode_without_a_locale() {
+	let contents = "[Desktop Entry]\nName=Files\nName[de]=Dateien\nExec=nautilus\n";
+	let files = tal::parse_desktop_entry(contents).unwrap();
+	assert_eq!(files.name, "Files");
+	assert_eq!(files, decode(contents).unwrap());
+	assert!(matches!(tal::parse_desktop_entry("Name=Files\n"), Err(tal::DecodeError::NotDesktopEntry)));
+}
+
+#[test]
+fn string_values_are_unescaped() {
+	let app = decode("[Desktop Entry]\nName=Foo\\sBar\nGenericName=Tab\\there\nComment=Line\\none\\rtwo\nExec=tool C:\\\\dir\nIcon=trailing\\\nActions=new;\n\n\
+		[Desktop Action new]\nName=New\\sWindow\nExec=tool\\s--new\n").unwrap();
+	assert_eq!(app.name, "Foo Bar");
+	assert_eq!(app.generic_name.as_deref(), Some("Tab\there"));
+	assert_eq!(app.comment.as_deref(), Some("Line\none\rtwo"));
+	assert_eq!(app.exec, "tool C:\\dir");
+	assert_eq!(app.icon.as_deref(), Some("trailing\\"));
+	assert_eq!(app.actions["new"].name.as_deref(), Some("New Window"));
+	assert_eq!(app.actions["new"].exec.as_deref(), Some("tool --new"));
+	assert_eq!(decode(&app.to_desktop_string()).unwrap(), app);
+	assert_eq!(decode(&app.to_desktop_string()).unwrap().comment, app.comment);
+	assert!(app.to_desktop_string().contains("Comment=Line\\none\\rtwo\n"));
+}
+
+#[test]
+fn general_escapes_apply_before_exec_quoting() {
+	let app = decode("[Desktop Entry]\nName=Say\nExec=say \"a\\\\\"b\" \"back\\\\\\\\slash\" \"cost \\\\$5\"\n").unwrap();
+	assert_eq!(app.exec, r#"say "a\"b" "back\\slash" "cost \$5""#);
+	assert_eq!(app.exec_tokens(), ["say", "a\"b", "back\\slash", "cost $5"]);
+}
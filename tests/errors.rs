@@ -0,0 +1,16 @@
+use std::{io, path::PathBuf};
+use tal::apps::{ReadError, RunError, Source};
+
+#[test]
+fn errors_serialize_with_their_kind() {
+	assert_eq!(RunError::NotFound("foo".to_owned()).to_json().to_string(), r#"{"error":"NotFound","name":"foo"}"#);
+	assert_eq!(
+		RunError::Read(ReadError::NotFound(PathBuf::from("/nope"))).to_json().to_string(),
+		r#"{"error":"DirectoryNotFound","path":"/nope"}"#
+	);
+	let denied = ReadError::Io(Source::System, PathBuf::from("/usr/share/applications"), io::Error::other("denied"));
+	assert_eq!(
+		RunError::Read(denied).to_json().to_string(),
+		r#"{"error":"ReadFailed","source":"system","path":"/usr/share/applications","message":"denied"}"#
+	);
+}
@@ -0,0 +1,427 @@
+mod common;
+
+use std::{env, fs, os::unix::fs::PermissionsExt, path::Path};
+use common::TempDir;
+use tal::{apps::{Ini, RunError, Spawn, SpawnMode, parse_desktop_entry}, exec::{IoClass, UnterminatedQuote, dir_args, exec_program, exec_split, exec_tokenize, expand_entry_codes, expand_field_codes, expand_invocations, file_uri, glob_match, local_path, shell_quote_program, shell_wrap, which}, terminal::{self, Origin}};
+
+fn argv(exec: &str, args: &[&str]) -> Vec<String> {
+	let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+	expand_field_codes(exec_split(exec), &args)
+}
+
+#[test]
+fn interpreter_keeps_script_before_files() {
+	assert_eq!(
+		argv("/usr/bin/python3 /opt/app/main.py %F", &["a.txt", "b.txt"]),
+		["/usr/bin/python3", "/opt/app/main.py", "a.txt", "b.txt"]
+	);
+}
+
+#[test]
+fn files_expand_at_field_code_position() {
+	assert_eq!(
+		argv("/usr/bin/python3 /opt/app/main.py %F --verbose", &["a.txt", "b.txt"]),
+		["/usr/bin/python3", "/opt/app/main.py", "a.txt", "b.txt", "--verbose"]
+	);
+}
+
+#[test]
+fn field_codes_without_args_are_dropped() {
+	assert_eq!(argv("/usr/bin/python3 /opt/app/main.py %F %i %%", &[]), ["/usr/bin/python3", "/opt/app/main.py", "%"]);
+}
+
+fn invocations(exec: &str, args: &[&str]) -> Vec<Vec<String>> {
+	let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+	expand_invocations(exec_split(exec), &args)
+}
+
+#[test]
+fn single_form_codes_launch_once_per_file() {
+	assert_eq!(invocations("mpv %f", &["a.mkv", "b.mkv"]), [["mpv", "a.mkv"], ["mpv", "b.mkv"]]);
+	assert_eq!(invocations("browser --url=%u", &["a.org", "b.org"]), [["browser", "--url=a.org"], ["browser", "--url=b.org"]]);
+}
+
+#[test]
+fn list_form_codes_launch_once() {
+	assert_eq!(invocations("mpv %F", &["a.mkv", "b.mkv"]), [["mpv", "a.mkv", "b.mkv"]]);
+	assert_eq!(invocations("mpv %f", &["a.mkv"]), [["mpv", "a.mkv"]]);
+}
+
+#[test]
+fn shell_wrap_quotes_the_command_line() {
+	let argv = vec!["mpv".to_owned(), "my video.mkv".to_owned(), "it's".to_owned(), "--fs".to_owned()];
+	assert_eq!(shell_wrap("/bin/zsh", argv), ["/bin/zsh", "-lc", r#"mpv 'my video.mkv' 'it'\''s' --fs"#]);
+	//A program with a `=` has to be quoted or the shell reads it as an assignment, later words don't
+	let argv = vec!["FOO=bar".to_owned(), "--opt=1".to_owned()];
+	assert_eq!(shell_wrap("sh", argv), ["sh", "-lc", "'FOO=bar' --opt=1"]);
+	assert_eq!(shell_quote_program("FOO=bar"), "'FOO=bar'");
+	assert_eq!(shell_quote_program("/usr/bin/app"), "/usr/bin/app");
+	//Unquoted, `FOO=bar true` would succeed as an assignment followed by `true`
+	let status = std::process::Command::new("sh").args(&shell_wrap("sh", vec!["FOO=bar".to_owned(), "true".to_owned()])[1..]).stderr(std::process::Stdio::null()).status().unwrap();
+	assert!(!status.success());
+}
+
+#[test]
+fn shell_wrap_applies_before_the_terminal() {
+	let app = Ini { name: "Htop".to_owned(), exec: "htop --tree".to_owned(), terminal: true, ..Default::default() };
+	let spawn = Spawn::new("Htop".to_owned(), Some("xterm".to_owned())).with_shell(Some("/bin/bash".to_owned()));
+	let resolved = spawn.resolve(&app).unwrap();
+	assert_eq!(resolved[0].program, "xterm");
+	assert_eq!(resolved[0].args, ["-e", "/bin/bash", "-lc", "htop --tree"]);
+}
+
+#[test]
+fn guessed_terminal_tools_open_in_a_terminal() {
+	let tools = Some(vec!["htop".to_owned()]);
+	let spawn = Spawn::new("Htop".to_owned(), Some("xterm".to_owned())).with_terminal_tools(tools);
+	let htop = Ini { name: "Htop".to_owned(), exec: "env TERM=xterm-256color /usr/bin/htop".to_owned(), ..Default::default() };
+	let resolved = spawn.resolve(&htop).unwrap();
+	assert_eq!(resolved[0].program, "xterm");
+	assert!(resolved[0].terminal);
+
+	let firefox = Ini { name: "Firefox".to_owned(), exec: "firefox %u".to_owned(), ..Default::default() };
+	assert_eq!(spawn.resolve(&firefox).unwrap()[0].program, "firefox");
+}
+
+#[test]
+fn glob_matches_wildcards() {
+	assert!(glob_match("*.jpg", "beach.jpg"));
+	assert!(glob_match("img_??.png", "img_01.png"));
+	assert!(glob_match("*a*b", "xaxxab"));
+	assert!(!glob_match("*.jpg", "beach.jpeg"));
+	assert!(!glob_match("img_?.png", "img_01.png"));
+}
+
+#[test]
+fn dir_args_are_sorted_and_filtered() {
+	let photos = TempDir::new();
+	photos.write("b.jpg", "");
+	photos.write("a.jpg", "");
+	photos.write("notes.txt", "");
+	photos.write("nested/c.jpg", "");
+	let path = |name: &str| photos.path().join(name).to_string_lossy().into_owned();
+	assert_eq!(dir_args(photos.path(), Some("*.jpg")).unwrap(), [path("a.jpg"), path("b.jpg")]);
+	assert_eq!(dir_args(photos.path(), None).unwrap().len(), 3);
+}
+
+#[test]
+fn program_path_resolves_absolute_programs() {
+	let bin = TempDir::new();
+	let program = bin.write("tool", "#!/bin/sh\n");
+	let app = Ini { exec: format!("{} --flag %f", program.display()), ..Default::default() };
+	assert_eq!(app.program_path(), Some(program.canonicalize().unwrap()));
+
+	let missing = Ini { exec: "/nonexistent/tool".to_owned(), ..Default::default() };
+	assert_eq!(missing.program_path(), None);
+}
+
+const FLATPAK_FIREFOX: &str = "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox --file-forwarding org.mozilla.firefox @@u %u @@";
+
+#[test]
+fn flatpak_forwarding_markers_wrap_files() {
+	let run = ["/usr/bin/flatpak", "run", "--branch=stable", "--arch=x86_64", "--command=firefox", "--file-forwarding", "org.mozilla.firefox"];
+	let with_file = expand_invocations(exec_split(FLATPAK_FIREFOX), &["page.html".to_owned()]);
+	assert_eq!(with_file, [[&run[..], &["@@u", "page.html", "@@"]].concat()]);
+
+	let without_files = expand_invocations(exec_split(FLATPAK_FIREFOX), &[]);
+	assert_eq!(without_files, [run.to_vec()]);
+}
+
+#[test]
+fn flatpak_markers_without_file_forwarding_are_stripped() {
+	let exec = "/usr/bin/flatpak run --command=gimp org.gimp.GIMP @@ %F @@";
+	let expanded = expand_invocations(exec_split(exec), &["a.png".to_owned(), "b.png".to_owned()]);
+	assert_eq!(expanded, [["/usr/bin/flatpak", "run", "--command=gimp", "org.gimp.GIMP", "a.png", "b.png"]]);
+}
+
+#[test]
+fn inline_flatpak_markers_are_handled_like_the_others() {
+	let run = ["flatpak", "run", "--file-forwarding", "org.example.Viewer"];
+	let exec = "flatpak run --file-forwarding org.example.Viewer @@i %f @@";
+	assert_eq!(expand_invocations(exec_split(exec), &["a.png".to_owned()]), [[&run[..], &["@@i", "a.png", "@@"]].concat()]);
+	assert_eq!(expand_invocations(exec_split(exec), &[]), [run.to_vec()]);
+	let exec = "flatpak run org.example.Viewer @@i %f @@";
+	assert_eq!(expand_invocations(exec_split(exec), &["a.png".to_owned()]), [["flatpak", "run", "org.example.Viewer", "a.png"]]);
+}
+
+#[test]
+fn flatpak_entries_launch_through_flatpak_run() {
+	let exported = format!("[Desktop Entry]\nName=Firefox\nExec={FLATPAK_FIREFOX}\nX-Flatpak=org.mozilla.firefox\n");
+	let firefox = parse_desktop_entry(&exported).unwrap();
+	assert_eq!(firefox.flatpak.as_deref(), Some("org.mozilla.firefox"));
+	let resolved = Spawn::new("Firefox".to_owned(), None).with_args(vec!["a.html".to_owned(), "b.html".to_owned()]).resolve(&firefox).unwrap();
+	let argv: Vec<Vec<&str>> = resolved.iter().map(|command| [command.program.as_str()].into_iter().chain(command.args.iter().map(String::as_str)).collect()).collect();
+	let run = ["/usr/bin/flatpak", "run", "--branch=stable", "--arch=x86_64", "--command=firefox", "--file-forwarding", "org.mozilla.firefox"];
+	assert_eq!(argv, [[&run[..], &["@@u", "a.html", "@@"]].concat(), [&run[..], &["@@u", "b.html", "@@"]].concat()]);
+
+	//Without X-Flatpak the ID comes from the `flatpak run` invocation
+	let gimp = Ini { exec: "env GTK_THEME=Adwaita flatpak run --branch=stable --command=gimp org.gimp.GIMP @@ %F @@".to_owned(), ..Default::default() };
+	assert_eq!(gimp.flatpak_id().as_deref(), Some("org.gimp.GIMP"));
+	assert_eq!(Ini { exec: "flatpak update".to_owned(), ..Default::default() }.flatpak_id(), None);
+	assert_eq!(Ini { exec: "vim %F".to_owned(), ..Default::default() }.flatpak_id(), None);
+}
+
+#[test]
+fn terminal_here_runs_the_bare_terminal_in_the_directory() {
+	let spawn = Spawn::new("terminal".to_owned(), Some("foot".to_owned()));
+	let resolved = spawn.resolve_terminal_here("/srv/project".into()).unwrap();
+	assert_eq!(resolved.program, "foot");
+	assert!(resolved.args.is_empty());
+	assert_eq!(resolved.cwd.as_deref(), Some(std::path::Path::new("/srv/project")));
+
+	let without_terminal = Spawn::new("terminal".to_owned(), None).with_terminal_detection(false);
+	assert!(without_terminal.resolve_terminal_here("/srv/project".into()).is_err());
+}
+
+#[test]
+fn file_uris_are_absolute_and_percent_encoded() {
+	assert_eq!(file_uri("/home/me/My Photos/it's.jpg"), "file:///home/me/My%20Photos/it%27s.jpg");
+	assert_eq!(file_uri("https://example.org/a b"), "https://example.org/a b");
+	assert!(file_uri("notes.txt").starts_with("file:///"));
+}
+
+#[test]
+fn failed_dbus_activation_falls_back_to_exec() {
+	let app = |exec: &str| Ini {
+		id: "org.tal.Test.NoSuchService.desktop".to_owned(),
+		name: "Missing".to_owned(),
+		exec: exec.to_owned(),
+		dbus_activatable: true,
+		..Default::default()
+	};
+	let spawn = Spawn::new("Missing".to_owned(), None);
+	let children = spawn.spawn(&app("true"), SpawnMode::Detached).unwrap();
+	assert_eq!(children.len(), 1);
+	children.into_iter().for_each(|mut child| { child.wait().unwrap(); });
+
+	let error = spawn.spawn(&app(""), SpawnMode::Detached).unwrap_err();
+	assert_eq!(error.kind(), "ActivationFailed");
+	assert!(matches!(error, RunError::Activation(_, _, fallback) if matches!(*fallback, RunError::EmptyExec(_))));
+}
+
+#[test]
+fn file_codes_get_decoded_paths_and_url_codes_the_url() {
+	let url = "file:///home/me/My%20Notes.txt";
+	assert_eq!(argv("editor %f", &[url]), ["editor", "/home/me/My Notes.txt"]);
+	assert_eq!(argv("editor %F", &[url, "file://localhost/tmp/a%2Bb"]), ["editor", "/home/me/My Notes.txt", "/tmp/a+b"]);
+	assert_eq!(argv("editor %u", &[url]), ["editor", url]);
+	assert_eq!(argv("editor %U", &[url]), ["editor", url]);
+}
+
+#[test]
+fn remote_urls_only_reach_url_codes() {
+	let url = "https://example.org/a%20b";
+	assert_eq!(argv("viewer %F", &[url, "local.png"]), ["viewer", "local.png"]);
+	assert_eq!(argv("viewer %f", &[url]), ["viewer"]);
+	assert_eq!(argv("viewer %U", &[url]), ["viewer", url]);
+	assert_eq!(local_path("file://otherhost/etc/hosts"), None);
+}
+
+#[test]
+fn priorities_prefix_the_command() {
+	let app = Ini { name: "Blender".to_owned(), exec: "blender %f".to_owned(), ..Default::default() };
+	let spawn = Spawn::new("Blender".to_owned(), None).with_priority(Some(10), Some(IoClass::Idle));
+	let resolved = &spawn.resolve(&app).unwrap()[0];
+	let argv: Vec<&str> = [resolved.program.as_str()].into_iter().chain(resolved.args.iter().map(String::as_str)).collect();
+	//Only the tools installed here are used, the rest are skipped
+	let mut expected = Vec::new();
+	if which("ionice").is_some() {
+		expected.extend(["ionice", "-c", "3"]);
+	}
+	if which("nice").is_some() {
+		expected.extend(["nice", "-n", "10"]);
+	}
+	expected.push("blender");
+	assert_eq!(argv, expected);
+}
+
+#[test]
+fn entry_codes_become_the_name_and_file() {
+	let tokens = |exec: &str| expand_entry_codes(exec_split(exec), "100% Tool", Some("/apps/tool.desktop"), Some("tool"));
+	assert_eq!(expand_field_codes(tokens("tool --title %c --from=%k %%c"), &[]), ["tool", "--title", "100% Tool", "--from=/apps/tool.desktop", "%c"]);
+	assert_eq!(expand_field_codes(tokens("tool %i %F"), &["a.txt".to_owned()]), ["tool", "--icon", "tool", "a.txt"]);
+	assert_eq!(expand_field_codes(expand_entry_codes(exec_split("tool %k %i"), "Tool", None, None), &[]), ["tool"]);
+}
+
+#[test]
+fn args_are_appended_without_file_codes() {
+	let args = ["one.mkv".to_owned(), "two.mkv".to_owned()];
+	assert_eq!(expand_invocations(exec_split("mpv --fs"), &args), [["mpv", "--fs", "one.mkv", "two.mkv"]]);
+	assert_eq!(expand_invocations(exec_split("mpv -- %U"), &args), [["mpv", "--", "one.mkv", "two.mkv"]]);
+	assert_eq!(expand_invocations(exec_split("mpv --fs"), &[]), [["mpv", "--fs"]]);
+}
+
+#[test]
+fn resolve_substitutes_the_entry_name() {
+	let app = Ini { name: "Notes".to_owned(), exec: "editor --class %c %F".to_owned(), ..Default::default() };
+	let resolved = Spawn::new("Notes".to_owned(), None).with_args(vec!["a.txt".to_owned()]).resolve(&app).unwrap();
+	assert_eq!(resolved[0].args, ["--class", "Notes", "a.txt"]);
+}
+
+#[test]
+fn quoted_arguments_stay_whole() {
+	assert_eq!(exec_tokenize(r#""/opt/My App/bin/app" --flag"#).unwrap(), ["/opt/My App/bin/app", "--flag"]);
+	assert_eq!(exec_tokenize(r#"sh -c "echo \"hi\" \$HOME \`date\` a\\b""#).unwrap(), ["sh", "-c", r#"echo "hi" $HOME `date` a\b"#]);
+	assert_eq!(argv(r#""/opt/My App/app" %F"#, &["a b.txt"]), ["/opt/My App/app", "a b.txt"]);
+	assert_eq!(exec_tokenize(r#"app "" end"#).unwrap(), ["app", "", "end"]);
+	//Single quotes mean nothing to Exec, they are left to the shell
+	assert_eq!(exec_tokenize(r#"sh -c "exec myapp --flag 'a b'""#).unwrap(), ["sh", "-c", "exec myapp --flag 'a b'"]);
+	assert_eq!(exec_tokenize("").unwrap(), Vec::<String>::new());
+	assert_eq!(exec_tokenize(" \t ").unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn unclosed_quotes_are_errors() {
+	assert_eq!(exec_tokenize(r#"app "never closed"#), Err(UnterminatedQuote(4)));
+	assert_eq!(exec_tokenize(r#"app "escaped end\""#), Err(UnterminatedQuote(4)));
+
+	let app = Ini { name: "Broken".to_owned(), exec: r#""/opt/My App/app"#.to_owned(), ..Default::default() };
+	let error = Spawn::new("Broken".to_owned(), None).resolve(&app).unwrap_err();
+	assert!(matches!(error, RunError::MalformedExec(name, UnterminatedQuote(0)) if name == "Broken"));
+}
+
+#[test]
+fn tal_env_reaches_the_child() {
+	let dir = TempDir::new();
+	let script = dir.write("print-env", "#!/bin/sh\nprintf '%s %s' \"$MOZ_ENABLE_WAYLAND\" \"$TAL_TEST_EMPTY\" > \"$1\"\n");
+	fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+	let out = dir.path().join("env");
+	let app = Ini {
+		name: "Print".to_owned(),
+		exec: format!("{} %f", script.display()),
+		env: vec![("MOZ_ENABLE_WAYLAND".to_owned(), "1".to_owned()), ("TAL_TEST_EMPTY".to_owned(), "set".to_owned())],
+		..Default::default()
+	};
+	let spawn = Spawn::new("Print".to_owned(), None).with_args(vec![out.display().to_string()]);
+	spawn.spawn(&app, SpawnMode::Detached).unwrap().into_iter().for_each(|mut child| { child.wait().unwrap(); });
+	assert_eq!(fs::read_to_string(out).unwrap(), "1 set");
+}
+
+#[test]
+fn try_exec_needs_an_executable_file() {
+	let dir = TempDir::new();
+	let tool = dir.write("bin/tal-tool", "#!/bin/sh\n");
+	let data = dir.write("bin/tal-data", "");
+	fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+	let path = dir.path().join("bin").into_os_string();
+	let try_exec = |program: &str| Ini { try_exec: Some(program.to_owned()), ..Default::default() };
+
+	assert!(try_exec("tal-tool").try_exec_found_in(&path));
+	assert!(try_exec(&tool.display().to_string()).try_exec_found_in("".as_ref()));
+	assert!(!try_exec("tal-data").try_exec_found_in(&path));
+	assert!(!try_exec(&data.display().to_string()).try_exec_found_in(&path));
+	assert!(!try_exec("tal-tool").try_exec_found_in("/nonexistent".as_ref()));
+	assert!(Ini::default().try_exec_found_in("".as_ref()));
+}
+
+#[test]
+fn detached_launches_get_their_own_session() {
+	let dir = TempDir::new();
+	let script = dir.write("print-pgid", "#!/bin/sh\nread -r _ _ _ _ pgid sid _ < /proc/$$/stat\necho \"$pgid $sid\" > \"$1\"\n");
+	fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+	let app = Ini { name: "Group".to_owned(), exec: format!("{} %f", script.display()), ..Default::default() };
+	let pgid = |mode: SpawnMode| {
+		let out = dir.path().join("pgid");
+		let spawn = Spawn::new("Group".to_owned(), None).with_args(vec![out.display().to_string()]);
+		let mut children = spawn.spawn(&app, mode).unwrap();
+		let mut child = children.remove(0);
+		child.wait().unwrap();
+		let ids: Vec<u32> = fs::read_to_string(out).unwrap().split_whitespace().map(|id| id.parse().unwrap()).collect();
+		(child.id(), ids[0], ids[1])
+	};
+
+	let (pid, group, session) = pgid(SpawnMode::Detached);
+	assert_eq!((group, session), (pid, pid));
+	let (pid, group, session) = pgid(SpawnMode::Background);
+	assert_ne!(group, pid);
+	assert_ne!(session, pid);
+}
+
+#[test]
+fn attached_launches_report_a_failed_exit() {
+	let app = |exec: &str| Ini { name: "Exit".to_owned(), exec: exec.to_owned(), ..Default::default() };
+	let spawn = Spawn::new("Exit".to_owned(), None);
+	assert!(spawn.launch(app("true"), SpawnMode::Attached).is_ok());
+	let error = spawn.launch(app("sh -c \"exit 3\""), SpawnMode::Attached).unwrap_err();
+	assert!(matches!(&error, RunError::Exited(name, status) if name == "Exit" && status.code() == Some(3)));
+	assert_eq!(error.to_json().to_string(), r#"{"error":"Exited","name":"Exit","code":3,"signal":null}"#);
+	assert!(spawn.launch(app("false"), SpawnMode::Detached).is_ok());
+}
+
+#[test]
+fn path_is_the_working_directory() {
+	let dir = TempDir::new();
+	let app = |path: Option<&Path>| Ini { name: "Project".to_owned(), exec: "make".to_owned(), path: path.map(ToOwned::to_owned), ..Default::default() };
+	let spawn = Spawn::new("Project".to_owned(), None);
+	let cwd = |app: Ini| spawn.resolve(&app).unwrap().remove(0).cwd;
+
+	assert_eq!(cwd(app(Some(dir.path()))).as_deref(), Some(dir.path()));
+	assert_eq!(cwd(app(None)), env::current_dir().ok());
+	let missing = dir.path().join("missing");
+	let error = spawn.resolve(&app(Some(&missing))).unwrap_err();
+	assert!(matches!(&error, RunError::PathNotFound(name, path) if name == "Project" && *path == missing));
+	assert_eq!(error.kind(), "PathNotFound");
+}
+
+#[test]
+fn exec_program_skips_an_env_prefix() {
+	let program = |exec: &str| exec_program(&exec_split(exec)).map(str::to_owned);
+	assert_eq!(program("env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/spotify_spotify.desktop /snap/bin/spotify %U").as_deref(), Some("/snap/bin/spotify"));
+	assert_eq!(program("/usr/bin/env GDK_SCALE=2 A=b gimp").as_deref(), Some("gimp"));
+	assert_eq!(program("firefox %u").as_deref(), Some("firefox"));
+	assert_eq!(program("env FOO=bar"), None);
+	assert_eq!(program(""), None);
+	//Options of env and the values some of them take aren't the program
+	assert_eq!(program("env -u VAR app").as_deref(), Some("app"));
+	assert_eq!(program("env -i -uVAR --unset=OTHER --unset THIRD A=b app %F").as_deref(), Some("app"));
+	assert_eq!(program("env -iC /tmp --chdir=/tmp --chdir /opt app").as_deref(), Some("app"));
+	assert_eq!(program("env -- A=b app").as_deref(), Some("app"));
+	assert_eq!(program("env -S \"app --flag\" %U").as_deref(), Some("app"));
+	assert_eq!(program("env --split-string=app").as_deref(), Some("app"));
+	assert_eq!(program("env -u").as_deref(), None);
+}
+
+#[test]
+fn known_terminals_are_detected_on_path() {
+	let dir = TempDir::new();
+	for term in ["xterm", "konsole", "gnome-terminal"] {
+		let program = dir.write(&format!("bin/{term}"), "#!/bin/sh\n");
+		fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+	}
+	let path = dir.path().join("bin").into_os_string();
+
+	let detected = terminal::detect_in(&path).unwrap();
+	assert_eq!((detected.program.as_str(), detected.origin), ("gnome-terminal", Origin::Detected));
+	assert_eq!(detected.wrap(vec!["htop".to_owned()]), ["gnome-terminal", "--", "htop"]);
+	assert_eq!(terminal::detect_in("/nonexistent".as_ref()), None);
+}
+
+#[test]
+fn the_terminal_fallback_is_only_used_when_nothing_is_detected() {
+	let dir = TempDir::new();
+	for term in ["xterm", "myterm"] {
+		let program = dir.write(&format!("bin/{term}"), "#!/bin/sh\n");
+		fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+	}
+	let path = dir.path().join("bin").into_os_string();
+	let resolved = |env: Option<&str>, fallback: &str, detect| terminal::resolve_in(env.map(str::to_owned), Some(fallback.to_owned()), detect, &path)
+		.map(|term| (term.program, term.origin));
+
+	assert_eq!(resolved(Some("kitty"), "myterm", true), Some(("kitty".to_owned(), Origin::Env)));
+	assert_eq!(resolved(None, "myterm", true), Some(("xterm".to_owned(), Origin::Detected)));
+	assert_eq!(resolved(None, "myterm", false), Some(("myterm".to_owned(), Origin::Fallback)));
+	assert_eq!(resolved(None, "missing", false), None);
+	assert_eq!(terminal::resolve_in(None, Some("myterm".to_owned()), true, "/nonexistent".as_ref()), None);
+}
+
+#[test]
+fn terminal_flag_wins_over_the_environment() {
+	let app = Ini { name: "Htop".to_owned(), exec: "htop".to_owned(), terminal: true, ..Default::default() };
+	let spawn = Spawn::new("Htop".to_owned(), Some("xterm".to_owned())).with_terminal(Some("gnome-terminal".to_owned()));
+	assert_eq!(spawn.resolve_terminal().map(|term| term.origin), Some(Origin::Flag));
+	let resolved = spawn.resolve(&app).unwrap();
+	assert_eq!((resolved[0].program.as_str(), resolved[0].args.as_slice()), ("gnome-terminal", ["--".to_owned(), "htop".to_owned()].as_slice()));
+
+	let unset = Spawn::new("Htop".to_owned(), Some("xterm".to_owned())).with_terminal(Some(String::new()));
+	assert_eq!(unset.resolve_terminal().map(|term| term.origin), Some(Origin::Env));
+}
@@ -0,0 +1,100 @@
+use tal::{apps::{Ini, IniAction, MatchOptions, RunError, filter, find_entry}, fold::fold_diacritics};
+
+fn entries() -> Vec<Ini> {
+	let ini = |name: &str, exec: &str| Ini { id: format!("{}.desktop", name.to_lowercase()), name: name.to_owned(), exec: exec.to_owned(), ..Default::default() };
+	vec![
+		ini("Firefox", "firefox %u"),
+		ini("GNU Image Manipulation Program", "/usr/bin/gimp-2.10 %U"),
+		ini("Image Viewer", "eog %U"),
+		ini("Thunderbird", "thunderbird %u"),
+		ini("Files", "nautilus --new-window %U"),
+		ini("Files", "thunar %F"),
+		ini("Disk Usage", "baobab"),
+		ini("Disk Utility", "gnome-disks"),
+	]
+}
+
+fn found(query: &str) -> String {
+	let app = find_entry(entries(), query, MatchOptions::default()).unwrap_or_else(|e| panic!("{query:?} not found: {e}"));
+	format!("{} ({})", app.name, app.exec)
+}
+
+#[test]
+fn exact_names_win_and_the_first_of_a_name_is_picked() {
+	assert_eq!(found("FIREFOX"), "Firefox (firefox %u)");
+	assert_eq!(found("files"), "Files (nautilus --new-window %U)");
+}
+
+#[test]
+fn programs_match_before_partial_names() {
+	assert_eq!(found("thunar"), "Files (thunar %F)");
+	assert_eq!(found("gimp-2.10"), "GNU Image Manipulation Program (/usr/bin/gimp-2.10 %U)");
+	assert_eq!(found("Baobab"), "Disk Usage (baobab)");
+}
+
+#[test]
+fn unique_prefixes_and_substrings_match() {
+	assert_eq!(found("fire"), "Firefox (firefox %u)");
+	assert_eq!(found("thunder"), "Thunderbird (thunderbird %u)");
+	assert_eq!(found("manipulation"), "GNU Image Manipulation Program (/usr/bin/gimp-2.10 %U)");
+	//A unique prefix wins over other entries containing the query
+	assert_eq!(found("image"), "Image Viewer (eog %U)");
+}
+
+#[test]
+fn ambiguous_queries_list_the_candidates() {
+	match find_entry(entries(), "disk", MatchOptions::default()) {
+		Err(RunError::Ambiguous(query, candidates)) => assert_eq!((query.as_str(), candidates), ("disk", vec!["Disk Usage".to_owned(), "Disk Utility".to_owned()])),
+		other => panic!("expected Ambiguous, got {other:?}"),
+	}
+	assert!(matches!(find_entry(entries(), "e", MatchOptions::default()), Err(RunError::Ambiguous(..))));
+	assert!(matches!(find_entry(entries(), "chromium", MatchOptions::default()), Err(RunError::NotFound(query)) if query == "chromium"));
+}
+
+#[test]
+fn search_looks_at_names_execs_comments_and_actions() {
+	let mut entries = entries();
+	entries[0].actions.insert("new-private-window".to_owned(), IniAction { name: Some("New Private Window".to_owned()), exec: None, terminal: None, path: None });
+	entries[3].comment = Some("Read and write mail".to_owned());
+	let names = |query: &str| filter(entries.clone(), query).into_iter().map(|app| app.name).collect::<Vec<_>>();
+
+	assert_eq!(names("PRIVATE"), ["Firefox"]);
+	assert_eq!(names("mail"), ["Thunderbird"]);
+	assert_eq!(names("nautilus"), ["Files"]);
+	assert_eq!(names("disk u"), ["Disk Usage", "Disk Utility"]);
+	assert!(names("chromium").is_empty());
+}
+
+#[test]
+fn diacritics_fold_to_their_base_letters() {
+	assert_eq!(fold_diacritics("Téléphone"), "Telephone");
+	assert_eq!(fold_diacritics("Čeština Ångström Łódź"), "Cestina Angstrom Łodz");
+	//Already decomposed text loses its combining marks
+	assert_eq!(fold_diacritics("Te\u{301}le\u{301}phone"), "Telephone");
+	assert_eq!(fold_diacritics("Straße Ørsted"), "Straße Ørsted");
+}
+
+#[test]
+fn folded_names_match_accented_entries() {
+	let ini = |name: &str| Ini { name: name.to_owned(), exec: "true".to_owned(), ..Default::default() };
+	let entries = || vec![ini("Téléphone"), ini("Café Manager"), ini("Ärzte")];
+	let found = |query: &str| find_entry(entries(), query, MatchOptions { fold_diacritics: true, ..Default::default() }).map(|app| app.name);
+
+	assert_eq!(found("telephone").unwrap(), "Téléphone");
+	assert_eq!(found("TÉLÉPHONE").unwrap(), "Téléphone");
+	assert_eq!(found("cafe").unwrap(), "Café Manager");
+	assert_eq!(found("arzte").unwrap(), "Ärzte");
+	assert!(matches!(find_entry(entries(), "telephone", MatchOptions::default()), Err(RunError::NotFound(_))));
+}
+
+#[test]
+fn letters_in_order_match_last_and_first_picks_the_best() {
+	assert_eq!(found("firfox"), "Firefox (firefox %u)");
+	assert_eq!(found("thndrbrd"), "Thunderbird (thunderbird %u)");
+	assert!(matches!(find_entry(entries(), "dsku", MatchOptions::default()), Err(RunError::Ambiguous(..))));
+
+	let first = |query: &str| find_entry(entries(), query, MatchOptions { first: true, ..Default::default() }).unwrap().name;
+	assert_eq!(first("disk"), "Disk Usage");
+	assert_eq!(first("dskut"), "Disk Utility");
+	assert_eq!(first("files"), "Files");
+}
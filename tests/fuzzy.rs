@@ -0,0 +1,35 @@
+use tal::{apps::Ini, fuzzy::{self, Scorer, Subsequence, Weights}};
+
+fn ini(name: &str) -> Ini {
+	Ini { name: name.to_owned(), exec: "true".to_owned(), ..Default::default() }
+}
+
+#[test]
+fn subsequence_requires_every_query_char_in_order() {
+	assert!(Subsequence.score("ffx", "Firefox").is_some());
+	assert!(Subsequence.score("xff", "Firefox").is_none());
+	assert!(Subsequence.score("fire", "Firefox") > Subsequence.score("fire", "Office Reader"));
+}
+
+#[test]
+fn name_matches_outrank_comment_matches() {
+	let by_name = ini("Terminal");
+	let by_comment = Ini { comment: Some("Terminal emulator".to_owned()), ..ini("Console") };
+	let by_keyword = Ini { keywords: vec!["terminal".to_owned()], ..ini("Shell") };
+	let entries = [by_comment, by_keyword, by_name];
+
+	let ranked: Vec<&str> = fuzzy::rank(&Subsequence, &Weights::default(), "terminal", &entries)
+		.into_iter().map(|(_, ini)| ini.name.as_str()).collect();
+	assert_eq!(ranked, ["Terminal", "Shell", "Console"]);
+}
+
+#[test]
+fn weights_can_flip_the_ranking() {
+	let by_name = ini("Terminal");
+	let by_comment = Ini { comment: Some("Terminal emulator".to_owned()), ..ini("Console") };
+	let entries = [by_name, by_comment];
+	let comment_first = Weights { name: 1, comment: 10, ..Weights::default() };
+
+	let ranked = fuzzy::rank(&Subsequence, &comment_first, "terminal", &entries);
+	assert_eq!(ranked[0].1.name, "Console");
+}
@@ -0,0 +1,51 @@
+use tal::apps::Ini;
+use tal::history::{HALF_LIFE, History, frecency};
+
+fn ini(id: &str, name: &str) -> Ini {
+	Ini { id: id.to_owned(), name: name.to_owned(), ..Default::default() }
+}
+
+#[test]
+fn record_counts_launches_per_id() {
+	let mut history = History::default();
+	history.record(&ini("firefox.desktop", "Firefox"), 10);
+	history.record(&ini("files.desktop", "Files"), 20);
+	history.record(&ini("firefox.desktop", "Firefox"), 30);
+	assert_eq!(history.launches.len(), 2);
+	assert_eq!(history.launches[0].count, 2);
+	assert_eq!(history.last().map(|launch| launch.id.as_str()), Some("firefox.desktop"));
+}
+
+#[test]
+fn damaged_lines_are_skipped() {
+	let history = History::parse("10\t1\tfiles.desktop\tFiles\nnot a launch\n20\t3\tfirefox.desktop\tFirefox Web\n");
+	assert_eq!(history.launches.len(), 2);
+	assert_eq!(history.last().map(|launch| (launch.name.as_str(), launch.count)), Some(("Firefox Web", 3)));
+}
+
+#[test]
+fn empty_history_has_no_last() {
+	assert!(History::parse("").last().is_none());
+}
+
+#[test]
+fn frecency_halves_every_half_life() {
+	assert_eq!(frecency(4, 100, 100), 4.0);
+	assert_eq!(frecency(4, 100, 100 + HALF_LIFE), 2.0);
+	assert_eq!(frecency(4, 100, 100 + 2 * HALF_LIFE), 1.0);
+	//A launch in the future counts fully
+	assert_eq!(frecency(3, 200, 100), 3.0);
+	assert!(frecency(10, 0, 4 * HALF_LIFE) < frecency(1, 4 * HALF_LIFE, 4 * HALF_LIFE));
+}
+
+#[test]
+fn sort_puts_frecent_apps_first_then_names() {
+	let history = History::parse(&format!("{now}\t2\tvim.desktop\tVim\n0\t50\tfiles.desktop\tFiles\n{now}\t9\tfirefox.desktop\tFirefox\n", now = 40 * HALF_LIFE));
+	let mut entries = vec![ini("zed.desktop", "Zed"), ini("files.desktop", "Files"), ini("vim.desktop", "Vim"), ini("firefox.desktop", "Firefox"), ini("atom.desktop", "atom")];
+	history.sort(&mut entries, 40 * HALF_LIFE);
+	let names: Vec<&str> = entries.iter().map(|app| app.name.as_str()).collect();
+	assert_eq!(names, ["Firefox", "Vim", "Files", "atom", "Zed"]);
+
+	History::parse("damaged").sort(&mut entries, 0);
+	assert_eq!(entries.iter().map(|app| app.name.as_str()).collect::<Vec<_>>(), ["atom", "Files", "Firefox", "Vim", "Zed"]);
+}
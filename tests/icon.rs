@@ -0,0 +1,55 @@
+mod common;
+
+use common::TempDir;
+use tal::icon::{IconCache, IconLookup};
+
+const INDEX: &str = "[Icon Theme]\nName=Test\nInherits=hicolor\nDirectories=16x16/apps,48x48/apps,scalable/apps\n\n[16x16/apps]\nSize=16\nType=Fixed\n\n[48x48/apps]\nSize=48\nType=Fixed\n\n[scalable/apps]\nSize=48\nMinSize=8\nMaxSize=512\nType=Scalable\n";
+
+fn icons() -> TempDir {
+	let base = TempDir::new();
+	base.write("test/index.theme", INDEX);
+	base.write("test/16x16/apps/firefox.png", "");
+	base.write("test/48x48/apps/firefox.png", "");
+	base.write("hicolor/index.theme", "[Icon Theme]\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\nType=Threshold\n");
+	base.write("hicolor/48x48/apps/vim.png", "");
+	base.write("htop.xpm", "");
+	base
+}
+
+#[test]
+fn lookup_prefers_the_matching_size_then_parents_then_unthemed() {
+	let base = icons();
+	let lookup = IconLookup::new(vec![base.path().to_owned()]);
+	assert_eq!(lookup.resolve("firefox", 16, "test"), Some(base.path().join("test/16x16/apps/firefox.png")));
+	assert_eq!(lookup.resolve("firefox", 40, "test"), Some(base.path().join("test/48x48/apps/firefox.png")));
+	assert_eq!(lookup.resolve("vim", 16, "test"), Some(base.path().join("hicolor/48x48/apps/vim.png")));
+	assert_eq!(lookup.resolve("htop", 48, "test"), Some(base.path().join("htop.xpm")));
+	assert_eq!(lookup.resolve("missing", 48, "test"), None);
+}
+
+#[test]
+fn cache_keeps_results_until_the_theme_changes() {
+	let base = icons();
+	let cache = IconCache::new(IconLookup::new(vec![base.path().to_owned()]));
+	assert!(cache.resolve("firefox", 48, "test").is_some());
+	assert!(cache.resolve("missing", 48, "test").is_none());
+	assert_eq!(cache.len(), 2);
+
+	assert!(cache.resolve("firefox", 48, "hicolor").is_none());
+	assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn cache_round_trips_through_disk() {
+	let base = icons();
+	let lookup = IconLookup::new(vec![base.path().to_owned()]);
+	let cache = IconCache::new(lookup.clone());
+	cache.resolve("firefox", 48, "test");
+	cache.resolve("missing", 48, "test");
+	let cache_file = base.path().join("cache/icons");
+	cache.save(&cache_file).unwrap();
+
+	let loaded = IconCache::load(lookup, &cache_file).unwrap();
+	assert_eq!(loaded.len(), 1);
+	assert_eq!(loaded.resolve("firefox", 48, "test"), Some(base.path().join("test/48x48/apps/firefox.png")));
+}
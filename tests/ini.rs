@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+use tal::apps::{ApplicationEntry, Ini, IniAction, RunError};
+
+fn ini(name: &str, exec: &str, terminal: bool) -> Ini {
+	Ini {
+		name: name.to_owned(),
+		untranslated_name: None,
+		exec: exec.to_owned(),
+		terminal,
+		..Default::default()
+	}
+}
+
+fn action(exec: &str) -> IniAction<String> {
+	IniAction { name: Some("New Window".to_owned()), exec: Some(exec.to_owned()), terminal: None, path: None }
+}
+
+#[test]
+fn equal_fields_are_equal() {
+	assert_eq!(ini("Firefox", "firefox %u", false), ini("Firefox", "firefox %u", false));
+	assert_ne!(ini("Firefox", "firefox %u", false), ini("Firefox", "firefox %u", true));
+	assert_ne!(ini("Firefox", "firefox %u", false), ini("Firefox", "firefox-esr %u", false));
+}
+
+#[test]
+fn actions_take_part_in_equality() {
+	let mut with_action = ini("Firefox", "firefox %u", false);
+	with_action.actions.insert("new-window".to_owned(), action("firefox --new-window"));
+	assert_ne!(with_action, ini("Firefox", "firefox %u", false));
+
+	let mut same_action = ini("Firefox", "firefox %u", false);
+	same_action.actions.insert("new-window".to_owned(), action("firefox --new-window"));
+	assert_eq!(with_action, same_action);
+}
+
+#[test]
+fn hash_set_dedups_equal_entries() {
+	let entries: HashSet<Ini> = [
+		ini("Firefox", "firefox %u", false),
+		ini("Firefox", "firefox %u", false),
+		ini("Vim", "vim %F", true),
+	].into_iter().collect();
+	assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn desktop_string_round_trips() {
+	let mut app = ini("Firefox", "firefox %u", false);
+	app.generic_name = Some("Web Browser".to_owned());
+	app.icon = Some("firefox".to_owned());
+	app.categories = vec!["Network".to_owned(), "WebBrowser".to_owned()];
+	app.mime_types = vec!["text/html".to_owned()];
+	app.startup_wm_class = Some("firefox".to_owned());
+	app.actions.insert("new-window".to_owned(), action("firefox --new-window"));
+
+	let decoded = ApplicationEntry::new(app.to_desktop_string()).decode(None).unwrap();
+	assert_eq!(decoded, app);
+	assert_eq!(decoded.generic_name, app.generic_name);
+	assert_eq!(decoded.icon, app.icon);
+	assert_eq!(decoded.categories, app.categories);
+	assert_eq!(decoded.mime_types, app.mime_types);
+	assert_eq!(decoded.startup_wm_class, app.startup_wm_class);
+}
+
+#[test]
+fn escaped_values_round_trip() {
+	let mut app = ini(" Leading space", "app --title \" x\"", false);
+	app.comment = Some("two\nlines with a \\ and a\ttab".to_owned());
+	app.icon = Some(" icon".to_owned());
+	app.keywords = vec![" spaced".to_owned(), "semi;colon".to_owned(), "back\\slash".to_owned()];
+	app.env = vec![("OPTS".to_owned(), " -a;-b\\c".to_owned()), ("MULTI".to_owned(), "x\ny".to_owned())];
+	app.path = Some(" /opt/app".into());
+	app.actions.insert("new".to_owned(), IniAction { name: Some(" New\\Window".to_owned()), ..action("app --new") });
+
+	let written = app.to_desktop_string();
+	assert!(written.contains("\nName=\\sLeading space\n"), "{written}");
+	assert!(written.contains("\nKeywords=\\sspaced;semi\\;colon;back\\\\slash;\n"), "{written}");
+	let decoded = ApplicationEntry::new(written).decode(None).unwrap();
+	assert_eq!(decoded, app);
+	assert_eq!(decoded.to_json_details(), app.to_json_details());
+}
+
+#[test]
+fn actions_replace_exec_and_inherit_terminal() {
+	let mut htop = ini("Htop", "htop", true);
+	htop.actions.insert("tree".to_owned(), action("htop --tree"));
+	htop.actions.insert("sort".to_owned(), IniAction { terminal: Some(false), ..action("htop --sort-key PERCENT_CPU") });
+
+	let tree = htop.with_action("tree").unwrap();
+	assert_eq!((tree.exec.as_str(), tree.terminal), ("htop --tree", true));
+	assert!(!htop.with_action("sort").unwrap().terminal);
+	match htop.with_action("missing") {
+		Err(RunError::ActionNotFound(_, action, available)) => assert_eq!((action.as_str(), available), ("missing", vec!["sort".to_owned(), "tree".to_owned()])),
+		other => panic!("expected ActionNotFound, got {other:?}"),
+	}
+}
+
+#[test]
+fn actions_match_by_key_or_name_ignoring_case() {
+	let mut firefox = ini("Firefox", "firefox %u", false);
+	firefox.actions.insert("new-private-window".to_owned(), IniAction { name: Some("New Private Window".to_owned()), ..action("firefox --private-window") });
+	firefox.actions.insert("new-window".to_owned(), action("firefox --new-window"));
+
+	assert_eq!(firefox.action_id("New-Private-Window"), Some("new-private-window"));
+	assert_eq!(firefox.action_id("new private window"), Some("new-private-window"));
+	assert_eq!(firefox.action_id("NEW WINDOW"), Some("new-window"));
+	assert_eq!(firefox.action_id("private"), None);
+}
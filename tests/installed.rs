@@ -0,0 +1,330 @@
+mod common;
+
+use std::{fs::{self, File}, io::ErrorKind, num::NonZeroUsize, path::Path, time::{Duration, UNIX_EPOCH}};
+use common::{TempDir, entry};
+use tal::{apps::{DecodeError, DedupKey, Installed, ReadError, RunError, Source, Spawn, SpawnMode}, locale::Locale};
+
+fn names(installed: &Installed) -> Vec<String> {
+	let mut names: Vec<String> = installed.all().expect("scan failed").into_iter().map(|app| app.name).collect();
+	names.sort();
+	names
+}
+
+#[test]
+fn earlier_directories_shadow_later_ones() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	user.write("firefox.desktop", &entry("Firefox (user)", "firefox --user %u"));
+	system.write("firefox.desktop", &entry("Firefox", "firefox %u"));
+	system.write("vim.desktop", &entry("Vim", "vim %F"));
+
+	let installed = Installed::from_paths([user.path().to_owned(), system.path().to_owned()]);
+	assert_eq!(names(&installed), ["Firefox (user)", "Vim"]);
+
+	let firefox = installed.all().unwrap().into_iter().find(|app| app.id == "firefox.desktop").unwrap();
+	assert_eq!(firefox.exec, "firefox --user %u");
+	assert_eq!(Spawn::new("Firefox (user)".to_owned(), None).candidates(&installed).unwrap().len(), 1);
+	assert_eq!(Spawn::new("Firefox".to_owned(), None).find(&installed).unwrap().exec, "firefox --user %u");
+}
+
+#[test]
+fn parallel_scans_match_serial_ones() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	for i in 0..300 {
+		system.write(&format!("app-{i}.desktop"), &entry(&format!("App {i}"), &format!("app-{i}")));
+		if i % 7 == 0 {
+			user.write(&format!("app-{i}.desktop"), &entry(&format!("App {i} (user)"), &format!("app-{i} --user")));
+		}
+	}
+	let flatpak = TempDir::new();
+	flatpak.write("org.app.desktop", &entry("Flatpak App", "flatpak run org.app"));
+	let unreadable = flatpak.write("not-a-dir", "");
+	let scan = |jobs| {
+		let dirs = [
+			(Source::User, user.path().to_owned()), (Source::Flatpak, flatpak.path().to_owned()),
+			(Source::Flatpak, unreadable.clone()), (Source::System, system.path().to_owned()),
+		];
+		let installed = Installed::from_dirs(dirs).with_jobs(NonZeroUsize::new(jobs).unwrap());
+		let (entries, errors) = installed.all_with_errors();
+		(entries.into_iter().map(|app| (app.id, app.name)).collect::<Vec<_>>(), errors.len())
+	};
+	let (serial, errors) = scan(1);
+	assert_eq!((serial.len(), errors), (301, 1));
+	assert!(serial.contains(&("app-14.desktop".to_owned(), "App 14 (user)".to_owned())));
+	assert_eq!(scan(8), (serial, errors));
+}
+
+#[test]
+fn dedup_key_chooses_what_counts_as_a_duplicate() {
+	let apps = TempDir::new();
+	apps.write("firefox.desktop", &entry("Firefox", "firefox %u"));
+	apps.write("firefox-private.desktop", &entry("Firefox", "firefox --private-window %u"));
+	apps.write("web.desktop", &entry("Web Browser", "firefox %U"));
+
+	let installed = |dedup| Installed::from_paths([apps.path().to_owned()]).with_dedup(dedup);
+	assert_eq!(names(&installed(DedupKey::Id)), ["Firefox", "Firefox", "Web Browser"]);
+	assert_eq!(names(&installed(DedupKey::Name)), ["Firefox", "Web Browser"]);
+	//`firefox %u` and `firefox %U` launch the same command
+	assert_eq!(names(&installed(DedupKey::Exec)).len(), 2);
+}
+
+#[test]
+fn entries_carry_their_file_modification_time() {
+	let apps = TempDir::new();
+	let old = apps.write("old.desktop", &entry("Old", "old"));
+	apps.write("new.desktop", &entry("New", "new"));
+	let long_ago = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+	File::options().write(true).open(old).unwrap().set_modified(long_ago).unwrap();
+
+	let entries = Installed::from_paths([apps.path().to_owned()]).all().unwrap();
+	let modified = |name: &str| entries.iter().find(|app| app.name == name).and_then(|app| app.modified);
+	assert_eq!(modified("Old"), Some(long_ago));
+	assert!(modified("New").is_some_and(|modified| modified > long_ago));
+}
+
+#[test]
+fn resolution_reports_shadowed_files_and_other_matches() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	let user_firefox = user.write("firefox.desktop", &entry("Firefox", "firefox --user %u"));
+	let system_firefox = system.write("firefox.desktop", &entry("Firefox", "firefox %u"));
+	system.write("firefox-esr.desktop", &entry("Firefox", "firefox-esr %u"));
+
+	let installed = Installed::from_paths([user.path().to_owned(), system.path().to_owned()]);
+	let candidates = Spawn::new("firefox".to_owned(), None).candidates(&installed).unwrap();
+	let files: Vec<_> = candidates.iter().map(|app| app.file.clone().unwrap()).collect();
+	assert_eq!(files, [user_firefox, system.path().join("firefox-esr.desktop")]);
+	assert_eq!(installed.shadowed("firefox.desktop"), [system_firefox]);
+	assert!(installed.shadowed("firefox-esr.desktop").is_empty());
+}
+
+#[test]
+fn decode_errors_cover_shadowed_files_too() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	user.write("vim.desktop", &entry("Vim", "vim %F"));
+	let broken = system.write("vim.desktop", "Name=Vim\n");
+	let nameless = system.write("htop.desktop", "[Desktop Entry]\nExec=htop\n");
+
+	let installed = Installed::from_paths([user.path().to_owned(), system.path().to_owned()]);
+	let mut errors: Vec<_> = installed.decode_errors().into_iter().map(|(path, e)| (path, e.to_string())).collect();
+	errors.sort();
+	assert_eq!(errors, [
+		(nameless, DecodeError::MissingName.to_string()),
+		(broken, DecodeError::NotDesktopEntry.to_string()),
+	]);
+}
+
+#[test]
+fn run_action_reports_unknown_actions() {
+	let apps = TempDir::new();
+	apps.write("tool.desktop", &(entry("Tool", "true") + "Actions=fail;\n\n[Desktop Action fail]\nName=Fail\nExec=\n"));
+	let installed = Installed::from_paths([apps.path().to_owned()]);
+	let spawn = Spawn::new("Tool".to_owned(), None);
+
+	assert!(matches!(spawn.run_action(&installed, "FAIL", SpawnMode::Detached), Err(RunError::EmptyExec(_))));
+	match spawn.run_action(&installed, "missing", SpawnMode::Detached) {
+		Err(RunError::ActionNotFound(app, action, available)) => assert_eq!((app.as_str(), action.as_str(), available), ("Tool", "missing", vec!["fail".to_owned()])),
+		other => panic!("expected ActionNotFound, got {other:?}"),
+	}
+}
+
+#[test]
+fn names_with_a_colon_pick_an_action() {
+	let apps = TempDir::new();
+	apps.write("tool.desktop", &(entry("Tool", "tool") + "Actions=fast;\n\n[Desktop Action fast]\nName=Go Fast\nExec=tool --fast\n"));
+	apps.write("ratio.desktop", &entry("Ratio: 2", "ratio"));
+	let installed = Installed::from_paths([apps.path().to_owned()]);
+	let target = |name: &str| Spawn::new(name.to_owned(), None).find_target(&installed, None);
+
+	assert_eq!(target("tool:go fast").unwrap().exec, "tool --fast");
+	assert_eq!(target("Ratio: 2").unwrap().exec, "ratio");
+	assert!(matches!(target("nothing:fast"), Err(RunError::NotFound(name)) if name == "nothing:fast"));
+	assert!(matches!(target("tool:slow"), Err(RunError::ActionNotFound(..))));
+}
+
+#[test]
+fn no_display_entries_are_skipped_and_shadow_others() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	user.write("vim.desktop", &(entry("Vim", "vim %F") + "NoDisplay=true\n"));
+	system.write("vim.desktop", &entry("Vim", "vim %F"));
+	system.write("hidden-helper.desktop", &(entry("Helper", "helper") + "NoDisplay=true\n"));
+	system.write("htop.desktop", &entry("Htop", "htop"));
+
+	let installed = Installed::from_paths([user.path().to_owned(), system.path().to_owned()]);
+	assert_eq!(names(&installed), ["Htop"]);
+}
+
+#[test]
+fn hidden_and_other_desktops_entries_are_skipped() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	user.write("vim.desktop", &(entry("Vim", "vim %F") + "Hidden=true\n"));
+	system.write("vim.desktop", &entry("Vim", "vim %F"));
+	system.write("kcm.desktop", &(entry("KDE Settings", "systemsettings") + "OnlyShowIn=KDE;\n"));
+	system.write("tweaks.desktop", &(entry("Tweaks", "gnome-tweaks") + "NotShowIn=KDE;\n"));
+	system.write("htop.desktop", &entry("Htop", "htop"));
+
+	let installed = |desktops: &[&str]| Installed::from_paths([user.path().to_owned(), system.path().to_owned()])
+		.with_desktops(desktops.iter().map(|desktop| desktop.to_string()).collect());
+	assert_eq!(names(&installed(&["GNOME"])), ["Htop", "Tweaks"]);
+	assert_eq!(names(&installed(&["KDE"])), ["Htop", "KDE Settings"]);
+	assert_eq!(names(&installed(&[])), ["Htop", "KDE Settings", "Tweaks"]);
+	assert!(matches!(Spawn::new("Vim".to_owned(), None).find(&installed(&[])), Err(RunError::NotFound(_))));
+}
+
+#[test]
+fn entries_without_their_try_exec_are_skipped_unless_unfiltered() {
+	let apps = TempDir::new();
+	apps.write("shell.desktop", &(entry("Shell", "sh") + "TryExec=sh\n"));
+	apps.write("absolute.desktop", &(entry("Absolute", "true") + "TryExec=/bin/sh\n"));
+	apps.write("gone.desktop", &(entry("Gone", "gone") + "TryExec=tal-test-not-installed\n"));
+	apps.write("hidden.desktop", &(entry("Hidden", "true") + "Hidden=true\n"));
+	apps.write("kde.desktop", &(entry("KDE Only", "true") + "OnlyShowIn=KDE;\n"));
+
+	let installed = || Installed::from_paths([apps.path().to_owned()]).with_desktops(vec!["GNOME".to_owned()]);
+	assert_eq!(names(&installed()), ["Absolute", "Shell"]);
+	assert!(matches!(Spawn::new("Gone".to_owned(), None).find(&installed()), Err(RunError::NotFound(_))));
+	assert_eq!(names(&installed().with_filter(false)), ["Absolute", "Gone", "Hidden", "KDE Only", "Shell"]);
+}
+
+#[test]
+fn localized_names_follow_the_locale() {
+	let apps = TempDir::new();
+	apps.write("files.desktop", &(entry("Files", "nautilus") + "Name[de]=Dateien\nName[de_DE]=Dateien (DE)\nName[fr]=Fichiers\n"));
+
+	let dirs = || [apps.path().to_owned()];
+	assert_eq!(names(&Installed::from_paths(dirs()).with_locale(Locale::parse("de_DE.UTF-8"))), ["Dateien (DE)"]);
+	assert_eq!(names(&Installed::from_paths(dirs()).with_locale(Locale::parse("de_AT.UTF-8"))), ["Dateien"]);
+	assert_eq!(names(&Installed::from_paths(dirs()).with_locale(Locale::parse("ja_JP.UTF-8"))), ["Files"]);
+	assert_eq!(names(&Installed::from_paths(dirs()).with_locale(None)), ["Files"]);
+
+	let localized = Installed::from_paths(dirs()).with_locale(Locale::parse("fr_FR")).all().unwrap();
+	assert_eq!(localized[0].untranslated_name.as_deref(), Some("Files"));
+}
+
+#[test]
+fn semicolon_lists_drop_empty_segments() {
+	let apps = TempDir::new();
+	apps.write("code.desktop", &(entry("Code", "code %F") + "Categories=Development;IDE;\nOnlyShowIn=GNOME;KDE;\n"));
+
+	let code = Installed::from_paths([apps.path().to_owned()]).with_desktops(Vec::new()).all().unwrap().remove(0);
+	assert_eq!(code.categories, ["Development", "IDE"]);
+	assert_eq!(code.only_show_in, ["GNOME", "KDE"]);
+	assert!(code.shown_in(&["KDE".to_owned()]));
+	assert!(!code.shown_in(&["XFCE".to_owned()]));
+}
+
+#[test]
+fn source_priority_lets_flatpak_shadow_system() {
+	let flatpak = TempDir::new();
+	let system = TempDir::new();
+	flatpak.write("org.gimp.GIMP.desktop", &entry("GIMP (flatpak)", "flatpak run org.gimp.GIMP"));
+	system.write("org.gimp.GIMP.desktop", &entry("GIMP", "gimp %U"));
+	let dirs = || [(Source::System, system.path().to_owned()), (Source::Flatpak, flatpak.path().to_owned())];
+
+	assert_eq!(names(&Installed::from_dirs(dirs())), ["GIMP"]);
+	let flatpak_first = Installed::from_dirs(dirs()).with_source_priority(&[Source::Flatpak, Source::User, Source::System]);
+	assert_eq!(names(&flatpak_first), ["GIMP (flatpak)"]);
+}
+
+#[test]
+fn missing_directories_are_empty_and_unreadable_ones_skipped() {
+	let apps = TempDir::new();
+	apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	let missing = apps.path().join("missing");
+	let not_a_dir = apps.write("not-a-dir", "");
+
+	let installed = Installed::from_dirs([(Source::User, apps.path().to_owned()), (Source::Flatpak, missing.clone())]);
+	assert_eq!(names(&installed), ["Vim"]);
+	assert!(matches!(installed.flatpak(), Err(ReadError::NotFound(path)) if path == missing));
+
+	let installed = Installed::from_dirs([(Source::User, apps.path().to_owned()), (Source::System, not_a_dir.clone())]);
+	assert_eq!(names(&installed), ["Vim"]);
+	let (entries, errors) = installed.all_with_errors();
+	assert_eq!(entries.len(), 1);
+	assert_eq!(errors.len(), 1);
+
+	let unreadable = Installed::from_dirs([(Source::Flatpak, missing), (Source::System, not_a_dir)]);
+	assert!(matches!(unreadable.all(), Err(RunError::Read(ReadError::Io(Source::System, _, _)))));
+}
+
+#[test]
+fn run_pid_returns_the_started_processes() {
+	let apps = TempDir::new();
+	apps.write("true.desktop", &entry("True", "true"));
+	let installed = Installed::from_paths([apps.path().to_owned()]);
+	let pids = Spawn::new("True".to_owned(), None).run_pid(&installed, SpawnMode::Detached).unwrap();
+	assert_eq!(pids.len(), 1);
+	assert_ne!(pids[0], 0);
+}
+
+#[test]
+fn scan_dir_recurses_with_relative_ids() {
+	let apps = TempDir::new();
+	apps.write("vim.desktop", &entry("Vim", "vim %F"));
+	apps.write("kde4/okular.desktop", &entry("Okular", "okular %U"));
+	apps.write("org/gnome/maps.desktop", &entry("Maps", "gnome-maps"));
+	apps.write("org/README", "not an entry");
+	std::os::unix::fs::symlink(apps.path(), apps.path().join("org/gnome/loop")).unwrap();
+
+	let mut ids: Vec<String> = Installed::default().scan_dir(apps.path()).into_iter().map(|app| app.id).collect();
+	ids.sort();
+	assert_eq!(ids, ["kde4-okular.desktop", "org-gnome-maps.desktop", "vim.desktop"]);
+	assert!(Installed::default().scan_dir(&apps.path().join("missing")).is_empty());
+}
+
+#[test]
+fn snap_entries_are_their_own_source() {
+	let snap = TempDir::new();
+	let system = TempDir::new();
+	snap.write("spotify_spotify.desktop", "[Desktop Entry]\nX-SnapInstanceName=spotify\nType=Application\nName=Spotify\n\
+		Exec=env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/spotify_spotify.desktop /snap/bin/spotify %U\n\
+		Icon=/snap/spotify/current/usr/share/spotify/icons/spotify-linux-128.png\n");
+	system.write("htop.desktop", &entry("Htop", "htop"));
+	let installed = Installed::from_dirs([(Source::Snap, snap.path().to_owned()), (Source::System, system.path().to_owned())]);
+
+	assert_eq!(installed.snap().unwrap().into_iter().map(|app| app.name).collect::<Vec<_>>(), ["Spotify"]);
+	assert_eq!(names(&installed), ["Htop", "Spotify"]);
+	assert_eq!(installed.find("spotify").unwrap().id, "spotify_spotify.desktop");
+	let counts = installed.counts();
+	assert!(counts.contains(&(Source::Snap, 1)) && counts.contains(&(Source::System, 1)));
+	assert_eq!(Source::from_name("snap"), Some(Source::Snap));
+}
+
+#[test]
+fn desktop_files_are_read_by_path() {
+	let dir = TempDir::new();
+	let app = dir.write("checkout/myapp.desktop", &(entry("My App", "myapp %f") + "Name[de]=Meine App\n"));
+	let not_utf8 = dir.write("not-utf8.desktop", "");
+	fs::write(&not_utf8, [0xff, 0xfe]).unwrap();
+	let no_exec = dir.write("no-exec.desktop", "[Desktop Entry]\nName=Broken\n");
+	let spawn = Spawn::new("myapp".to_owned(), None);
+
+	let ini = spawn.read_file(&app).unwrap();
+	assert_eq!((ini.id.as_str(), ini.name.as_str(), ini.file.as_deref()), ("myapp.desktop", "My App", Some(app.as_path())));
+	assert_eq!(Spawn::new("myapp".to_owned(), None).with_locale(Some("de_DE.UTF-8".to_owned())).read_file(&app).unwrap().name, "Meine App");
+	let reason = |path: &Path| match spawn.read_file(path) {
+		Err(RunError::InvalidFile(file, e)) if file == path => e,
+		other => panic!("expected InvalidFile, got {other:?}"),
+	};
+	assert!(matches!(reason(&dir.path().join("missing.desktop")), DecodeError::Read(e) if e.kind() == ErrorKind::NotFound));
+	assert!(matches!(reason(&not_utf8), DecodeError::NotUtf8));
+	assert!(matches!(reason(&no_exec), DecodeError::MissingExec));
+}
+
+#[test]
+fn counts_leave_out_entries_another_source_shadows() {
+	let user = TempDir::new();
+	let system = TempDir::new();
+	user.write("firefox.desktop", &entry("Firefox (user)", "firefox --user %u"));
+	system.write("firefox.desktop", &entry("Firefox", "firefox %u"));
+	system.write("vim.desktop", &entry("Vim", "vim %F"));
+	let installed = Installed::from_dirs([(Source::User, user.path().to_owned()), (Source::System, system.path().to_owned())]);
+
+	let counts = installed.counts();
+	assert_eq!(counts, [(Source::User, 1), (Source::Flatpak, 0), (Source::Snap, 0), (Source::System, 1)]);
+	assert_eq!(counts.iter().map(|(_, count)| count).sum::<usize>(), installed.all().unwrap().len());
+}
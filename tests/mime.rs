@@ -0,0 +1,54 @@
+use std::path::Path;
+use tal::{apps::Ini, mime::{MimeApps, MimeGlobs}};
+
+const GLOBS: &str = "# comment\n50:text/plain:*.txt\n50:application/pdf:*.pdf\n60:application/x-compressed-tar:*.tar.gz\n50:application/gzip:*.gz\n50:text/x-csrc:*.c:cs\n50:text/x-c++src:*.C:cs\n";
+
+#[test]
+fn highest_weight_then_longest_glob_wins() {
+	let globs = MimeGlobs::parse(GLOBS);
+	assert_eq!(globs.match_name("backup.tar.gz"), Some("application/x-compressed-tar"));
+	assert_eq!(globs.match_name("notes.gz"), Some("application/gzip"));
+	assert_eq!(globs.match_name("REPORT.PDF"), Some("application/pdf"));
+	assert_eq!(globs.match_name("unknown.xyz"), None);
+}
+
+#[test]
+fn case_sensitive_globs_keep_their_case() {
+	let globs = MimeGlobs::parse(GLOBS);
+	assert_eq!(globs.match_name("main.c"), Some("text/x-csrc"));
+	assert_eq!(globs.match_name("main.C"), Some("text/x-c++src"));
+	assert_eq!(globs.guess(Path::new("/")), Some("inode/directory".to_owned()));
+}
+
+#[test]
+fn handlers_match_exact_types_and_wildcards() {
+	let viewer = Ini { mime_types: vec!["application/pdf".to_owned(), "image/*".to_owned()], ..Default::default() };
+	assert!(viewer.handles("application/pdf"));
+	assert!(viewer.handles("image/png"));
+	assert!(!viewer.handles("application/zip"));
+}
+
+fn handler(id: &str, mime_types: &[&str]) -> Ini {
+	Ini { id: id.to_owned(), name: id.trim_end_matches(".desktop").to_owned(), mime_types: mime_types.iter().map(|t| t.to_string()).collect(), ..Default::default() }
+}
+
+#[test]
+fn defaults_skip_applications_that_are_not_installed() {
+	let entries = [handler("evince.desktop", &["application/pdf"]), handler("okular.desktop", &["application/pdf"])];
+	let user = MimeApps::parse("[Default Applications]\napplication/pdf=gone.desktop;okular.desktop;\n");
+	assert_eq!(user.default_for("application/pdf", &entries).map(|app| app.id.as_str()), Some("okular.desktop"));
+	assert!(user.default_for("text/plain", &entries).is_none());
+
+	let merged = MimeApps::from_files(["[Default Applications]\napplication/pdf=evince.desktop\n".to_owned(), "[Default Applications]\napplication/pdf=okular.desktop\n".to_owned()]);
+	assert_eq!(merged.default_for("application/pdf", &entries).map(|app| app.id.as_str()), Some("evince.desktop"));
+}
+
+#[test]
+fn associations_are_added_and_removed() {
+	let entries = [handler("evince.desktop", &["application/pdf"]), handler("gimp.desktop", &["image/*"]), handler("zathura.desktop", &[])];
+	let apps = MimeApps::parse("# comment\n[Added Associations]\napplication/pdf=zathura.desktop;\n\n[Removed Associations]\napplication/pdf=evince.desktop;\n[Other Group]\napplication/pdf=gimp.desktop\n");
+	let ids = |mime_type: &str| apps.handlers(mime_type, &entries).into_iter().map(|app| app.id.as_str()).collect::<Vec<_>>();
+	assert_eq!(ids("application/pdf"), ["zathura.desktop"]);
+	assert_eq!(ids("image/png"), ["gimp.desktop"]);
+	assert!(MimeApps::default().handlers("application/pdf", &entries).len() == 1);
+}
@@ -0,0 +1,117 @@
+use tal::{apps::{Ini, IniAction}, category, render::{self, JsonArray, Ndjson, Renderer, Text}};
+
+fn firefox() -> Ini {
+	let mut firefox = Ini { id: "firefox.desktop".to_owned(), name: "Firefox".to_owned(), exec: "firefox %u".to_owned(), ..Default::default() };
+	for id in ["private", "new-window"] {
+		firefox.actions.insert(id.to_owned(), IniAction { name: Some(id.to_owned()), exec: Some(format!("firefox --{id}")), terminal: None, path: None });
+	}
+	firefox
+}
+
+fn vim() -> Ini {
+	Ini { id: "vim.desktop".to_owned(), name: "Vim".to_owned(), exec: "vim %F".to_owned(), categories: vec!["Development".to_owned()], ..Default::default() }
+}
+
+#[derive(Default)]
+struct Model(Vec<String>);
+impl Renderer for Model {
+	fn entry(&mut self, ini: &Ini) {
+		self.0.push(format!("entry {}", ini.id));
+	}
+
+	fn action(&mut self, id: &str, _action: &IniAction<String>) {
+		self.0.push(format!("action {id}"));
+	}
+
+	fn category(&mut self, name: &str) {
+		self.0.push(format!("category {name}"));
+	}
+}
+
+#[test]
+fn custom_renderers_receive_entries_then_their_sorted_actions() {
+	let mut model = Model::default();
+	render::render(&mut model, &[firefox(), vim()]);
+	assert_eq!(model.0, ["entry firefox.desktop", "action new-window", "action private", "entry vim.desktop"]);
+
+	let mut model = Model::default();
+	render::render_tree(&mut model, &category::tree(vec![vim()]));
+	let vim_at = model.0.iter().position(|call| call == "entry vim.desktop").unwrap();
+	assert_eq!(model.0[vim_at - 1], "category Development");
+}
+
+fn rendered(renderer: impl FnOnce(&mut Vec<u8>)) -> String {
+	let mut out = Vec::new();
+	renderer(&mut out);
+	String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn provided_renderers_write_text_and_json() {
+	let entries = [firefox(), vim()];
+	assert_eq!(rendered(|out| render::render(&mut Text::new(out, false), &entries)), "Firefox\nVim\n");
+
+	let ndjson = rendered(|out| render::render(&mut Ndjson::new(out), &entries));
+	let lines: Vec<&str> = ndjson.lines().collect();
+	assert_eq!(lines.len(), 2);
+	assert!(lines[0].starts_with(r#"{"id":"firefox.desktop""#));
+	assert!(lines[0].ends_with(r#""actions":[{"id":"new-window","name":"new-window","exec":"firefox --new-window","terminal":null},{"id":"private","name":"private","exec":"firefox --private","terminal":null}]}"#));
+	assert!(lines[1].ends_with(r#""actions":[]}"#));
+
+	let array = rendered(|out| render::render(&mut JsonArray::new(out), &entries));
+	assert_eq!(array.trim_end(), format!("[{}]", lines.join(",")));
+}
+
+#[test]
+fn details_show_raw_or_stripped_exec() {
+	let entries = [firefox()];
+	let raw = rendered(|out| render::render(&mut Text::new(out, true), &entries));
+	assert!(raw.starts_with("Name=Firefox\n\t- Exec=firefox %u\n"));
+	let stripped = rendered(|out| render::render(&mut Text::new(out, true).with_strip_field_codes(true), &entries));
+	assert!(stripped.starts_with("Name=Firefox\n\t- Exec=firefox\n"));
+	assert!(stripped.contains("\t- Exec=firefox --private\n"));
+
+	//Quoting only the shell understands is shown as written, re-quoting the tokens would change the command
+	let shell = Ini { exec: "sh -c 'echo hi' %f".to_owned(), ..vim() };
+	assert!(shell.to_string().contains("\n\t- Exec=sh -c 'echo hi' %f\n"));
+}
+
+#[test]
+fn details_show_optional_keys_only_when_set() {
+	let gimp = Ini {
+		name: "GIMP".to_owned(),
+		generic_name: Some("Image Editor".to_owned()),
+		icon: Some("gimp".to_owned()),
+		exec: "gimp %U".to_owned(),
+		..Default::default()
+	};
+	assert_eq!(gimp.to_string(), "Name=GIMP\n\t- GenericName=Image Editor\n\t- Icon=gimp\n\t- Exec=gimp %U\n\t- Terminal=false");
+	assert_eq!(vim().to_string(), "Name=Vim\n\t- Categories=Development\n\t- Exec=vim %F\n\t- Terminal=false");
+	let overridden = Ini { file: Some("/home/me/.local/share/applications/vim.desktop".into()), ..vim() };
+	assert_eq!(overridden.to_string(), "Name=Vim\n\t- Categories=Development\n\t- Exec=vim %F\n\t- Terminal=false\n\t- File=/home/me/.local/share/applications/vim.desktop");
+	let stripped = rendered(|out| render::render(&mut Text::new(out, true).with_strip_field_codes(true), &[Ini { comment: Some("Edit images".to_owned()), ..gimp }]));
+	assert_eq!(stripped, "Name=GIMP\n\t- GenericName=Image Editor\n\t- Comment=Edit images\n\t- Icon=gimp\n\t- Exec=gimp\n\t- Terminal=false\n");
+	let categorized = Ini { categories: vec!["Graphics".to_owned(), "2DGraphics".to_owned()], ..vim() };
+	assert_eq!(categorized.to_string(), "Name=Vim\n\t- Categories=Graphics;2DGraphics\n\t- Exec=vim %F\n\t- Terminal=false");
+	let decoded = tal::parse_desktop_entry("[Desktop Entry]\nName=Vim\nExec=vim\nCategories=Utility;;TextEditor;\n").unwrap();
+	assert_eq!(decoded.categories, ["Utility", "TextEditor"]);
+}
+
+#[test]
+fn json_details_add_every_key() {
+	let app = Ini { env: vec![("GDK_SCALE".to_owned(), "2".to_owned())], startup_notify: true, ..vim() };
+	let plain = rendered(|out| render::render(&mut JsonArray::new(out), std::slice::from_ref(&app)));
+	assert!(!plain.contains("startup_notify"));
+	let details = rendered(|out| render::render(&mut JsonArray::new(out).with_details(true), &[app]));
+	assert!(details.contains(r#""hidden":false,"dbus_activatable":false,"startup_notify":true,"startup_wm_class":null,"try_exec":null,"path":null,"flatpak":null,"env":{"GDK_SCALE":"2"}"#));
+	assert!(details.starts_with(r#"[{"id":"vim.desktop","name":"Vim""#));
+	assert!(details.ends_with("\"actions\":[]}]\n"));
+}
+
+#[test]
+fn entries_without_actions_have_an_empty_actions_array() {
+	let json = rendered(|out| render::render(&mut JsonArray::new(out), &[vim()]));
+	assert_eq!(json, "[{\"id\":\"vim.desktop\",\"name\":\"Vim\",\"generic_name\":null,\"comment\":null,\"icon\":null,\"exec\":\"vim %F\",\"terminal\":false,\"categories\":[\"Development\"],\"keywords\":[],\"file\":null,\"actions\":[]}]\n");
+	let ndjson = rendered(|out| render::render(&mut Ndjson::new(out).with_details(true), &[vim()]));
+	assert!(ndjson.ends_with(",\"actions\":[]}\n"));
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt};
+use common::TempDir;
+use tal::{apps::{Ini, Spawn, SpawnMode}, startup::StartupId};
+
+#[test]
+fn ids_are_unique_and_timestamped() {
+	let (first, second) = (StartupId::new(), StartupId::new());
+	assert_ne!(first, second);
+	assert!(first.as_str().starts_with("tal-"));
+	assert!(first.as_str().contains(&format!("-{}-", std::process::id())));
+	assert!(first.as_str().rsplit_once("_TIME").is_some_and(|(_, millis)| millis.parse::<u128>().is_ok()));
+	assert!(!first.as_str().contains(char::is_whitespace));
+}
+
+#[test]
+fn remove_message_quotes_the_id() {
+	assert_eq!(StartupId::from_string("tal-host-1-0_TIME5".to_owned()).remove_message(), r#"remove: ID="tal-host-1-0_TIME5""#);
+	assert_eq!(StartupId::from_string(r#"odd "id"\"#.to_owned()).remove_message(), r#"remove: ID="odd \"id\"\\""#);
+}
+
+#[test]
+fn only_startup_notify_entries_get_an_id() {
+	let dir = TempDir::new();
+	let script = dir.write("print-id", "#!/bin/sh\nprintf '%s' \"$DESKTOP_STARTUP_ID\" > \"$1\"\n");
+	fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+	let out = dir.path().join("id");
+	let launch = |startup_notify: bool, id: Option<StartupId>| {
+		let app = Ini { name: "Print".to_owned(), exec: format!("{} %f", script.display()), startup_notify, ..Default::default() };
+		let spawn = Spawn::new("Print".to_owned(), None).with_args(vec![out.display().to_string()]).with_startup_id(id);
+		spawn.spawn(&app, SpawnMode::Detached).unwrap().into_iter().for_each(|mut child| { child.wait().unwrap(); });
+		fs::read_to_string(&out).unwrap()
+	};
+
+	assert_eq!(launch(true, Some(StartupId::from_string("given_TIME1".to_owned()))), "given_TIME1");
+	assert!(launch(true, None).starts_with("tal-"));
+	assert_eq!(launch(false, Some(StartupId::from_string("given_TIME1".to_owned()))), "");
+}
@@ -0,0 +1,19 @@
+use std::time::{Duration, UNIX_EPOCH};
+use tal::time::parse_rfc3339;
+
+#[test]
+fn timestamps_convert_to_epoch_time() {
+	assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z").unwrap(), UNIX_EPOCH);
+	assert_eq!(parse_rfc3339("2024-01-01T00:00:00Z").unwrap(), UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+	assert_eq!(parse_rfc3339("2024-01-01T02:00:00.25+02:00").unwrap(), UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+	assert_eq!(parse_rfc3339("2023-12-31t19:00:00-05:00").unwrap(), UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+	assert_eq!(parse_rfc3339("1969-12-31T23:59:00Z").unwrap(), UNIX_EPOCH - Duration::from_secs(60));
+}
+
+#[test]
+fn malformed_timestamps_are_rejected() {
+	for timestamp in ["2024-01-01", "2024-01-01T00:00:00", "2024-13-01T00:00:00Z", "2023-02-29T00:00:00Z", "2024-01-01T24:00:00Z", "24-01-01T00:00:00Z", "2024-01-01T00:00:00+2:00"] {
+		assert!(parse_rfc3339(timestamp).is_err(), "{timestamp} parsed");
+	}
+	assert!(parse_rfc3339("2024-02-29T00:00:00Z").is_ok());
+}
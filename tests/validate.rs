@@ -0,0 +1,50 @@
+use tal::validate::{Severity, Validator, Violation, validate};
+
+fn errors(violations: &[Violation]) -> Vec<&str> {
+	violations.iter().filter(|v| v.severity == Severity::Error).map(|v| v.message.as_str()).collect()
+}
+
+#[test]
+fn valid_entry_has_no_violations() {
+	let contents = "[Desktop Entry]\nType=Application\nName=Vim\nExec=vim %F\nTerminal=true\nCategories=Utility;TextEditor;\n\n[Desktop Action new]\nName=New\nExec=vim\n";
+	assert_eq!(validate(contents), []);
+}
+
+#[test]
+fn missing_required_keys_are_errors() {
+	assert_eq!(errors(&validate("[Desktop Entry]\nType=Application\n")), [
+		"Required key Name is missing",
+		"Applications need an Exec key unless DBusActivatable=true",
+	]);
+	assert!(validate("[Desktop Entry]\nType=Application\nName=Portal\nDBusActivatable=true\n").is_empty());
+}
+
+#[test]
+fn line_problems_carry_their_line() {
+	let violations = validate("[Desktop Entry]\nType=Application\nName=Vim\nExec=vim %x %d\nTerminal=yes\nKeywords=edit\n");
+	let found: Vec<(Severity, Option<usize>)> = violations.iter().map(|v| (v.severity, v.line)).collect();
+	assert_eq!(found, [
+		(Severity::Error, Some(4)),
+		(Severity::Warning, Some(4)),
+		(Severity::Error, Some(5)),
+		(Severity::Warning, Some(6)),
+	]);
+}
+
+#[test]
+fn first_group_must_be_the_desktop_entry() {
+	let violations = validate("[X-Extra]\nFoo=bar\n[Desktop Entry]\nType=Application\nName=A\nExec=a\n");
+	assert_eq!(errors(&violations), ["The first group must be [Desktop Entry], found [X-Extra]"]);
+}
+
+#[test]
+fn only_configured_booleans_are_strict() {
+	let contents = "[Desktop Entry]\nType=Application\nName=A\nExec=a\nNoDisplay=yes\nX-Vendor-Flag=On\n";
+	assert_eq!(errors(&validate(contents)), [r#"NoDisplay="yes" is not a boolean"#]);
+
+	let terminal_only = Validator::new(vec!["Terminal".to_owned()]);
+	assert!(terminal_only.validate(contents).is_empty());
+
+	let vendor = Validator::new(vec!["X-Vendor-Flag".to_owned()]);
+	assert_eq!(errors(&vendor.validate(contents)), [r#"X-Vendor-Flag="On" is not a boolean"#]);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::{ffi::OsStr, path::{Path, PathBuf}};
+use tal::{apps::{Installed, Source}, xdg::{resolve_data_dirs, resolve_data_home}};
+
+fn paths(dirs: &[&str]) -> Vec<PathBuf> {
+	dirs.iter().map(PathBuf::from).collect()
+}
+
+#[test]
+fn data_dirs_fall_back_when_unset_or_unusable() {
+	assert_eq!(resolve_data_dirs(None), paths(&["/usr/local/share", "/usr/share"]));
+	assert_eq!(resolve_data_dirs(Some(OsStr::new(""))), paths(&["/usr/local/share", "/usr/share"]));
+	assert_eq!(resolve_data_dirs(Some(OsStr::new("relative/share"))), paths(&["/usr/local/share", "/usr/share"]));
+	assert_eq!(
+		resolve_data_dirs(Some(OsStr::new("/run/current-system/sw/share::relative:/usr/share"))),
+		paths(&["/run/current-system/sw/share", "/usr/share"])
+	);
+}
+
+#[test]
+fn data_home_falls_back_to_local_share() {
+	let home = Some(Path::new("/home/me"));
+	assert_eq!(resolve_data_home(Some(OsStr::new("/data")), home), Some(PathBuf::from("/data")));
+	assert_eq!(resolve_data_home(Some(OsStr::new("")), home), Some(PathBuf::from("/home/me/.local/share")));
+	assert_eq!(resolve_data_home(None, home), Some(PathBuf::from("/home/me/.local/share")));
+	assert_eq!(resolve_data_home(None, None), None);
+}
+
+#[test]
+fn applications_dirs_follow_the_data_dirs() {
+	let data_dirs = paths(&["/run/current-system/sw/share", "/var/lib/flatpak/exports/share", "/var/lib/snapd/desktop", "/opt/snapd/desktop", "/usr/share"]);
+	let dirs = Installed::resolve_dirs(Some(Path::new("/home/me/.local/share")), &data_dirs);
+	assert_eq!(dirs, [
+		(Source::User, PathBuf::from("/home/me/.local/share/applications")),
+		(Source::Flatpak, PathBuf::from("/home/me/.local/share/flatpak/exports/share/applications")),
+		(Source::Flatpak, PathBuf::from("/var/lib/flatpak/exports/share/applications")),
+		(Source::Snap, PathBuf::from("/var/lib/snapd/desktop/applications")),
+		(Source::System, PathBuf::from("/run/current-system/sw/share/applications")),
+		(Source::Snap, PathBuf::from("/opt/snapd/desktop/applications")),
+		(Source::System, PathBuf::from("/usr/share/applications")),
+	]);
+}
+
+#[test]
+fn missing_data_dirs_are_skipped() {
+	let data_home = common::TempDir::new();
+	let data_dir = common::TempDir::new();
+	data_home.write("applications/vim.desktop", &common::entry("Vim", "vim %F"));
+	data_dir.write("applications/htop.desktop", &common::entry("Htop", "htop"));
+
+	let data_dirs = [data_dir.path().join("missing"), data_dir.path().to_owned()];
+	//Leaves out the system flatpak and snap directories, which the host may have
+	let dirs = Installed::resolve_dirs(Some(data_home.path()), &data_dirs).into_iter()
+		.filter(|(source, _)| ![Source::Flatpak, Source::Snap].contains(source));
+	let installed = Installed::from_dirs(dirs);
+	let mut names: Vec<String> = installed.all().expect("scan failed").into_iter().map(|app| app.name).collect();
+	names.sort();
+	assert_eq!(names, ["Htop", "Vim"]);
+	assert_eq!(installed.system().unwrap().len(), 1);
+}